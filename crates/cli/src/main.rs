@@ -1,51 +1,376 @@
-use lib::{FontBackend, FontOptions, FontVerseion, OpentypeTtfBackend, Workspace};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use clap::{Parser, Subcommand};
+use lib::{
+    coverage::Charset,
+    font::Font,
+    import::{
+        read_bdf, read_hex, read_outline_font, read_pixel_font, ImportedFont, RasterizeOptions,
+    },
+    BackendRegistry, BuildReporter, BuildTarget, Diagnostic, FontStyle, OutlineSettings, OutputDir,
+    Progress, Project, TargetContext, Workspace,
+};
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use yaff::SemanticGlyphLabel;
+
+#[derive(Parser)]
+struct Args {
+    /// Workspace directory, searched from the current directory upwards if omitted.
+    #[arg(long)]
+    workspace: Option<PathBuf>,
+    /// Build twice and fail unless both builds are byte-identical.
+    #[arg(long)]
+    check_reproducible: bool,
+    /// Compile every glyph again instead of reusing the ones in `.studio/cache`.
+    #[arg(long)]
+    no_cache: bool,
+    /// Number of threads to build with, one per core if omitted.
+    #[arg(short, long)]
+    jobs: Option<usize>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Import a font of another format as YAFF files of a project.
+    Import {
+        /// Font to import, whose format is told by its extension.
+        font: PathBuf,
+        /// Project directory to write into, created if missing.
+        project: PathBuf,
+        /// Pixels per em to render TrueType and OpenType fonts at.
+        #[arg(long, default_value_t = 12)]
+        ppem: u16,
+        /// Characters to render from TrueType and OpenType fonts, like `U+0020..U+007E`
+        /// or literal characters, Latin-1 if omitted.
+        #[arg(long)]
+        chars: Option<String>,
+        /// Coverage from 0 to 1 from which a rendered pixel is inked.
+        #[arg(long, default_value_t = 0.5)]
+        threshold: f32,
+        /// Render coverage as the 100%, 75%, 50% and 25% opacities of the palette.
+        #[arg(long)]
+        grayscale: bool,
+        /// Recover the pixels of TrueType and OpenType fonts made of squares instead of
+        /// rendering them, keeping every character of `cmap`.
+        #[arg(long)]
+        devectorize: bool,
+        /// Size of a pixel in font units when devectorizing, found from the outlines if omitted.
+        #[arg(long, requires = "devectorize")]
+        grid: Option<u16>,
+    },
+}
 
 fn main() -> eyre::Result<()> {
     color_eyre::install()?;
-    let workspace = Workspace::load("./examples/bitkodi")?;
-    let doc = &workspace.projects[0].files[0].document;
-
-    let mut builder = OpentypeTtfBackend::new(FontOptions {
-        copyright_notice: None,
-        family_name: "Bitkodi".to_string(),
-        sub_family_name: "Regular".to_string(),
-        version: FontVerseion::new(1, 0).unwrap(),
-        unique_id: "bitkodi-test".to_owned(),
-        full_font_name: None,
-        postscript_name: None,
-        height: 8,
-
-        ascender: 5,
-        descender: 1,
-    })?;
-    for glyph in doc.list_glyph() {
-        println!();
-        println!(
-            "{}",
-            glyph
+    let args = Args::parse();
+    if let Some(jobs) = args.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()?;
+    }
+    if let Some(Command::Import {
+        font,
+        project,
+        ppem,
+        chars,
+        threshold,
+        grayscale,
+        devectorize,
+        grid,
+    }) = &args.command
+    {
+        if *devectorize {
+            return import(font, project, |font| Ok(read_pixel_font(font, *grid)?));
+        }
+        let chars = match chars {
+            Some(chars) => Charset::parse("chars", chars)?,
+            None => Charset::latin_1(),
+        };
+        let options = RasterizeOptions {
+            ppem: *ppem,
+            chars: chars.chars,
+            threshold: *threshold,
+            grayscale: *grayscale,
+        };
+        return import(font, project, |font| Ok(read_outline_font(font, &options)?));
+    }
+    let workspace_path = match args.workspace {
+        Some(path) => path,
+        None => Workspace::discover(std::env::current_dir()?)
+            .ok_or_else(|| eyre::eyre!("could not find workspace.toml in any parent directory"))?,
+    };
+    let (workspace, diagnostics) = Workspace::load(workspace_path);
+    for diagnostic in &diagnostics {
+        eprintln!("{diagnostic}");
+    }
+    if diagnostics.iter().any(|diagnostic| diagnostic.is_error()) {
+        eyre::bail!("failed to load workspace");
+    }
+    for project in &workspace.projects {
+        for glyph in project.list_glyph() {
+            println!();
+            println!(
+                "{}",
+                glyph
+                    .labels
+                    .iter()
+                    .flat_map(|label| label.to_semantic().map(|s| s.to_string()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            let provenance = glyph
                 .labels
                 .iter()
-                .flat_map(|label| label.to_semantic().map(|s| s.to_string()))
-                .collect::<Vec<_>>()
-                .join(", ")
-        );
-        let Some(value) = &glyph.value else {
-            continue;
-        };
-        for row in &value.data {
-            for col in row {
-                if let Some(col) = col {
-                    print!("{:x} ", col.value())
-                } else {
-                    print!(". ");
+                .find_map(|label| match label.to_semantic()? {
+                    SemanticGlyphLabel::CharSequence(vec) if vec.len() == 1 => {
+                        project.find_glyph(vec[0])
+                    }
+                    _ => None,
+                });
+            if let Some(provenance) = provenance {
+                println!("defined at {provenance}");
+            }
+            let Some(value) = &glyph.value else {
+                continue;
+            };
+            for row in &value.data {
+                for col in row {
+                    if let Some(col) = col {
+                        print!("{:x} ", col.value())
+                    } else {
+                        print!(". ");
+                    }
                 }
+                println!();
             }
             println!();
         }
-        println!();
-        builder.add_glyph(glyph);
     }
-    builder.build_to("./examples/bitkodi/dist")?;
+
+    let dist = workspace.path.join("dist");
+    let cache_dir = (!args.no_cache).then(|| workspace.path.join(".studio").join("cache"));
+    let out = OutputDir::open(&dist)?;
+    build_workspace(&workspace, &out, cache_dir.as_deref())?;
+    let artifacts = out.finish()?;
+
+    if args.check_reproducible {
+        for project in &workspace.projects {
+            if project.manifest.build.timestamp()?.is_none() {
+                eyre::bail!(
+                    "builds of {} are stamped with the current time, \
+                     set SOURCE_DATE_EPOCH or `source-date` in [build] to check reproducibility",
+                    project.path.display()
+                );
+            }
+        }
+        let check_dir = ScratchDir(env::temp_dir().join(format!("studio-check-{}", process::id())));
+        let out = OutputDir::open(&check_dir.0)?;
+        // built from scratch, so a stale cache shows up as a difference as well.
+        build_workspace(&workspace, &out, None)?;
+        let check_artifacts = out.finish()?;
+        let first = hash_artifacts(&dist, &artifacts)?;
+        let second = hash_artifacts(&check_dir.0, &check_artifacts)?;
+        for (name, hash) in &first {
+            println!("{hash}  {name}");
+        }
+        if first != second {
+            eyre::bail!("two builds of the same source differ");
+        }
+        println!("reproducible: two builds are identical");
+    }
     println!("ok, written well");
     Ok(())
 }
+
+/// Imports `font` as YAFF files of `project`, reading TrueType and OpenType fonts
+/// with `read_outline`.
+fn import(
+    font: &Path,
+    project: &Path,
+    read_outline: impl FnOnce(&Path) -> eyre::Result<(ImportedFont, Vec<Diagnostic>)>,
+) -> eyre::Result<()> {
+    let extension = font
+        .extension()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_ascii_lowercase();
+    let (imported, diagnostics): (ImportedFont, Vec<Diagnostic>) = match extension.as_str() {
+        "bdf" => read_bdf(font)?,
+        "hex" => read_hex(font)?,
+        "ttf" | "otf" => read_outline(font)?,
+        _ => eyre::bail!(
+            "cannot import `.{extension}` files, expected `.bdf`, `.hex`, `.ttf` or `.otf`"
+        ),
+    };
+    for diagnostic in &diagnostics {
+        eprintln!("{diagnostic}");
+    }
+    let file_stem = font.file_stem().unwrap_or_default().to_string_lossy();
+    for path in imported.write_project(project, &file_stem)? {
+        println!("wrote {}", path.display());
+    }
+    Ok(())
+}
+
+/// Builds every target of every font of every project in parallel.
+/// Errors are reported in the order of projects and fonts, whichever thread hits one first.
+fn build_workspace(
+    workspace: &Workspace,
+    out: &OutputDir,
+    cache_dir: Option<&Path>,
+) -> eyre::Result<()> {
+    let registry = BackendRegistry::builtin();
+    let projects = workspace
+        .projects
+        .iter()
+        .map(|project| {
+            let targets = project.build_targets();
+            // catch unknown formats before building anything.
+            for target in &targets {
+                registry.get(&target.format)?;
+            }
+            Ok((project, font_variants(project), targets))
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+    let mut jobs = Vec::new();
+    for (project, variants, targets) in &projects {
+        for variant in variants {
+            for target in targets {
+                if variant.outline_only && !registry.get(&target.format)?.has_outlines {
+                    continue;
+                }
+                jobs.push((*project, variant, target));
+            }
+        }
+    }
+    let results: Vec<_> = jobs
+        .par_iter()
+        .map(|(project, variant, target)| {
+            build_font(&registry, project, out, variant, target, cache_dir)
+        })
+        .collect();
+    results.into_iter().collect()
+}
+
+/// A font to build from a project.
+struct FontVariant {
+    sub_family_name: String,
+    outline: OutlineSettings,
+    style: FontStyle,
+    /// Differs from Regular only in pixel shape, so formats without outlines skip it.
+    outline_only: bool,
+}
+
+/// Regular, then the synthesized styles, then the outline styles.
+fn font_variants(project: &Project) -> Vec<FontVariant> {
+    let outline = &project.manifest.outline;
+    let mut variants = vec![FontVariant {
+        sub_family_name: "Regular".to_owned(),
+        outline: outline.clone(),
+        style: FontStyle::default(),
+        outline_only: false,
+    }];
+    for (sub_family_name, style) in project.manifest.synthesis.styles() {
+        variants.push(FontVariant {
+            sub_family_name: sub_family_name.to_owned(),
+            outline: outline.clone(),
+            style,
+            outline_only: false,
+        });
+    }
+    for (sub_family_name, shape) in &outline.styles {
+        variants.push(FontVariant {
+            sub_family_name: sub_family_name.clone(),
+            outline: OutlineSettings {
+                shape: *shape,
+                ..outline.clone()
+            },
+            style: FontStyle::default(),
+            outline_only: true,
+        });
+    }
+    variants
+}
+
+/// Directory removed once dropped, so a failed build leaves nothing behind.
+struct ScratchDir(PathBuf);
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        // nothing to do about it if it fails, the directory is in the temporary directory anyway.
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// SHA-256 of each of `artifacts` inside `dir` in hex, keyed by file name,
+/// which can be compared across machines and releases.
+/// Other files of the directory, which the build did not write, are left out.
+fn hash_artifacts(
+    dir: &Path,
+    artifacts: &BTreeSet<String>,
+) -> eyre::Result<BTreeMap<String, String>> {
+    let mut hashes = BTreeMap::new();
+    for name in artifacts {
+        let digest = Sha256::digest(fs::read(dir.join(name))?);
+        let hash = digest.iter().map(|byte| format!("{byte:02x}")).collect();
+        hashes.insert(name.clone(), hash);
+    }
+    Ok(hashes)
+}
+
+fn build_font(
+    registry: &BackendRegistry,
+    project: &Project,
+    out: &OutputDir,
+    variant: &FontVariant,
+    target: &BuildTarget,
+    cache_dir: Option<&Path>,
+) -> eyre::Result<()> {
+    let font = Font::from_project(project, &variant.sub_family_name, variant.style)?;
+    let backend = registry.create(
+        target,
+        &TargetContext {
+            file_name: target
+                .file_name
+                .clone()
+                .unwrap_or_else(|| project.manifest.build.file_name.clone()),
+            outline: &variant.outline,
+            cache_dir,
+        },
+    )?;
+    let artifacts = backend
+        .build_any(&font, &StderrReporter)
+        .map_err(|e| eyre::eyre!(e))?;
+    out.write_artifacts(&artifacts)?;
+    Ok(())
+}
+
+/// Prints diagnostics and notes as they come, and each stage of a font once it is complete.
+struct StderrReporter;
+
+impl BuildReporter for StderrReporter {
+    fn progress(&self, progress: Progress<'_>) {
+        if progress.done == progress.total {
+            eprintln!(
+                "{}: {} {}/{}",
+                progress.font, progress.stage, progress.done, progress.total
+            );
+        }
+    }
+
+    fn diagnostic(&self, diagnostic: Diagnostic) {
+        eprintln!("{diagnostic}");
+    }
+
+    fn info(&self, font: &str, message: &str) {
+        eprintln!("{font}: {message}");
+    }
+}
@@ -0,0 +1,315 @@
+use std::fmt::Write;
+
+use snafu::prelude::*;
+
+use crate::{
+    diagnostic::{Diagnostic, DiagnosticCode},
+    font::{Font, Glyph, InkBox},
+};
+
+use super::{
+    xlfd::{glyph_name, unicode_glyph_name, Xlfd, XlfdValue},
+    Artifact, BuildReporter, FileNameTemplate, FontBackend, Progress,
+};
+
+/// Builds BDF 2.1 fonts, which X11, Linux console tools and embedded toolchains take.
+///
+/// Every inked pixel is set regardless of its color.
+/// A glyph mapped to several codepoints is written once for each,
+/// and a glyph mapped to none is written with `ENCODING -1`.
+pub struct BdfBackend {
+    options: BdfOptions,
+}
+
+#[derive(Debug, Clone)]
+pub struct BdfOptions {
+    /// Name of the output file, which `.bdf` is appended to.
+    pub file_name: FileNameTemplate,
+    /// `FOUNDRY` of the XLFD name, left empty if `None`.
+    pub foundry: Option<String>,
+    /// Resolution in dots per inch, which the point size is derived from.
+    pub resolution: u16,
+}
+
+impl Default for BdfOptions {
+    fn default() -> Self {
+        BdfOptions {
+            file_name: FileNameTemplate::default(),
+            foundry: None,
+            resolution: 72,
+        }
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum BdfBuildError {
+    #[snafu(display("font height must not be zero"))]
+    FontHeightZero,
+    #[snafu(display("resolution must not be zero"))]
+    ResolutionZero,
+}
+
+impl BdfBackend {
+    pub fn new(options: BdfOptions) -> Self {
+        BdfBackend { options }
+    }
+}
+
+impl FontBackend for BdfBackend {
+    type Err = BdfBuildError;
+
+    fn build(&self, font: &Font, reporter: &dyn BuildReporter) -> Result<Vec<Artifact>, Self::Err> {
+        ensure!(font.options.height > 0, FontHeightZeroSnafu);
+        ensure!(self.options.resolution > 0, ResolutionZeroSnafu);
+        let file_name = self.options.file_name.render(&font.options, "bdf");
+        for sequence in font.features.ligatures.keys() {
+            reporter.diagnostic(Diagnostic::warning(
+                DiagnosticCode::UnsupportedFeature,
+                &file_name,
+                None,
+                format!(
+                    "ligature {:?} is written unencoded, BDF has no ligatures",
+                    sequence.iter().collect::<String>()
+                ),
+            ));
+        }
+        let bdf = BdfWriter {
+            font,
+            options: &self.options,
+        }
+        .write();
+        reporter.progress(Progress {
+            font: &font.options.full_name(),
+            stage: "writing glyphs",
+            done: font.glyphs.len(),
+            total: font.glyphs.len(),
+        });
+        Ok(vec![Artifact {
+            file_name,
+            mime_type: "application/x-font-bdf",
+            bytes: bdf.into_bytes(),
+        }])
+    }
+}
+
+struct BdfWriter<'a> {
+    font: &'a Font,
+    options: &'a BdfOptions,
+}
+
+/// A `STARTCHAR` entry, of which a glyph has one per codepoint.
+struct BdfChar<'a> {
+    name: String,
+    encoding: Option<char>,
+    glyph: &'a Glyph,
+    ink: Option<InkBox>,
+}
+
+impl BdfWriter<'_> {
+    fn write(&self) -> String {
+        let chars = self.chars();
+        let mut out = String::new();
+        // writing into a `String` never fails.
+        self.write_header(&mut out, &chars).unwrap();
+        for char in &chars {
+            self.write_char(&mut out, char).unwrap();
+        }
+        out.push_str("ENDFONT\n");
+        out
+    }
+
+    /// Encoded glyphs in codepoint order, then unencoded ones in glyph order.
+    fn chars(&self) -> Vec<BdfChar<'_>> {
+        let font = self.font;
+        let mut chars: Vec<_> = font
+            .cmap
+            .iter()
+            .filter_map(|(&ch, &id)| {
+                let glyph = font.glyph(id)?;
+                Some(BdfChar {
+                    name: unicode_glyph_name(ch),
+                    encoding: Some(ch),
+                    glyph,
+                    ink: font.ink_box(glyph),
+                })
+            })
+            .collect();
+        for glyph in &font.glyphs {
+            if font.codepoints(glyph.id).next().is_none() {
+                chars.push(BdfChar {
+                    name: glyph_name(font, glyph),
+                    encoding: None,
+                    glyph,
+                    ink: font.ink_box(glyph),
+                });
+            }
+        }
+        chars
+    }
+
+    fn write_header(&self, out: &mut String, chars: &[BdfChar]) -> std::fmt::Result {
+        let resolution = self.options.resolution;
+        let xlfd = Xlfd::new(self.font, self.options.foundry.as_deref(), resolution);
+        let (x, y, width, height) = self.font_bounding_box(chars);
+        writeln!(out, "STARTFONT 2.1")?;
+        writeln!(out, "FONT {}", xlfd.name)?;
+        writeln!(
+            out,
+            "SIZE {} {resolution} {resolution}",
+            (xlfd.point_size + 5) / 10
+        )?;
+        writeln!(out, "FONTBOUNDINGBOX {width} {height} {x} {y}")?;
+        writeln!(out, "STARTPROPERTIES {}", xlfd.properties.len())?;
+        for (name, value) in &xlfd.properties {
+            match value {
+                XlfdValue::String(value) => writeln!(out, "{name} {}", quote(value))?,
+                XlfdValue::Integer(value) => writeln!(out, "{name} {value}")?,
+            }
+        }
+        writeln!(out, "ENDPROPERTIES")?;
+        writeln!(out, "CHARS {}", chars.len())?;
+        Ok(())
+    }
+
+    /// Union of the ink of every glyph as `(x, y, width, height)`.
+    fn font_bounding_box(&self, chars: &[BdfChar]) -> (i32, i32, usize, usize) {
+        let boxes = chars.iter().filter_map(|char| char.ink);
+        let Some((left, bottom, right, top)) = boxes
+            .map(|ink| {
+                (
+                    ink.x,
+                    ink.y,
+                    ink.x + ink.width as i32,
+                    ink.y + ink.height as i32,
+                )
+            })
+            .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))
+        else {
+            return (0, -(self.font.options.descender as i32), 0, 0);
+        };
+        (
+            left,
+            bottom,
+            (right - left) as usize,
+            (top - bottom) as usize,
+        )
+    }
+
+    fn write_char(&self, out: &mut String, char: &BdfChar) -> std::fmt::Result {
+        let advance = char.glyph.metrics.advance;
+        // the scalable width is in 1/1000 of the point size, which is the pixel size
+        // at the resolution the font is made for.
+        let pixel_size = self.font.options.height as usize;
+        let swidth = (advance * 1000 + pixel_size / 2) / pixel_size;
+        writeln!(out, "STARTCHAR {}", char.name)?;
+        match char.encoding {
+            Some(ch) => writeln!(out, "ENCODING {}", ch as u32)?,
+            None => writeln!(out, "ENCODING -1")?,
+        }
+        writeln!(out, "SWIDTH {swidth} 0")?;
+        writeln!(out, "DWIDTH {advance} 0")?;
+        let Some(ink) = char.ink else {
+            writeln!(out, "BBX 0 0 0 0")?;
+            writeln!(out, "BITMAP")?;
+            return writeln!(out, "ENDCHAR");
+        };
+        writeln!(out, "BBX {} {} {} {}", ink.width, ink.height, ink.x, ink.y)?;
+        writeln!(out, "BITMAP")?;
+        for row in ink.row..ink.row + ink.height {
+            // rows are padded to whole bytes, most significant bit first.
+            let mut bytes = vec![0u8; ink.width.div_ceil(8)];
+            for col in 0..ink.width {
+                if char.glyph.is_inked(row, ink.col + col) {
+                    bytes[col / 8] |= 0x80 >> (col % 8);
+                }
+            }
+            for byte in bytes {
+                write!(out, "{byte:02X}")?;
+            }
+            writeln!(out)?;
+        }
+        writeln!(out, "ENDCHAR")
+    }
+}
+
+/// String property value, with quotes doubled as BDF escapes them.
+fn quote(value: &str) -> String {
+    format!(
+        "\"{}\"",
+        value.replace('"', "\"\"").replace(['\r', '\n'], " ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backend::{CollectingReporter, FontStyle},
+        font::test_font,
+    };
+
+    use super::*;
+
+    const SOURCE: &str = "'A':\n  .@.\n  @.@\n  @@@\n  ...\n\n\
+                          'j':\n  ..@\n  ...\n  ..@\n  @@.\n\n\
+                          \"dot\":\n  ...\n  .@.\n  ...\n  ...\n";
+
+    fn build(style: FontStyle) -> (String, CollectingReporter) {
+        let font = test_font(SOURCE, 4, 1, style);
+        let reporter = CollectingReporter::default();
+        let artifacts = BdfBackend::new(BdfOptions::default())
+            .build(&font, &reporter)
+            .unwrap();
+        (
+            String::from_utf8(artifacts[0].bytes.clone()).unwrap(),
+            reporter,
+        )
+    }
+
+    #[test]
+    fn writes_glyphs_cropped_to_ink() {
+        let (bdf, reporter) = build(FontStyle::default());
+        assert!(reporter.codes().is_empty());
+        assert!(bdf.starts_with(
+            "STARTFONT 2.1\n\
+             FONT --Test-Medium-R-Normal--4-40-72-72-M-30-ISO10646-1\n\
+             SIZE 4 72 72\n\
+             FONTBOUNDINGBOX 3 4 0 -1\n\
+             STARTPROPERTIES 19\n"
+        ));
+        let glyphs = &bdf[bdf.find("\nCHARS ").unwrap() + 1..];
+        assert_eq!(
+            glyphs,
+            "CHARS 3\n\
+             STARTCHAR uni0041\nENCODING 65\nSWIDTH 750 0\nDWIDTH 3 0\nBBX 3 3 0 0\n\
+             BITMAP\n40\nA0\nE0\nENDCHAR\n\
+             STARTCHAR uni006A\nENCODING 106\nSWIDTH 750 0\nDWIDTH 3 0\nBBX 3 4 0 -1\n\
+             BITMAP\n20\n00\n20\nC0\nENDCHAR\n\
+             STARTCHAR dot\nENCODING -1\nSWIDTH 750 0\nDWIDTH 3 0\nBBX 1 1 1 1\n\
+             BITMAP\n80\nENDCHAR\n\
+             ENDFONT\n"
+        );
+    }
+
+    #[test]
+    fn names_synthesized_styles() {
+        let (bdf, _) = build(FontStyle {
+            embolden: 1,
+            oblique: Some(2),
+        });
+        for line in [
+            "FONT --Test-Bold-O-Normal--4-40-72-72-M-40-ISO10646-1",
+            "WEIGHT_NAME \"Bold\"",
+            "RELATIVE_WEIGHT 70",
+            "SLANT \"O\"",
+            "FACE_NAME \"Test Bold Oblique\"",
+            "DWIDTH 4 0",
+        ] {
+            assert!(bdf.lines().any(|l| l == line), "no `{line}` in\n{bdf}");
+        }
+    }
+
+    #[test]
+    fn quotes_strings() {
+        assert_eq!(quote("say \"hi\"\nnow"), "\"say \"\"hi\"\" now\"");
+    }
+}
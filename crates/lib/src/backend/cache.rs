@@ -0,0 +1,309 @@
+use std::{
+    collections::HashMap,
+    fs,
+    hash::Hasher,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    diagnostic::{Diagnostic, DiagnosticCode},
+    glyph::{BitmapMatrix, DiagonalRule, PixelShape},
+};
+
+use super::output::write_atomic;
+
+const MAGIC: &[u8; 8] = b"STUDIOGC";
+/// Bumped whenever the layout of the file or of any cached value changes.
+const FORMAT_VERSION: u32 = 1;
+
+/// Compiled glyphs of one font from the previous build, keyed by a hash of everything
+/// the compiled glyph depends on.
+///
+/// Values are opaque bytes encoded by the backend. A change to any input yields another key,
+/// so stale entries are never hit; they are dropped when the cache is saved, which keeps only
+/// the entries used by the current build.
+/// The cache is tied to the version of this crate, since tracing itself may change between them.
+pub(crate) struct GlyphCache {
+    path: Option<PathBuf>,
+    previous: HashMap<u64, Vec<u8>>,
+    current: HashMap<u64, Vec<u8>>,
+    hits: usize,
+    misses: usize,
+}
+
+impl GlyphCache {
+    /// Loads the cache file `name` in `dir`, or starts an empty cache if it is missing or broken.
+    /// A `None` directory disables caching.
+    pub(crate) fn load(dir: Option<&Path>, name: &str) -> (GlyphCache, Option<Diagnostic>) {
+        let path = dir.map(|dir| dir.join(name));
+        let mut diagnostic = None;
+        let previous = match &path {
+            Some(path) => match fs::read(path) {
+                Ok(bytes) => decode(&bytes).unwrap_or_else(|| {
+                    diagnostic = Some(Diagnostic::warning(
+                        DiagnosticCode::GlyphCache,
+                        path,
+                        None,
+                        "ignoring glyph cache of another version",
+                    ));
+                    HashMap::new()
+                }),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+                Err(e) => {
+                    diagnostic = Some(Diagnostic::warning(
+                        DiagnosticCode::GlyphCache,
+                        path,
+                        None,
+                        format!("ignoring unreadable glyph cache: {e}"),
+                    ));
+                    HashMap::new()
+                }
+            },
+            None => HashMap::new(),
+        };
+        let cache = GlyphCache {
+            path,
+            previous,
+            current: HashMap::new(),
+            hits: 0,
+            misses: 0,
+        };
+        (cache, diagnostic)
+    }
+
+    /// Returns the value stored for `key`, keeping it for the next build as well.
+    pub(crate) fn get(&mut self, key: u64) -> Option<&[u8]> {
+        if !self.current.contains_key(&key) {
+            let value = self.previous.remove(&key)?;
+            self.hits += 1;
+            self.current.insert(key, value);
+        }
+        self.current.get(&key).map(Vec::as_slice)
+    }
+
+    /// Stores a value computed after [`GlyphCache::get`] missed.
+    pub(crate) fn insert(&mut self, key: u64, value: Vec<u8>) {
+        self.misses += 1;
+        self.current.insert(key, value);
+    }
+
+    /// Number of [`GlyphCache::get`] calls served from the previous build.
+    pub(crate) fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Writes the entries used by this build back, unless nothing has changed.
+    /// A failure only costs the next build its cache, so it is a warning.
+    pub(crate) fn save(self) -> Option<Diagnostic> {
+        let path = self.path.as_ref()?;
+        if self.misses == 0 && self.previous.is_empty() {
+            return None;
+        }
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| write_atomic(path, &encode(&self.current)));
+        result.err().map(|e| {
+            Diagnostic::warning(
+                DiagnosticCode::GlyphCache,
+                path,
+                None,
+                format!("failed to save glyph cache: {e}"),
+            )
+        })
+    }
+}
+
+fn encode(entries: &HashMap<u64, Vec<u8>>) -> Vec<u8> {
+    let version = env!("CARGO_PKG_VERSION").as_bytes();
+    let mut keys = Vec::from_iter(entries.keys());
+    keys.sort();
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.push(version.len() as u8);
+    bytes.extend_from_slice(version);
+    bytes.extend_from_slice(&(keys.len() as u32).to_le_bytes());
+    for key in keys {
+        let value = &entries[key];
+        bytes.extend_from_slice(&key.to_le_bytes());
+        bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(value);
+    }
+    bytes
+}
+
+fn decode(bytes: &[u8]) -> Option<HashMap<u64, Vec<u8>>> {
+    let mut reader = Reader(bytes);
+    if reader.take(MAGIC.len())? != MAGIC || reader.u32()? != FORMAT_VERSION {
+        return None;
+    }
+    let version_len = reader.take(1)?[0] as usize;
+    if reader.take(version_len)? != env!("CARGO_PKG_VERSION").as_bytes() {
+        return None;
+    }
+    let count = reader.u32()?;
+    let mut entries = HashMap::new();
+    for _ in 0..count {
+        let key = u64::from_le_bytes(reader.take(8)?.try_into().ok()?);
+        let len = reader.u32()? as usize;
+        entries.insert(key, reader.take(len)?.to_vec());
+    }
+    reader.0.is_empty().then_some(entries)
+}
+
+/// Little-endian reader over cached bytes, returning `None` once they run out.
+pub(crate) struct Reader<'a>(pub(crate) &'a [u8]);
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(head)
+    }
+
+    pub(crate) fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    pub(crate) fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+}
+
+/// FNV-1a, which unlike the hasher of `std` is guaranteed to stay the same across builds.
+pub(crate) struct CacheKey(u64);
+
+impl Default for CacheKey {
+    fn default() -> Self {
+        CacheKey(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for CacheKey {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+impl CacheKey {
+    pub(crate) fn write_str(&mut self, value: &str) {
+        self.write_u64(value.len() as u64);
+        self.write(value.as_bytes());
+    }
+
+    pub(crate) fn write_matrix(&mut self, matrix: &BitmapMatrix) {
+        self.write_u64(matrix.0.len() as u64);
+        for row in &matrix.0 {
+            self.write_u64(row.len() as u64);
+            // 0xFF never collides with the 16 palette colors.
+            self.write(&Vec::from_iter(
+                row.iter()
+                    .map(|cell| cell.as_ref().map_or(0xFF, |color| color.value())),
+            ));
+        }
+    }
+
+    pub(crate) fn write_shape(&mut self, shape: &PixelShape) {
+        let (kind, parameter) = match *shape {
+            PixelShape::Square => (0, 0.),
+            PixelShape::Dot { size } => (1, size),
+            PixelShape::Rounded { radius } => (2, radius),
+            PixelShape::Scanline { gap } => (3, gap),
+            PixelShape::Smoothed => (4, 0.),
+        };
+        self.write_u8(kind);
+        self.write_u64(f64::to_bits(parameter));
+    }
+
+    pub(crate) fn write_diagonal(&mut self, rule: DiagonalRule) {
+        self.write_u8(match rule {
+            DiagonalRule::Join => 0,
+            DiagonalRule::Split => 1,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_only_entries_used_by_the_last_build() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut cache, diagnostic) = GlyphCache::load(Some(dir.path()), "font");
+        assert!(diagnostic.is_none(), "a missing cache is not a problem");
+        assert_eq!(cache.get(1), None);
+        cache.insert(1, b"one".to_vec());
+        cache.insert(2, b"two".to_vec());
+        assert!(cache.save().is_none());
+
+        let (mut cache, _) = GlyphCache::load(Some(dir.path()), "font");
+        assert_eq!(cache.get(2), Some(&b"two"[..]));
+        assert_eq!(cache.get(2), Some(&b"two"[..]));
+        assert_eq!(cache.hits(), 1, "a key is hit once");
+        assert!(cache.save().is_none());
+
+        let (mut cache, _) = GlyphCache::load(Some(dir.path()), "font");
+        assert_eq!(cache.get(1), None, "unused entries are dropped on save");
+        assert_eq!(cache.get(2), Some(&b"two"[..]));
+    }
+
+    #[test]
+    fn ignores_broken_caches() {
+        let mut entries = HashMap::new();
+        entries.insert(7, vec![1, 2, 3]);
+        let bytes = encode(&entries);
+        assert_eq!(decode(&bytes), Some(entries));
+        assert_eq!(decode(&bytes[..bytes.len() - 1]), None, "truncated");
+        assert_eq!(decode(&[&bytes[..], &[0]].concat()), None, "trailing bytes");
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("font"), b"STUDIOGC\x00").unwrap();
+        let (mut cache, diagnostic) = GlyphCache::load(Some(dir.path()), "font");
+        assert_eq!(cache.get(7), None);
+        assert!(matches!(
+            diagnostic,
+            Some(Diagnostic {
+                code: DiagnosticCode::GlyphCache,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn hashes_with_fnv1a() {
+        let hash = |bytes: &[u8]| {
+            let mut key = CacheKey::default();
+            key.write(bytes);
+            key.finish()
+        };
+        assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn keys_strings_by_their_bounds() {
+        let hash = |values: &[&str]| {
+            let mut key = CacheKey::default();
+            for value in values {
+                key.write_str(value);
+            }
+            key.finish()
+        };
+        assert_ne!(hash(&["ab", "c"]), hash(&["a", "bc"]));
+    }
+}
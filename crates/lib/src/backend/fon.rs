@@ -0,0 +1,707 @@
+use std::collections::BTreeMap;
+
+use encoding_rs::Encoding;
+use serde::Deserialize;
+use snafu::prelude::*;
+
+use crate::{
+    diagnostic::{Diagnostic, DiagnosticCode},
+    font::{Font, Glyph, GlyphId},
+};
+
+use super::{Artifact, BuildReporter, FileNameTemplate, FontBackend, Progress};
+
+const FNT_HEADER_SIZE_V2: usize = 118;
+const FNT_HEADER_SIZE_V3: usize = 148;
+/// Bytes of the FNT header copied into the font directory.
+const FONT_DIR_ENTRY_SIZE: usize = 113;
+const FF_DONTCARE: u8 = 0x00;
+const FF_MODERN: u8 = 0x30;
+const VARIABLE_PITCH: u8 = 0x01;
+const DFF_FIXED: u32 = 0x01;
+const DFF_PROPORTIONAL: u32 = 0x02;
+
+const RT_FONTDIR: u16 = 0x8007;
+const RT_FONT: u16 = 0x8008;
+/// Resources are aligned to `1 << NE_ALIGN_SHIFT` bytes.
+const NE_ALIGN_SHIFT: u16 = 4;
+/// Offset of the NE header, right after the DOS stub.
+const NE_OFFSET: usize = 0x80;
+
+/// DOS program telling that the file is not one, followed by the message it prints.
+const DOS_STUB_CODE: &[u8] = b"\x0e\x1f\xba\x0e\x00\xb4\x09\xcd\x21\xb8\x01\x4c\xcd\x21";
+const DOS_STUB_MESSAGE: &[u8] = b"This is a font file, not a program.\r\n$";
+
+/// Builds Windows raster fonts, which are FNT resources packed in an NE executable
+/// like Windows 3.x and Wine load.
+///
+/// FNT fonts are 8-bit, so characters are encoded by a Windows code page,
+/// and the ones outside of it are left out.
+/// The font has a single size, which is the height of the font;
+/// glyphs are clipped to their advance and the height of the font.
+pub struct FonBackend {
+    options: FonOptions,
+}
+
+#[derive(Debug, Clone)]
+pub struct FonOptions {
+    /// Name of the output file, which `.fon` is appended to.
+    pub file_name: FileNameTemplate,
+    /// Version of the FNT resource, 2.0 unless the font is larger than 64 KiB if `None`.
+    pub version: Option<FntVersion>,
+    /// Label of the Windows code page encoding characters, like `windows-1252`.
+    pub code_page: String,
+    /// Resolution in dots per inch, which the point size is derived from.
+    pub resolution: u16,
+}
+
+impl Default for FonOptions {
+    fn default() -> Self {
+        FonOptions {
+            file_name: FileNameTemplate::default(),
+            version: None,
+            code_page: "windows-1252".to_owned(),
+            resolution: 96,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+pub enum FntVersion {
+    /// Read by every version of Windows, with bitmaps up to 64 KiB.
+    #[serde(rename = "2.0")]
+    V2,
+    /// Read by Windows 3.0 and later, with 32-bit offsets.
+    #[serde(rename = "3.0")]
+    V3,
+}
+
+#[derive(Debug, Snafu)]
+pub enum FonBuildError {
+    #[snafu(display("font height must not be zero"))]
+    FontHeightZero,
+    #[snafu(display("resolution must not be zero"))]
+    ResolutionZero,
+    #[snafu(display("glyph {labels} is {width} pixels wide, but FNT allows at most 65535"))]
+    GlyphTooWide { labels: String, width: usize },
+    #[snafu(display("`{label}` is not a Windows code page"))]
+    UnknownCodePage { label: String },
+    #[snafu(display("no glyph is encoded by {code_page}"))]
+    NoEncodedGlyph { code_page: String },
+    #[snafu(display(
+        "FNT 2.0 only holds fonts up to 64 KiB but got {size} bytes, use version 3.0"
+    ))]
+    TooLargeForV2 { size: usize },
+}
+
+impl FonBackend {
+    pub fn new(options: FonOptions) -> Self {
+        FonBackend { options }
+    }
+}
+
+impl FontBackend for FonBackend {
+    type Err = FonBuildError;
+
+    fn build(&self, font: &Font, reporter: &dyn BuildReporter) -> Result<Vec<Artifact>, Self::Err> {
+        let options = &self.options;
+        ensure!(font.options.height > 0, FontHeightZeroSnafu);
+        ensure!(options.resolution > 0, ResolutionZeroSnafu);
+        if let Some(glyph) = font
+            .glyphs
+            .iter()
+            .find(|glyph| glyph.metrics.advance > 0xFFFF)
+        {
+            return GlyphTooWideSnafu {
+                labels: glyph.display_labels(),
+                width: glyph.metrics.advance,
+            }
+            .fail();
+        }
+        let (encoding, charset) = Encoding::for_label(options.code_page.as_bytes())
+            .and_then(|encoding| Some((encoding, charset_of(encoding)?)))
+            .context(UnknownCodePageSnafu {
+                label: &options.code_page,
+            })?;
+        let file_name = options.file_name.render(&font.options, "fon");
+        for sequence in font.features.ligatures.keys() {
+            reporter.diagnostic(Diagnostic::warning(
+                DiagnosticCode::UnsupportedFeature,
+                &file_name,
+                None,
+                format!(
+                    "ligature {:?} is left out, FNT has no ligatures",
+                    sequence.iter().collect::<String>()
+                ),
+            ));
+        }
+
+        let mut codes = BTreeMap::new();
+        for byte in 0..=u8::MAX {
+            let bytes = [byte];
+            let ch = encoding
+                .decode_without_bom_handling_and_without_replacement(&bytes)
+                .and_then(|decoded| decoded.chars().next());
+            if let Some(&id) = ch.and_then(|ch| font.cmap.get(&ch)) {
+                codes.insert(byte, id);
+            }
+        }
+        ensure!(
+            !codes.is_empty(),
+            NoEncodedGlyphSnafu {
+                code_page: encoding.name()
+            }
+        );
+        // code pages encode every character at most once.
+        let unencoded = font.cmap.len() - codes.len();
+        if unencoded > 0 {
+            reporter.diagnostic(Diagnostic::warning(
+                DiagnosticCode::UnsupportedFeature,
+                &file_name,
+                None,
+                format!(
+                    "{unencoded} characters are left out, {} does not encode them",
+                    encoding.name()
+                ),
+            ));
+        }
+        let writer = FntWriter {
+            font,
+            options,
+            charset,
+            codes,
+        };
+        for glyph in writer.glyphs() {
+            if !writer.fits(glyph) {
+                reporter.diagnostic(Diagnostic::warning(
+                    DiagnosticCode::GlyphClipped,
+                    &file_name,
+                    None,
+                    format!(
+                        "glyph {} does not fit its {}x{} cell and is clipped",
+                        glyph.display_labels(),
+                        glyph.metrics.advance,
+                        font.options.height
+                    ),
+                ));
+            }
+        }
+
+        let fnt = match options.version {
+            Some(version) => writer.fnt(version),
+            None => writer
+                .fnt(FntVersion::V2)
+                .or_else(|_| writer.fnt(FntVersion::V3)),
+        }?;
+        reporter.progress(Progress {
+            font: &font.options.full_name(),
+            stage: "writing glyphs",
+            done: font.glyphs.len(),
+            total: font.glyphs.len(),
+        });
+        Ok(vec![Artifact {
+            file_name,
+            mime_type: "application/x-font-fon",
+            bytes: writer.fon(&fnt),
+        }])
+    }
+}
+
+/// `dfCharSet` of a Windows code page.
+fn charset_of(encoding: &'static Encoding) -> Option<u8> {
+    use encoding_rs::{
+        WINDOWS_1250, WINDOWS_1251, WINDOWS_1252, WINDOWS_1253, WINDOWS_1254, WINDOWS_1255,
+        WINDOWS_1256, WINDOWS_1257, WINDOWS_1258, WINDOWS_874,
+    };
+
+    let charsets = [
+        (WINDOWS_1252, 0),
+        (WINDOWS_1250, 238),
+        (WINDOWS_1251, 204),
+        (WINDOWS_1253, 161),
+        (WINDOWS_1254, 162),
+        (WINDOWS_1255, 177),
+        (WINDOWS_1256, 178),
+        (WINDOWS_1257, 186),
+        (WINDOWS_1258, 163),
+        (WINDOWS_874, 222),
+    ];
+    charsets
+        .into_iter()
+        .find(|(known, _)| *known == encoding)
+        .map(|(_, charset)| charset)
+}
+
+struct FntWriter<'a> {
+    font: &'a Font,
+    options: &'a FonOptions,
+    charset: u8,
+    /// Glyphs of the bytes the code page encodes.
+    codes: BTreeMap<u8, GlyphId>,
+}
+
+impl FntWriter<'_> {
+    fn glyph(&self, id: GlyphId) -> &Glyph {
+        &self.font.glyphs[id.0 as usize]
+    }
+
+    /// Encoded glyphs, each once.
+    fn glyphs(&self) -> impl Iterator<Item = &Glyph> {
+        let mut ids: Vec<_> = self.codes.values().copied().collect();
+        ids.sort();
+        ids.dedup();
+        ids.into_iter().map(|id| self.glyph(id))
+    }
+
+    /// Whether every inked pixel of `glyph` is inside of its advance and the height of the font.
+    fn fits(&self, glyph: &Glyph) -> bool {
+        self.font.ink_box(glyph).is_none_or(|ink| {
+            ink.x >= 0
+                && ink.x as usize + ink.width <= glyph.metrics.advance
+                && ink.row + ink.height <= self.font.options.height as usize
+        })
+    }
+
+    /// Size of the font in points at the resolution of the options.
+    fn points(&self) -> u16 {
+        let height = self.font.options.height as u32;
+        let resolution = self.options.resolution as u32;
+        ((height * 72 + resolution / 2) / resolution) as u16
+    }
+
+    /// Bitmap of `glyph` in columns of 8 pixels, each from the top row to the bottom one.
+    fn bitmap(&self, glyph: &Glyph) -> Vec<u8> {
+        let height = self.font.options.height as usize;
+        let width = glyph.metrics.advance;
+        let overhang = glyph.metrics.overhang;
+        let mut bytes = vec![0u8; width.div_ceil(8) * height];
+        for row in 0..height {
+            for x in 0..width {
+                if glyph.is_inked(row, x + overhang) {
+                    bytes[x / 8 * height + row] |= 0x80 >> (x % 8);
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Writes the FNT resource, failing only if it does not fit in `version`.
+    fn fnt(&self, version: FntVersion) -> Result<Vec<u8>, FonBuildError> {
+        let options = &self.font.options;
+        let height = options.height;
+        let (&first_char, _) = self.codes.first_key_value().unwrap();
+        let (&last_char, _) = self.codes.last_key_value().unwrap();
+        let code_of = |ch: u8| self.codes.contains_key(&ch).then_some(ch);
+        let default_char = code_of(b'?').unwrap_or(first_char);
+        let break_char = code_of(b' ').unwrap_or(first_char);
+        let default_glyph = self.codes[&default_char];
+        let chars: Vec<_> = (first_char..=last_char)
+            .map(|code| self.codes.get(&code).copied().unwrap_or(default_glyph))
+            .collect();
+
+        let widths: Vec<_> = self
+            .glyphs()
+            .map(|glyph| glyph.metrics.advance as u16)
+            .collect();
+        let max_width = widths.iter().copied().max().unwrap_or(0);
+        let is_fixed = widths.iter().all(|&width| width == max_width);
+        let average_width = {
+            let sum: usize = widths.iter().map(|&width| width as usize).sum();
+            ((sum + widths.len() / 2) / widths.len()) as u16
+        };
+
+        let header_size = match version {
+            FntVersion::V2 => FNT_HEADER_SIZE_V2,
+            FntVersion::V3 => FNT_HEADER_SIZE_V3,
+        };
+        let entry_size = match version {
+            FntVersion::V2 => 4,
+            FntVersion::V3 => 6,
+        };
+        // one more entry for the absolute space, which is as wide as the average.
+        let bits_offset = header_size + (chars.len() + 1) * entry_size;
+        let mut bitmaps = Vec::new();
+        let mut offsets = BTreeMap::new();
+        for &id in &chars {
+            offsets.entry(id).or_insert_with(|| {
+                let offset = bits_offset + bitmaps.len();
+                bitmaps.extend(self.bitmap(self.glyph(id)));
+                offset
+            });
+        }
+        let space_offset = bits_offset + bitmaps.len();
+        bitmaps.resize(
+            bitmaps.len() + (average_width as usize).div_ceil(8) * height as usize,
+            0,
+        );
+        let face_offset = bits_offset + bitmaps.len();
+        let face = options.family_name.replace('\0', "");
+        let size = face_offset + face.len() + 1;
+        if version == FntVersion::V2 {
+            ensure!(space_offset <= 0xFFFF, TooLargeForV2Snafu { size });
+        }
+
+        let resolution = self.options.resolution;
+        let width_bytes = chars
+            .iter()
+            .map(|&id| self.glyph(id).metrics.advance.div_ceil(8))
+            .sum::<usize>()
+            .next_multiple_of(2);
+        let pitch_and_family = if is_fixed {
+            FF_MODERN
+        } else {
+            FF_DONTCARE | VARIABLE_PITCH
+        };
+        let mut copyright = [0u8; 60];
+        if let Some(notice) = &options.copyright_notice {
+            let notice = notice.as_bytes();
+            let len = notice.len().min(copyright.len());
+            copyright[..len].copy_from_slice(&notice[..len]);
+        }
+
+        let mut bytes = Vec::with_capacity(size);
+        let word = |bytes: &mut Vec<u8>, value: u16| bytes.extend(value.to_le_bytes());
+        let dword = |bytes: &mut Vec<u8>, value: u32| bytes.extend(value.to_le_bytes());
+        word(
+            &mut bytes,
+            match version {
+                FntVersion::V2 => 0x0200,
+                FntVersion::V3 => 0x0300,
+            },
+        );
+        dword(&mut bytes, size as u32);
+        bytes.extend(copyright);
+        // raster font.
+        word(&mut bytes, 0);
+        word(&mut bytes, self.points());
+        word(&mut bytes, resolution);
+        word(&mut bytes, resolution);
+        word(&mut bytes, self.font.baseline() as u16);
+        // internal and external leading.
+        word(&mut bytes, 0);
+        word(&mut bytes, 0);
+        bytes.push(options.style.is_italic() as u8);
+        // underline and strike out.
+        bytes.push(0);
+        bytes.push(0);
+        word(&mut bytes, options.weight_class());
+        bytes.push(self.charset);
+        word(&mut bytes, if is_fixed { max_width } else { 0 });
+        word(&mut bytes, height);
+        bytes.push(pitch_and_family);
+        word(&mut bytes, average_width);
+        word(&mut bytes, max_width);
+        bytes.push(first_char);
+        bytes.push(last_char);
+        bytes.push(default_char - first_char);
+        bytes.push(break_char - first_char);
+        word(&mut bytes, width_bytes as u16);
+        // device name.
+        dword(&mut bytes, 0);
+        dword(&mut bytes, face_offset as u32);
+        // bits pointer, which is set when loaded.
+        dword(&mut bytes, 0);
+        dword(&mut bytes, bits_offset as u32);
+        bytes.push(0);
+        if version == FntVersion::V3 {
+            dword(
+                &mut bytes,
+                if is_fixed {
+                    DFF_FIXED
+                } else {
+                    DFF_PROPORTIONAL
+                },
+            );
+            // A, B and C spaces, color pointer and reserved bytes.
+            bytes.resize(bytes.len() + 2 * 3 + 4 + 16, 0);
+        }
+        debug_assert_eq!(bytes.len(), header_size);
+
+        let entries = chars
+            .iter()
+            .map(|id| (self.glyph(*id).metrics.advance as u16, offsets[id]))
+            .chain([(average_width, space_offset)]);
+        for (width, offset) in entries {
+            word(&mut bytes, width);
+            match version {
+                FntVersion::V2 => word(&mut bytes, offset as u16),
+                FntVersion::V3 => dword(&mut bytes, offset as u32),
+            }
+        }
+        bytes.extend(bitmaps);
+        bytes.extend(face.as_bytes());
+        bytes.push(0);
+        Ok(bytes)
+    }
+
+    /// Packs `fnt` into an NE executable with the font directory Windows lists fonts from.
+    fn fon(&self, fnt: &[u8]) -> Vec<u8> {
+        let face = self.font.options.family_name.replace('\0', "");
+        let module_name: String = face
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|ch| ch.to_ascii_uppercase())
+            .take(8)
+            .collect();
+        let module_name = if module_name.is_empty() {
+            "FONT".to_owned()
+        } else {
+            module_name
+        };
+        let points = self.points();
+        let resolution = self.options.resolution;
+        let description = pascal(&format!(
+            "FONTRES 100,{resolution},{resolution} : {face} {points}"
+        ));
+
+        let mut font_dir = 1u16.to_le_bytes().to_vec();
+        // ordinal of the font resource.
+        font_dir.extend(1u16.to_le_bytes());
+        font_dir.extend(&fnt[..FONT_DIR_ENTRY_SIZE]);
+        // device name and face name.
+        font_dir.push(0);
+        font_dir.extend(face.as_bytes());
+        font_dir.push(0);
+
+        let resource_names = pascal("FONTDIR");
+        // shift, two types with one resource each, and the end of types.
+        let names_offset = 2 + 2 * (8 + 12) + 2;
+        let resource_table_len = names_offset + resource_names.len() + 1;
+        let resident_names_len = pascal(&module_name).len() + 2 + 1;
+        let resource_table = 0x40;
+        let resident_names = resource_table + resource_table_len;
+        let module_refs = resident_names + resident_names_len;
+        let imported_names = module_refs;
+        let entry_table = imported_names + 1;
+        let nonresident_names = NE_OFFSET + entry_table + 2;
+        let nonresident_names_len = description.len() + 2 + 1;
+        let align = |offset: usize| offset.next_multiple_of(1 << NE_ALIGN_SHIFT);
+        let font_dir_offset = align(nonresident_names + nonresident_names_len);
+        let fnt_offset = align(font_dir_offset + font_dir.len());
+        let end = align(fnt_offset + fnt.len());
+
+        let mut bytes = dos_stub();
+        let word = |bytes: &mut Vec<u8>, value: usize| bytes.extend((value as u16).to_le_bytes());
+        bytes.extend(b"NE");
+        // linker version and revision.
+        bytes.extend([5, 10]);
+        word(&mut bytes, entry_table);
+        word(&mut bytes, 2);
+        // checksum.
+        bytes.extend(0u32.to_le_bytes());
+        // a library module without data.
+        word(&mut bytes, 0x8000);
+        // automatic data segment, heap, stack, CS:IP, SS:SP and counts of segments and modules.
+        bytes.resize(bytes.len() + 2 * 3 + 4 * 2 + 2 * 2, 0);
+        word(&mut bytes, nonresident_names_len);
+        // no segments.
+        word(&mut bytes, resource_table);
+        word(&mut bytes, resource_table);
+        word(&mut bytes, resident_names);
+        word(&mut bytes, module_refs);
+        word(&mut bytes, imported_names);
+        bytes.extend((nonresident_names as u32).to_le_bytes());
+        // movable entries.
+        word(&mut bytes, 0);
+        word(&mut bytes, NE_ALIGN_SHIFT as usize);
+        // resource segments.
+        word(&mut bytes, 0);
+        // for Windows.
+        bytes.push(2);
+        bytes.push(0);
+        // fast load area and swap area.
+        bytes.resize(bytes.len() + 2 * 3, 0);
+        word(&mut bytes, 0x0300);
+        debug_assert_eq!(bytes.len(), NE_OFFSET + resource_table);
+
+        word(&mut bytes, NE_ALIGN_SHIFT as usize);
+        let resources = [
+            (
+                RT_FONTDIR,
+                font_dir_offset,
+                font_dir.len(),
+                0x0c50,
+                names_offset,
+            ),
+            (RT_FONT, fnt_offset, fnt.len(), 0x1c30, 0x8001),
+        ];
+        for (kind, offset, len, flags, id) in resources {
+            word(&mut bytes, kind as usize);
+            word(&mut bytes, 1);
+            bytes.extend(0u32.to_le_bytes());
+            word(&mut bytes, offset >> NE_ALIGN_SHIFT);
+            word(&mut bytes, len.div_ceil(1 << NE_ALIGN_SHIFT));
+            word(&mut bytes, flags);
+            word(&mut bytes, id);
+            bytes.extend(0u32.to_le_bytes());
+        }
+        word(&mut bytes, 0);
+        bytes.extend(resource_names);
+        bytes.push(0);
+
+        bytes.extend(pascal(&module_name));
+        word(&mut bytes, 0);
+        bytes.push(0);
+        // no imported names and no entries.
+        bytes.push(0);
+        bytes.extend([0, 0]);
+        debug_assert_eq!(bytes.len(), nonresident_names);
+        bytes.extend(description);
+        word(&mut bytes, 0);
+        bytes.push(0);
+
+        bytes.resize(font_dir_offset, 0);
+        bytes.extend(font_dir);
+        bytes.resize(fnt_offset, 0);
+        bytes.extend(fnt);
+        bytes.resize(end, 0);
+        bytes
+    }
+}
+
+/// DOS header and program, which point to the NE header at [`NE_OFFSET`].
+fn dos_stub() -> Vec<u8> {
+    let mut bytes = vec![0u8; NE_OFFSET];
+    let mut set = |offset: usize, value: u16| {
+        bytes[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    };
+    set(0x00, u16::from_le_bytes(*b"MZ"));
+    // bytes of the last page and pages of the program.
+    set(0x02, NE_OFFSET as u16);
+    set(0x04, 1);
+    // paragraphs of the header, and memory the program needs.
+    set(0x08, 4);
+    set(0x0c, 0xffff);
+    set(0x10, 0xb8);
+    set(0x18, 0x40);
+    set(0x3c, NE_OFFSET as u16);
+    let code = [DOS_STUB_CODE, DOS_STUB_MESSAGE].concat();
+    bytes[0x40..0x40 + code.len()].copy_from_slice(&code);
+    bytes
+}
+
+/// String prefixed by its length in bytes, cut at 255 bytes.
+fn pascal(value: &str) -> Vec<u8> {
+    let bytes = &value.as_bytes()[..value.len().min(255)];
+    let mut pascal = vec![bytes.len() as u8];
+    pascal.extend(bytes);
+    pascal
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backend::{CollectingReporter, FontStyle},
+        font::test_font,
+    };
+
+    use super::*;
+
+    /// A space, an 'A' and a 'Ą' which windows-1252 does not encode.
+    const SOURCE: &str = "' ':\n  ..\n  ..\n  ..\n  ..\n\n\
+                          'A':\n  .@.\n  @.@\n  @@@\n  ...\n\n\
+                          u+0104:\n  .@.\n  @.@\n  @@@\n  ..@\n";
+
+    fn build(font: &Font, options: FonOptions) -> (Vec<u8>, CollectingReporter) {
+        let reporter = CollectingReporter::default();
+        let mut artifacts = FonBackend::new(options).build(font, &reporter).unwrap();
+        (artifacts.remove(0).bytes, reporter)
+    }
+
+    fn u16_at(bytes: &[u8], offset: usize) -> usize {
+        u16::from_le_bytes([bytes[offset], bytes[offset + 1]]) as usize
+    }
+
+    /// The FNT resource inside of a FON file, found through its NE resource table.
+    fn fnt(fon: &[u8]) -> &[u8] {
+        assert_eq!(&fon[..2], b"MZ");
+        let ne = u16_at(fon, 0x3c);
+        assert_eq!(&fon[ne..ne + 2], b"NE");
+        let table = ne + u16_at(fon, ne + 0x24);
+        let shift = u16_at(fon, table);
+        // the font directory comes first, then the font.
+        let font = table + 2 + 20;
+        assert_eq!(u16_at(fon, font), RT_FONT as usize);
+        let offset = u16_at(fon, font + 8) << shift;
+        let size = u16_at(&fon[offset..], 2);
+        &fon[offset..offset + size]
+    }
+
+    #[test]
+    fn writes_fnt_2_in_a_fon() {
+        let font = test_font(SOURCE, 4, 1, FontStyle::default());
+        let (fon, reporter) = build(&font, FonOptions::default());
+        assert_eq!(
+            reporter.codes(),
+            [DiagnosticCode::UnsupportedFeature],
+            "'Ą' is not in windows-1252"
+        );
+        let fnt = fnt(&fon);
+        assert_eq!(u16_at(fnt, 0), 0x0200);
+        assert_eq!(u16_at(fnt, 74), 3, "ascent");
+        assert_eq!(fnt[85], 0, "ANSI charset");
+        assert_eq!(u16_at(fnt, 88), 4, "pixel height");
+        assert_eq!(
+            (fnt[90], u16_at(fnt, 86)),
+            (FF_DONTCARE | VARIABLE_PITCH, 0),
+            "variable pitch has no fixed width"
+        );
+        assert_eq!(
+            fnt[95..99],
+            [b' ', b'A', 0, 0],
+            "no '?', so space is the default"
+        );
+
+        // widths and offsets of each character from space to 'A', and the absolute space.
+        let entry = |code: u8| FNT_HEADER_SIZE_V2 + (code - b' ') as usize * 4;
+        assert_eq!(u16_at(fnt, entry(b' ')), 2);
+        assert_eq!(
+            u16_at(fnt, entry(b'!')),
+            2,
+            "missing characters are the default one"
+        );
+        assert_eq!(u16_at(fnt, entry(b'A')), 3);
+        let bits = u16_at(fnt, entry(b'A') + 2);
+        assert_eq!(fnt[bits..bits + 4], [0x40, 0xA0, 0xE0, 0x00]);
+        let face = u32::from_le_bytes(fnt[105..109].try_into().unwrap()) as usize;
+        assert_eq!(&fnt[face..], b"Test\0");
+    }
+
+    #[test]
+    fn marks_fixed_pitch_and_charsets() {
+        let font = test_font("'A':\n  @.\n\n'B':\n  .@\n", 1, 0, FontStyle::default());
+        let options = FonOptions {
+            version: Some(FntVersion::V3),
+            code_page: "windows-1251".to_owned(),
+            ..Default::default()
+        };
+        let (fon, _) = build(&font, options);
+        let fnt = fnt(&fon);
+        assert_eq!(u16_at(fnt, 0), 0x0300);
+        assert_eq!(fnt[85], 204, "Cyrillic charset");
+        assert_eq!((fnt[90], u16_at(fnt, 86)), (FF_MODERN, 2));
+        assert_eq!(fnt[118..122], DFF_FIXED.to_le_bytes());
+    }
+
+    #[test]
+    fn rejects_fonts_without_encoded_glyphs() {
+        let build = |source: &str, code_page: &str| {
+            let font = test_font(source, 4, 1, FontStyle::default());
+            let options = FonOptions {
+                code_page: code_page.to_owned(),
+                ..Default::default()
+            };
+            FonBackend::new(options).build(&font, &CollectingReporter::default())
+        };
+        assert!(matches!(
+            build(SOURCE, "utf-8"),
+            Err(FonBuildError::UnknownCodePage { .. })
+        ));
+        let ogonek = "u+0104:\n  .@.\n  @.@\n  @@@\n  ..@\n";
+        assert!(matches!(
+            build(ogonek, "windows-1252"),
+            Err(FonBuildError::NoEncodedGlyph { .. })
+        ));
+        assert!(build(ogonek, "windows-1250").is_ok());
+    }
+}
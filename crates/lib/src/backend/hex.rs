@@ -0,0 +1,220 @@
+use std::fmt::Write;
+
+use snafu::prelude::*;
+
+use crate::{
+    diagnostic::{Diagnostic, DiagnosticCode},
+    font::{Font, Glyph},
+    import::HEX_HEIGHT,
+};
+
+use super::{Artifact, BuildReporter, FileNameTemplate, FontBackend, Progress};
+
+/// Builds GNU Unifont `.hex` files, one `CODEPOINT:HEXBITS` line for each character.
+///
+/// Every glyph is 16 pixels high and 8 or 16 pixels wide, whichever its advance fits in.
+/// The advance is the drawn width plus emboldening, so synthesized Bold glyphs keep
+/// the columns they grow by.
+/// Wider glyphs, glyphs without a character and ligatures are left out with a warning.
+pub struct HexBackend {
+    options: HexOptions,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HexOptions {
+    /// Name of the output file, which `.hex` is appended to.
+    pub file_name: FileNameTemplate,
+}
+
+#[derive(Debug, Snafu)]
+pub enum HexBuildError {
+    #[snafu(display("Unifont glyphs are {HEX_HEIGHT} pixels high but the font is {height}"))]
+    InvalidHeight { height: u16 },
+}
+
+impl HexBackend {
+    pub fn new(options: HexOptions) -> Self {
+        HexBackend { options }
+    }
+}
+
+impl FontBackend for HexBackend {
+    type Err = HexBuildError;
+
+    fn build(&self, font: &Font, reporter: &dyn BuildReporter) -> Result<Vec<Artifact>, Self::Err> {
+        let height = font.options.height;
+        ensure!(height as usize == HEX_HEIGHT, InvalidHeightSnafu { height });
+        let file_name = self.options.file_name.render(&font.options, "hex");
+        let warn = |code, message| {
+            reporter.diagnostic(Diagnostic::warning(code, &file_name, None, message));
+        };
+        for sequence in font.features.ligatures.keys() {
+            warn(
+                DiagnosticCode::UnsupportedFeature,
+                format!(
+                    "ligature {:?} is left out, Unifont has no ligatures",
+                    sequence.iter().collect::<String>()
+                ),
+            );
+        }
+
+        let mut lines = Vec::new();
+        for glyph in &font.glyphs {
+            let codepoints: Vec<_> = font.codepoints(glyph.id).collect();
+            if codepoints.is_empty() {
+                let is_ligature = font.features.ligatures.values().any(|id| *id == glyph.id);
+                if !is_ligature {
+                    warn(
+                        DiagnosticCode::UnsupportedFeature,
+                        format!(
+                            "glyph {} is left out, Unifont only has glyphs of characters",
+                            glyph.display_labels()
+                        ),
+                    );
+                }
+                continue;
+            }
+            let width = match glyph.metrics.advance {
+                0..=8 => 8,
+                9..=16 => 16,
+                advance => {
+                    warn(
+                        DiagnosticCode::UnsupportedFeature,
+                        format!(
+                            "glyph {} is left out, it is {advance} pixels wide but Unifont allows 8 or 16",
+                            glyph.display_labels()
+                        ),
+                    );
+                    continue;
+                }
+            };
+            if !fits(font, glyph, width) {
+                warn(
+                    DiagnosticCode::GlyphClipped,
+                    format!(
+                        "glyph {} does not fit the {width}x{HEX_HEIGHT} cell and is clipped",
+                        glyph.display_labels()
+                    ),
+                );
+            }
+            let bits = hex_bits(glyph, width);
+            lines.extend(codepoints.into_iter().map(|ch| (ch, bits.clone())));
+        }
+        // lines are sorted by codepoint like Unifont.
+        lines.sort();
+        let mut hex = String::new();
+        for (ch, bits) in lines {
+            writeln!(hex, "{:04X}:{bits}", ch as u32).unwrap();
+        }
+
+        reporter.progress(Progress {
+            font: &font.options.full_name(),
+            stage: "writing glyphs",
+            done: font.glyphs.len(),
+            total: font.glyphs.len(),
+        });
+        Ok(vec![Artifact {
+            file_name,
+            mime_type: "text/plain",
+            bytes: hex.into_bytes(),
+        }])
+    }
+}
+
+/// Whether every inked pixel of `glyph` is inside of a cell `width` pixels wide.
+fn fits(font: &Font, glyph: &Glyph, width: usize) -> bool {
+    font.ink_box(glyph).is_none_or(|ink| {
+        ink.x >= 0 && ink.x as usize + ink.width <= width && ink.row + ink.height <= HEX_HEIGHT
+    })
+}
+
+/// Rows of `glyph` in a cell `width` pixels wide as hex digits, most significant bit first.
+fn hex_bits(glyph: &Glyph, width: usize) -> String {
+    let overhang = glyph.metrics.overhang;
+    let mut bits = String::with_capacity(width / 4 * HEX_HEIGHT);
+    for row in 0..HEX_HEIGHT {
+        for nibble in 0..width / 4 {
+            let value = (0..4).fold(0, |value, bit| {
+                let inked = glyph.is_inked(row, nibble * 4 + bit + overhang);
+                value << 1 | inked as u32
+            });
+            bits.push(char::from_digit(value, 16).unwrap().to_ascii_uppercase());
+        }
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backend::{CollectingReporter, FontStyle},
+        diagnostic::DiagnosticCode,
+        import::parse_hex,
+        project::{temp_project, Project},
+    };
+
+    use super::*;
+
+    /// YAFF glyph of `label`, `width` pixels wide and 16 high, inked where `inked` holds.
+    fn glyph(label: &str, width: usize, inked: impl Fn(usize, usize) -> bool) -> String {
+        let mut source = format!("{label}:\n");
+        for row in 0..HEX_HEIGHT {
+            let row: String = (0..width)
+                .map(|col| if inked(row, col) { '@' } else { '.' })
+                .collect();
+            source.push_str(&format!("  {row}\n"));
+        }
+        source + "\n"
+    }
+
+    fn build(manifest: &str, source: &str) -> Result<(String, CollectingReporter), HexBuildError> {
+        let dir = temp_project(&[("project.toml", manifest), ("src/glyphs.yaff", source)]);
+        let (project, diagnostics) = Project::load(dir.path());
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        let font = Font::from_project(&project, "Regular", FontStyle::default()).unwrap();
+        let reporter = CollectingReporter::default();
+        let artifacts = HexBackend::new(HexOptions::default()).build(&font, &reporter)?;
+        let hex = String::from_utf8(artifacts[0].bytes.clone()).unwrap();
+        Ok((hex, reporter))
+    }
+
+    #[test]
+    fn builds_16px_project() {
+        let source = [
+            glyph("'B'", 16, |row, col| row == 0 || col == 15),
+            glyph("'A'", 8, |_, col| col == 0),
+            glyph("'ab'", 8, |_, _| true),
+            glyph("\"unnamed\"", 8, |_, _| true),
+            glyph("'C'", 17, |_, _| false),
+        ]
+        .concat();
+        let (hex, reporter) = build("[project]\nheight = 16\ndescender = 2\n", &source).unwrap();
+        assert_eq!(
+            hex,
+            format!("0041:{}\n0042:FFFF{}\n", "80".repeat(16), "0001".repeat(15))
+        );
+        assert_eq!(
+            reporter.codes(),
+            [DiagnosticCode::UnsupportedFeature; 3],
+            "a ligature, a glyph without a character and a glyph too wide"
+        );
+
+        let (imported, diagnostics) = parse_hex(&hex, "unit.hex").unwrap();
+        assert!(diagnostics.is_empty());
+        let widths: Vec<_> = imported
+            .glyphs
+            .iter()
+            .map(|glyph| glyph.value.as_ref().unwrap().width)
+            .collect();
+        assert_eq!(widths, [8, 16]);
+    }
+
+    #[test]
+    fn rejects_other_heights() {
+        let result = build("[project]\nheight = 8\n", "'A':\n  @@@@\n  @..@\n");
+        assert!(matches!(
+            result,
+            Err(HexBuildError::InvalidHeight { height: 8 })
+        ));
+    }
+}
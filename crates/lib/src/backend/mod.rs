@@ -1,11 +1,35 @@
-use std::{error::Error, path::Path};
+use std::{error::Error, fmt};
 
-use yaff::GlyphDefinition;
+use jiff::Timestamp;
 
+use crate::{diagnostic::Diagnostic, font::Font, glyph::BitmapMatrix};
+
+mod bdf;
+mod cache;
+mod fon;
+mod hex;
 mod opentype_ttf;
+mod output;
+mod pcf;
+mod psf;
+mod registry;
+mod xlfd;
 
-pub use opentype_ttf::{OpentypeTtfBackend, OpentypeTtfBuildError};
+pub use bdf::{BdfBackend, BdfBuildError, BdfOptions};
+pub use fon::{FntVersion, FonBackend, FonBuildError, FonOptions};
+pub use hex::{HexBackend, HexBuildError, HexOptions};
+pub use opentype_ttf::{OpentypeTtfBackend, OpentypeTtfBuildError, OpentypeTtfOptions};
+pub use output::{
+    FileNameTemplate, FileNameTemplateError, OutputDir, OutputError, OUTPUT_MANIFEST_NAME,
+};
+pub use pcf::{PcfBackend, PcfBuildError, PcfOptions, PcfOrder};
+pub use psf::{PsfBackend, PsfBuildError, PsfOptions, PsfVersion};
+pub use registry::{
+    parse_target_options, AnyFontBackend, BackendEntry, BackendFactory, BackendRegistry,
+    BackendRegistryError, TargetContext,
+};
 
+/// Names and metrics of a font, which are the same for every backend.
 pub struct FontOptions {
     pub copyright_notice: Option<String>,
     pub family_name: String,
@@ -15,10 +39,74 @@ pub struct FontOptions {
     pub postscript_name: Option<String>,
 
     pub version: FontVerseion,
+    /// Weight class of the glyphs as drawn, before emboldening.
+    pub weight: u16,
+    /// Creation and modification time written into the font, the time of the build if `None`.
+    pub timestamp: Option<Timestamp>,
 
     pub height: u16,
     pub ascender: u16,
     pub descender: u16,
+
+    pub style: FontStyle,
+}
+
+impl FontOptions {
+    /// Full name of the font, which is `family_name sub_family_name` unless set.
+    pub fn full_name(&self) -> String {
+        self.full_font_name
+            .clone()
+            .unwrap_or_else(|| format!("{} {}", self.family_name, self.sub_family_name))
+    }
+
+    /// Weight class of the font, three steps heavier than drawn when emboldened.
+    pub fn weight_class(&self) -> u16 {
+        if self.style.is_bold() {
+            self.weight.saturating_add(300).min(1000)
+        } else {
+            self.weight
+        }
+    }
+}
+
+/// Style synthesized from the drawn bitmaps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FontStyle {
+    /// Pixels added to the right of strokes, `0` for the weight as drawn.
+    pub embolden: usize,
+    /// Rows per one pixel of shear, `None` for upright.
+    pub oblique: Option<usize>,
+}
+
+impl FontStyle {
+    pub fn is_bold(&self) -> bool {
+        self.embolden > 0
+    }
+
+    pub fn is_italic(&self) -> bool {
+        self.oblique.is_some()
+    }
+
+    /// Italic angle in counter-clockwise degrees from the vertical, negative when leaning right.
+    pub fn italic_angle(&self) -> f64 {
+        self.oblique
+            .map_or(0., |step| -(1. / step.max(1) as f64).atan().to_degrees())
+    }
+
+    /// Applies the style to a glyph whose baseline is above the row `baseline`.
+    /// Returns the number of columns added on the left as well, which should be hung
+    /// out of the advance.
+    pub(crate) fn apply(&self, matrix: BitmapMatrix, baseline: usize) -> (BitmapMatrix, usize) {
+        let matrix = if self.is_bold() {
+            matrix.emboldened(self.embolden)
+        } else {
+            matrix
+        };
+        match self.oblique {
+            Some(step) => matrix.sheared(step, baseline),
+            None => (matrix, 0),
+        }
+    }
 }
 
 pub struct FontVerseion {
@@ -50,10 +138,109 @@ impl FontVerseion {
     }
 }
 
+/// Writes the version as in manifests, like `1.0` or `2.05`, without metadata.
+impl fmt::Display for FontVerseion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // `minor` counts hundredths, so a whole tenth is written with one decimal.
+        if self.minor.is_multiple_of(10) {
+            write!(f, "{}.{}", self.major, self.minor / 10)
+        } else {
+            write!(f, "{}.{:02}", self.major, self.minor)
+        }
+    }
+}
+
+/// A file built by a backend, kept in memory until it is written with [`OutputDir`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    pub file_name: String,
+    pub mime_type: &'static str,
+    pub bytes: Vec<u8>,
+}
+
+/// How far a backend is in building a font.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress<'a> {
+    /// Full name of the font, like `Bitkodi Regular`.
+    pub font: &'a str,
+    /// What is being done, like `compiling glyphs`.
+    pub stage: &'a str,
+    pub done: usize,
+    pub total: usize,
+}
+
+/// Receives progress and diagnostics while fonts are built.
+///
+/// Backends may report from several threads at once.
+pub trait BuildReporter: Sync {
+    fn progress(&self, _progress: Progress<'_>) {}
+
+    fn diagnostic(&self, _diagnostic: Diagnostic) {}
+
+    /// Something worth telling about the font `font` which is neither progress nor a problem,
+    /// like how much a pass saved.
+    fn info(&self, _font: &str, _message: &str) {}
+}
+
+/// Reporter discarding everything.
+pub struct SilentReporter;
+
+impl BuildReporter for SilentReporter {}
+
+/// Reporter keeping diagnostics for tests to look into.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct CollectingReporter(std::sync::Mutex<Vec<Diagnostic>>);
+
+#[cfg(test)]
+impl CollectingReporter {
+    pub(crate) fn codes(&self) -> Vec<crate::diagnostic::DiagnosticCode> {
+        let diagnostics = self.0.lock().unwrap();
+        diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.code)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+impl BuildReporter for CollectingReporter {
+    fn diagnostic(&self, diagnostic: Diagnostic) {
+        self.0.lock().unwrap().push(diagnostic);
+    }
+}
+
 pub trait FontBackend {
     type Err: Error;
 
-    fn add_glyph(&mut self, glyph: &GlyphDefinition);
+    /// Builds `font` into one or more artifacts without touching the output directory.
+    fn build(&self, font: &Font, reporter: &dyn BuildReporter) -> Result<Vec<Artifact>, Self::Err>;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::font::test_font;
+
+    use super::*;
 
-    fn build_to(self, dir: impl AsRef<Path>) -> Result<(), Self::Err>;
+    #[test]
+    fn synthesizes_weights_and_angles() {
+        let bold = FontStyle {
+            embolden: 1,
+            oblique: None,
+        };
+        let mut options = test_font("", 1, 0, bold).options;
+        assert_eq!(options.weight_class(), 700);
+        options.weight = 800;
+        assert_eq!(options.weight_class(), 1000);
+        options.style = FontStyle::default();
+        assert_eq!(options.weight_class(), 800);
+
+        let oblique = FontStyle {
+            embolden: 0,
+            oblique: Some(1),
+        };
+        assert_eq!(oblique.italic_angle(), -45.);
+        assert_eq!(FontStyle::default().italic_angle(), 0.);
+    }
 }
@@ -1,24 +1,37 @@
 use std::{
-    collections::{BTreeMap, BTreeSet},
-    fs, io,
-    path::Path,
+    collections::BTreeSet,
+    hash::Hasher,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
+use crate::{
+    diagnostic::{Diagnostic, DiagnosticCode},
+    font::{Font, Glyph, GlyphMetrics},
+    glyph::{
+        optimize::{optimize_bezpath, remove_overlaps},
+        path::{analyze_bezpath, PointAndContours},
+        OutlineStyle, PathfinderError,
+    },
+    OutlineSettings,
+};
 use jiff::{civil::date, tz::TimeZone, Timestamp, Unit};
+use kurbo::{Affine, BezPath};
+use rayon::prelude::*;
 use snafu::prelude::*;
 use write_fonts::{
     from_obj::ToOwnedTable,
-    read::{FontRef, TableProvider},
+    read::{FontData, FontRead, FontRef, TableProvider},
     tables::{
         cmap::{Cmap, CmapSubtable, EncodingRecord, PlatformId, SequentialMapGroup},
-        glyf::{Glyf, GlyfLocaBuilder, SimpleGlyph},
+        glyf::{self, Bbox, Glyf, GlyfLocaBuilder, SimpleGlyph},
         head::{Head, MacStyle},
         hhea::Hhea,
         hmtx::Hmtx,
         loca::{Loca, LocaFormat},
         maxp::Maxp,
         name::{Name, NameRecord},
-        os2::Os2,
+        os2::{Os2, SelectionFlags},
         post::Post,
         sbix::HeaderFlags,
         vmtx::LongMetric,
@@ -26,20 +39,92 @@ use write_fonts::{
     types::{FWord, Fixed, LongDateTime, NameId},
     BuilderError, FontBuilder, OffsetMarker,
 };
-use yaff::{GlyphDefinition, SemanticGlyphLabel};
 
-use crate::glyph::{
-    path::{analyze_bezpath, PointAndContours},
-    BitmapMatrix,
+use super::{
+    cache::{CacheKey, GlyphCache, Reader},
+    Artifact, BuildReporter, FileNameTemplate, FontBackend, FontOptions, Progress,
 };
 
-use super::{FontBackend, FontOptions};
-
+/// Builds TrueType fonts with one contour per run of same-colored pixels.
 pub struct OpentypeTtfBackend {
-    options: FontOptions,
+    options: OpentypeTtfOptions,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OpentypeTtfOptions {
+    pub outline: OutlineSettings,
+    /// Name of the output file, which `.ttf` is appended to.
+    pub file_name: FileNameTemplate,
+    /// Directory to keep compiled glyphs in between builds, no caching if `None`.
+    pub cache_dir: Option<PathBuf>,
+}
+
+/// State of building a single font.
+struct TtfCompiler<'a> {
+    font: &'a Font,
+    /// Font-wide options, the same as `font.options`.
+    options: &'a FontOptions,
+    ttf: &'a OpentypeTtfOptions,
     size_multiplier: u16,
-    max_width: u16,
-    matrices: Vec<(Vec<SemanticGlyphLabel>, BitmapMatrix)>,
+    reporter: &'a dyn BuildReporter,
+    /// Name of the artifact, which diagnostics point to.
+    file_name: &'a str,
+    full_name: &'a str,
+}
+
+/// Tables made from the glyphs at once, as they refer to each other by glyph ids.
+type GlyphRelatedTables = (Glyf, Loca, Cmap, Hmtx, Maxp);
+/// A glyph ready to be put into `glyf` and `hmtx`, which is what the glyph cache stores.
+struct CompiledGlyph {
+    glyph: glyf::Glyph,
+    advance: u16,
+    lsb: i16,
+    optimized: PointAndContours,
+    savings: PointAndContours,
+}
+
+impl CompiledGlyph {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.advance.to_le_bytes());
+        bytes.extend_from_slice(&self.lsb.to_le_bytes());
+        for count in [self.optimized, self.savings]
+            .iter()
+            .flat_map(|count| [count.points, count.contours])
+        {
+            bytes.extend_from_slice(&(count as u32).to_le_bytes());
+        }
+        // an empty glyph takes no bytes in `glyf` either.
+        if let glyf::Glyph::Simple(glyph) = &self.glyph {
+            bytes.extend(write_fonts::dump_table(glyph).expect("compiled glyphs must be valid"));
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<CompiledGlyph> {
+        let mut reader = Reader(bytes);
+        let advance = reader.u16()?;
+        let lsb = reader.u16()? as i16;
+        let mut count = || {
+            Some(PointAndContours {
+                points: reader.u32()? as usize,
+                contours: reader.u32()? as usize,
+            })
+        };
+        let (optimized, savings) = (count()?, count()?);
+        let glyph = if reader.0.is_empty() {
+            glyf::Glyph::Empty
+        } else {
+            glyf::Glyph::Simple(SimpleGlyph::read(FontData::new(reader.0)).ok()?)
+        };
+        Some(CompiledGlyph {
+            glyph,
+            advance,
+            lsb,
+            optimized,
+            savings,
+        })
+    }
 }
 
 #[derive(Debug, Snafu)]
@@ -48,18 +133,30 @@ pub enum OpentypeTtfBuildError {
     FontHeightZero,
     #[snafu(display("expect font-height <= 16384 but got {height}"))]
     FontHeightTooBig { height: u16 },
+    #[snafu(display("failed to trace the outline of {glyph}"))]
+    Outline {
+        glyph: String,
+        source: PathfinderError,
+    },
     #[snafu(transparent)]
     Builder { source: BuilderError },
     #[snafu(transparent)]
-    Io { source: io::Error },
-    #[snafu(transparent)]
     WriteFonts { source: write_fonts::error::Error },
     #[snafu(transparent)]
     Jiff { source: jiff::Error },
 }
 
 impl OpentypeTtfBackend {
-    pub fn new(options: FontOptions) -> Result<Self, OpentypeTtfBuildError> {
+    pub fn new(options: OpentypeTtfOptions) -> Self {
+        OpentypeTtfBackend { options }
+    }
+}
+
+impl FontBackend for OpentypeTtfBackend {
+    type Err = OpentypeTtfBuildError;
+
+    fn build(&self, font: &Font, reporter: &dyn BuildReporter) -> Result<Vec<Artifact>, Self::Err> {
+        let options = &font.options;
         if options.height == 0 {
             return Err(OpentypeTtfBuildError::FontHeightZero);
         }
@@ -70,39 +167,31 @@ impl OpentypeTtfBackend {
         }
         // Apple requires `unitsPerEm` not to be less than 64.
         let size_multiplier = (64f64 / (options.height as f64)).ceil() as u16;
-        Ok(OpentypeTtfBackend {
+        let file_name = self.options.file_name.render(options, "ttf");
+        let bytes = TtfCompiler {
+            font,
             options,
+            ttf: &self.options,
             size_multiplier,
-            max_width: 0,
-            matrices: Vec::new(),
-        })
+            reporter,
+            file_name: &file_name,
+            full_name: &options.full_name(),
+        }
+        .compile()?;
+        Ok(vec![Artifact {
+            file_name,
+            mime_type: "font/ttf",
+            bytes,
+        }])
     }
 }
 
-impl FontBackend for OpentypeTtfBackend {
-    type Err = OpentypeTtfBuildError;
-
-    fn add_glyph(&mut self, glyph: &GlyphDefinition) {
-        let Some(glyph_value) = &glyph.value else {
-            return;
-        };
-        self.max_width = self.max_width.max(glyph_value.width);
-        dbg!(BitmapMatrix::from(glyph).as_bezier_paths(1).0[0].to_svg());
-        self.matrices.push((
-            glyph
-                .labels
-                .iter()
-                .map(|label| label.to_semantic())
-                .flatten()
-                .collect(),
-            BitmapMatrix::from(glyph),
-        ));
-    }
-
-    fn build_to(self, dir: impl AsRef<Path>) -> Result<(), Self::Err> {
-        let (loca_format, (glyf, loca, cmap, hmtx, maxp)) = self.make_glyph_related_tables()?;
+impl TtfCompiler<'_> {
+    fn compile(&self) -> Result<Vec<u8>, OpentypeTtfBuildError> {
+        let (loca_format, bounds, (glyf, loca, cmap, hmtx, maxp)) =
+            self.make_glyph_related_tables()?;
         let hhea = self.make_hhea(&hmtx);
-        let head = self.make_head(loca_format)?;
+        let head = self.make_head(loca_format, bounds)?;
         let os2 = self.make_os2();
         let name = self.make_name();
         let post = self.make_post();
@@ -149,30 +238,27 @@ impl FontBackend for OpentypeTtfBackend {
             .copy_missing_tables(font)
             .build();
 
-        let dir = dir.as_ref();
-        fs::remove_dir_all(dir)?;
-        fs::create_dir_all(dir)?;
-        fs::write(
-            dir.join(format!(
-                "{} {}.ttf",
-                self.options.family_name, self.options.sub_family_name
-            )),
-            bytes,
-        )?;
-
-        Ok(())
+        Ok(bytes)
     }
-}
 
-impl OpentypeTtfBackend {
-    fn make_head(&self, loca_format: LocaFormat) -> Result<Head, OpentypeTtfBuildError> {
+    /// `bounds` is the union of the bounding boxes of every glyph, `None` if none has contours.
+    fn make_head(
+        &self,
+        loca_format: LocaFormat,
+        bounds: Option<Bbox>,
+    ) -> Result<Head, OpentypeTtfBuildError> {
         let time = {
             let base = date(1904, 1, 1)
                 .to_zoned(TimeZone::UTC)
                 .expect("1904-01-01T00:00:00Z must be presentable in timestamp");
-            let now = Timestamp::now().to_zoned(TimeZone::UTC);
+            let now = self
+                .options
+                .timestamp
+                .unwrap_or_else(Timestamp::now)
+                .to_zoned(TimeZone::UTC);
             (&now - &base).total(Unit::Second)? as i64
         };
+        let bounds = bounds.unwrap_or_default();
         Ok(Head {
             font_revision: Fixed::from_f64(
                 (self.options.version.major as f64) + (self.options.version.minor as f64) / 100.0,
@@ -188,14 +274,22 @@ impl OpentypeTtfBackend {
             created: LongDateTime::new(time),
             modified: LongDateTime::new(time),
 
-            // @TODO i'm not confident about this
-            x_min: 0,
-            y_min: 0,
-            x_max: (self.max_width * self.size_multiplier) as _,
-            y_max: (self.options.height * self.size_multiplier) as _,
+            // including what an oblique shears out of the cell.
+            x_min: bounds.x_min,
+            y_min: bounds.y_min,
+            x_max: bounds.x_max,
+            y_max: bounds.y_max,
 
-            // @TODO bold and italic support
-            mac_style: MacStyle::empty(),
+            mac_style: {
+                let mut mac_style = MacStyle::empty();
+                if self.options.style.is_bold() {
+                    mac_style |= MacStyle::BOLD;
+                }
+                if self.options.style.is_italic() {
+                    mac_style |= MacStyle::ITALIC;
+                }
+                mac_style
+            },
             lowest_rec_ppem: self.options.height,
             // deprecated in spec; set to 2
             font_direction_hint: 2,
@@ -213,8 +307,9 @@ impl OpentypeTtfBackend {
             min_left_side_bearing: Default::default(),
             min_right_side_bearing: Default::default(),
             x_max_extent: Default::default(),
-            caret_slope_rise: Default::default(),
-            caret_slope_run: Default::default(),
+            // upright is 1/0, and an oblique leans one pixel every `step` rows
+            caret_slope_rise: self.options.style.oblique.map_or(1, |step| step as _),
+            caret_slope_run: self.options.style.oblique.map_or(0, |_| 1),
             caret_offset: Default::default(),
             number_of_long_metrics: hmtx.h_metrics.len() as _, /* + vmtx.v_metrics.len() */
         }
@@ -223,8 +318,7 @@ impl OpentypeTtfBackend {
     fn make_os2(&self) -> Os2 {
         Os2 {
             x_avg_char_width: Default::default(),
-            // @TODO change this
-            us_weight_class: 400,
+            us_weight_class: self.options.weight_class(),
             us_width_class: 5,
             fs_type: Default::default(),
             y_subscript_x_size: Default::default(),
@@ -244,7 +338,20 @@ impl OpentypeTtfBackend {
             ul_unicode_range_3: Default::default(),
             ul_unicode_range_4: Default::default(),
             ach_vend_id: Default::default(),
-            fs_selection: Default::default(),
+            fs_selection: {
+                let mut fs_selection = SelectionFlags::empty();
+                if self.options.style.is_bold() {
+                    fs_selection |= SelectionFlags::BOLD;
+                }
+                // sheared from the upright glyphs rather than drawn as italics.
+                if self.options.style.is_italic() {
+                    fs_selection |= SelectionFlags::OBLIQUE;
+                }
+                if fs_selection.is_empty() {
+                    fs_selection |= SelectionFlags::REGULAR;
+                }
+                fs_selection
+            },
             us_first_char_index: Default::default(),
             us_last_char_index: Default::default(),
             s_typo_ascender: (self.options.ascender * self.size_multiplier) as _,
@@ -252,21 +359,166 @@ impl OpentypeTtfBackend {
             s_typo_line_gap: Default::default(),
             us_win_ascent: Default::default(),
             us_win_descent: Default::default(),
-            ul_code_page_range_1: Default::default(),
-            ul_code_page_range_2: Default::default(),
-            sx_height: Default::default(),
-            s_cap_height: Default::default(),
-            us_default_char: Default::default(),
-            us_break_char: Default::default(),
-            us_max_context: Default::default(),
+            // the fields below make the table version 4, which the OBLIQUE flag needs.
+            ul_code_page_range_1: Some(0),
+            ul_code_page_range_2: Some(0),
+            sx_height: Some(self.ink_top('x')),
+            s_cap_height: Some(self.ink_top('H')),
+            us_default_char: Some(0),
+            us_break_char: Some(0x20),
+            // ligatures are left out, so no lookup looks beyond a single glyph.
+            us_max_context: Some(0),
             us_lower_optical_point_size: Default::default(),
             us_upper_optical_point_size: Default::default(),
         }
     }
 
+    /// Height of the ink of `ch` above the baseline in font units, `0` if it is missing.
+    fn ink_top(&self, ch: char) -> i16 {
+        let top = self
+            .font
+            .cmap
+            .get(&ch)
+            .and_then(|&id| self.font.ink_box(self.font.glyph(id)?))
+            .map_or(0, |ink| ink.y + ink.height as i32);
+        (top * self.size_multiplier as i32) as i16
+    }
+
+    /// Name of the glyph cache file, which is separate for every output file.
+    fn cache_name(&self) -> String {
+        let mut key = CacheKey::default();
+        key.write_str(&self.options.family_name);
+        key.write_str(&self.options.sub_family_name);
+        key.write_str(self.file_name);
+        format!("ttf-{:016x}", key.finish())
+    }
+
+    /// Hash of everything [`Self::compile_glyph`] depends on.
+    fn cache_key(&self, glyph: &Glyph) -> u64 {
+        let outline = &self.ttf.outline;
+        let mut key = CacheKey::default();
+        key.write_matrix(&glyph.bitmap);
+        key.write_usize(glyph.metrics.advance);
+        key.write_usize(glyph.metrics.overhang);
+        key.write_u16(self.size_multiplier);
+        key.write_u16(self.options.height);
+        key.write_u16(self.options.descender);
+        key.write_shape(&outline.shape);
+        key.write_diagonal(outline.diagonal);
+        key.write_u8(outline.remove_overlaps as u8);
+        key.finish()
+    }
+
+    /// Compiles every glyph in parallel, reusing the cached ones.
+    /// The result is in the order of glyph IDs, and so is the error if more than one glyph fails.
+    fn compile_glyphs(&self) -> Result<Vec<CompiledGlyph>, OpentypeTtfBuildError> {
+        let glyphs = &self.font.glyphs;
+        let (mut cache, diagnostic) =
+            GlyphCache::load(self.ttf.cache_dir.as_deref(), &self.cache_name());
+        if let Some(diagnostic) = diagnostic {
+            self.reporter.diagnostic(diagnostic);
+        }
+        let keys: Vec<u64> = glyphs
+            .par_iter()
+            .map(|glyph| self.cache_key(glyph))
+            .collect();
+        let cached: Vec<Option<CompiledGlyph>> = keys
+            .iter()
+            .map(|&key| cache.get(key).and_then(CompiledGlyph::from_bytes))
+            .collect();
+        self.progress("reusing cached glyphs", cache.hits(), glyphs.len());
+
+        let done = AtomicUsize::new(cache.hits());
+        let compiled: Vec<Result<_, OpentypeTtfBuildError>> = glyphs
+            .par_iter()
+            .zip(cached)
+            .map(|(glyph, cached)| match cached {
+                Some(compiled) => Ok((compiled, true)),
+                None => {
+                    let compiled = self.compile_glyph(glyph)?;
+                    let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                    self.progress("compiling glyphs", done, glyphs.len());
+                    Ok((compiled, false))
+                }
+            })
+            .collect();
+        let compiled = compiled.into_iter().collect::<Result<Vec<_>, _>>()?;
+        for (&key, (compiled, _)) in keys
+            .iter()
+            .zip(&compiled)
+            .filter(|(_, (_, is_cached))| !is_cached)
+        {
+            cache.insert(key, compiled.to_bytes());
+        }
+        if let Some(diagnostic) = cache.save() {
+            self.reporter.diagnostic(diagnostic);
+        }
+        Ok(compiled.into_iter().map(|(compiled, _)| compiled).collect())
+    }
+
+    fn progress(&self, stage: &str, done: usize, total: usize) {
+        self.reporter.progress(Progress {
+            font: self.full_name,
+            stage,
+            done,
+            total,
+        });
+    }
+
+    fn compile_glyph(&self, glyph: &Glyph) -> Result<CompiledGlyph, OpentypeTtfBuildError> {
+        let GlyphMetrics { advance, overhang } = glyph.metrics;
+        let scale = self.size_multiplier as f64;
+        let outline_context = || OutlineSnafu {
+            glyph: glyph.display_labels(),
+        };
+        let (paths, bb) = self
+            .ttf
+            .outline
+            .shape
+            .outline(
+                &glyph.bitmap,
+                self.size_multiplier as _,
+                self.ttf.outline.diagonal,
+            )
+            .with_context(|_| outline_context())?;
+        let mut path = BezPath::from_iter(paths.iter().flat_map(|path| path.elements().to_vec()));
+        if self.ttf.outline.remove_overlaps {
+            path = remove_overlaps(&path, self.size_multiplier as _, self.ttf.outline.diagonal)
+                .with_context(|_| outline_context())?;
+        }
+        let traced = analyze_bezpath(&path);
+        let mut path = optimize_bezpath(&path);
+        let optimized = analyze_bezpath(&path);
+
+        // Bitmaps grow downwards from the top of the cell, while glyf grows upwards from
+        // the baseline. Contours traced clockwise on screen stay clockwise once flipped.
+        path.apply_affine(Affine::new([
+            1.,
+            0.,
+            0.,
+            -1.,
+            -(overhang as f64) * scale,
+            self.font.baseline() as f64 * scale,
+        ]));
+
+        Ok(CompiledGlyph {
+            glyph: if path.is_empty() {
+                glyf::Glyph::Empty
+            } else {
+                SimpleGlyph::from_bezpath(&path)
+                    .expect("traced paths must be valid")
+                    .into()
+            },
+            advance: (advance as f64 * scale) as _,
+            lsb: (bb.x0 - overhang as f64 * scale) as _,
+            optimized,
+            savings: traced.savings(&optimized),
+        })
+    }
+
     fn make_glyph_related_tables(
         &self,
-    ) -> Result<(LocaFormat, (Glyf, Loca, Cmap, Hmtx, Maxp)), OpentypeTtfBuildError> {
+    ) -> Result<(LocaFormat, Option<Bbox>, GlyphRelatedTables), OpentypeTtfBuildError> {
         let mut num_glyphs = 0u16;
         let mut max_points = 0u16;
         let mut max_contours = 0u16;
@@ -274,60 +526,52 @@ impl OpentypeTtfBackend {
         let mut hmtx_h_metrics = Vec::new();
         let mut hmtx_left_side_bearings = Vec::new();
 
-        let mut character_mappings = BTreeMap::new();
-
         let mut glyf_loca_builder = GlyfLocaBuilder::new();
+        let mut savings = PointAndContours::default();
+        let mut bounds: Option<Bbox> = None;
+
+        for sequence in self.font.features.ligatures.keys() {
+            self.reporter.diagnostic(Diagnostic::warning(
+                DiagnosticCode::UnsupportedFeature,
+                self.file_name,
+                None,
+                format!(
+                    "ligature {:?} is left out, which is not supported yet",
+                    sequence.iter().collect::<String>()
+                ),
+            ));
+        }
 
-        for (labels, matrix) in &self.matrices {
-            let mut groups = Vec::new();
-            for label in labels {
-                match label {
-                    SemanticGlyphLabel::CharSequence(vec) => match &vec[..] {
-                        &[ch] => groups.push(ch),
-                        _ => {
-                            eprintln!("{} is not supported yet", vec.iter().collect::<String>());
-                            continue;
-                        }
-                    },
-                    SemanticGlyphLabel::Tag(tag) => {
-                        let Some(ch) = unicode_names2::character(&tag) else {
-                            continue;
-                        };
-                        groups.push(ch);
-                    }
-                }
-            }
+        for compiled in self.compile_glyphs()? {
+            savings += compiled.savings;
 
-            let (paths, bb) = matrix.as_bezier_paths(self.size_multiplier as _);
-            match &paths[..] {
-                [path] => {
-                    glyf_loca_builder.add_glyph(
-                        &SimpleGlyph::from_bezpath(path).expect("must be valid bezier path"),
-                    )?;
-                    hmtx_h_metrics.push(LongMetric::new(bb.width() as _, 0));
-                    hmtx_left_side_bearings.push(0);
-                    let PointAndContours { points, contours } = analyze_bezpath(path);
-                    max_points = max_points.max(points as _);
-                    max_contours = max_contours.max(contours as _);
-
-                    for ch in groups {
-                        character_mappings.insert(ch, num_glyphs);
-                    }
-
-                    num_glyphs += 1;
-                }
-                _ => {
-                    eprintln!("there is unsupported glyph");
-                }
+            if let Some(bbox) = compiled.glyph.bbox() {
+                bounds = Some(bounds.map_or(bbox, |bounds| bounds.union(bbox)));
             }
+            glyf_loca_builder.add_glyph(&compiled.glyph)?;
+            hmtx_h_metrics.push(LongMetric::new(compiled.advance, compiled.lsb));
+            hmtx_left_side_bearings.push(0);
+            let PointAndContours { points, contours } = compiled.optimized;
+            max_points = max_points.max(points as _);
+            max_contours = max_contours.max(contours as _);
+
+            num_glyphs += 1;
         }
 
+        self.reporter.info(
+            self.full_name,
+            &format!(
+                "outline optimization saved {} points and {} contours",
+                savings.points, savings.contours
+            ),
+        );
+
         let (glyf, loca, loca_format) = glyf_loca_builder.build();
         let hmtx = Hmtx::new(hmtx_h_metrics, hmtx_left_side_bearings);
         let cmap = Cmap::new(vec![{
             let mut groups = Vec::new();
-            for (ch, id) in character_mappings.into_iter() {
-                groups.push(SequentialMapGroup::new(ch as _, ch as _, id as _));
+            for (&ch, id) in &self.font.cmap {
+                groups.push(SequentialMapGroup::new(ch as _, ch as _, id.0 as _));
             }
 
             EncodingRecord::new(PlatformId::Unicode, 6, CmapSubtable::format_12(
@@ -369,7 +613,7 @@ impl OpentypeTtfBackend {
             max_stack_elements: Some(1),
         };
 
-        Ok((loca_format, (glyf, loca, cmap, hmtx, maxp)))
+        Ok((loca_format, bounds, (glyf, loca, cmap, hmtx, maxp)))
     }
 
     fn make_name(&self) -> Name {
@@ -401,15 +645,7 @@ impl OpentypeTtfBackend {
                     &self.options.sub_family_name,
                 )),
                 Some(make_name_record(NameId::UNIQUE_ID, &self.options.unique_id)),
-                Some(make_name_record(
-                    NameId::FULL_NAME,
-                    &self.options.full_font_name.clone().unwrap_or_else(|| {
-                        format!(
-                            "{} {}",
-                            self.options.family_name, self.options.sub_family_name
-                        )
-                    }),
-                )),
+                Some(make_name_record(NameId::FULL_NAME, self.full_name)),
                 Some(make_name_record(
                     NameId::VERSION_STRING,
                     format!(
@@ -439,6 +675,275 @@ impl OpentypeTtfBackend {
     }
 
     fn make_post(&self) -> Post {
-        Post::default()
+        Post {
+            italic_angle: Fixed::from_f64(self.options.style.italic_angle()),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use write_fonts::read::{tables::os2::SelectionFlags, FontRef, TableProvider};
+
+    use crate::{
+        backend::{FontStyle, SilentReporter},
+        font::test_font,
+        glyph::DiagonalRule,
+    };
+
+    use super::*;
+
+    const SLASH: &str = "'/':\n  ...@\n  ..@.\n  .@..\n  @...\n";
+
+    fn build(font: &Font) -> Vec<u8> {
+        let backend = OpentypeTtfBackend::new(OpentypeTtfOptions::default());
+        let mut artifacts = backend.build(font, &SilentReporter).unwrap();
+        artifacts.remove(0).bytes
+    }
+
+    /// Compiles every glyph of `font` with one font unit per pixel.
+    fn compile(font: &Font, ttf: &OpentypeTtfOptions) -> Vec<CompiledGlyph> {
+        TtfCompiler {
+            font,
+            options: &font.options,
+            ttf,
+            size_multiplier: 1,
+            reporter: &SilentReporter,
+            file_name: "Test-Regular.ttf",
+            full_name: "Test Regular",
+        }
+        .compile_glyphs()
+        .unwrap()
+    }
+
+    /// Twice the signed area of each contour, positive when counter-clockwise.
+    fn contour_areas(compiled: &CompiledGlyph) -> Vec<i32> {
+        let glyf::Glyph::Simple(glyph) = &compiled.glyph else {
+            return Vec::new();
+        };
+        glyph
+            .contours()
+            .iter()
+            .map(|contour| {
+                let points: Vec<_> = contour.iter().collect();
+                (0..points.len())
+                    .map(|idx| {
+                        let (a, b) = (points[idx], points[(idx + 1) % points.len()]);
+                        a.x as i32 * b.y as i32 - b.x as i32 * a.y as i32
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn keeps_holes_as_contours() {
+        let source = "'O':\n  @@@@\n  @..@\n  @@@@\n\n'i':\n  @\n  .\n  @\n";
+        let font = test_font(source, 3, 0, FontStyle::default());
+        let compiled = compile(&font, &OpentypeTtfOptions::default());
+        // TrueType fills clockwise contours, so a hole runs the other way.
+        assert_eq!(contour_areas(&compiled[0]), [-24, 4], "'O'");
+        assert_eq!(contour_areas(&compiled[1]), [-2, -2], "'i'");
+    }
+
+    #[test]
+    fn marks_synthesized_oblique() {
+        let style = FontStyle {
+            embolden: 0,
+            oblique: Some(2),
+        };
+        let bytes = build(&test_font(SLASH, 4, 1, style));
+        let font = FontRef::new(&bytes).unwrap();
+        let os2 = font.os2().unwrap();
+        assert_eq!(os2.version(), 4);
+        assert_eq!(
+            os2.fs_selection(),
+            SelectionFlags::OBLIQUE,
+            "sheared glyphs are not italics"
+        );
+    }
+
+    #[test]
+    fn bounds_head_by_glyphs() {
+        let upright = build(&test_font(SLASH, 4, 1, FontStyle::default()));
+        let head = FontRef::new(&upright).unwrap().head().unwrap();
+        // 4 pixels of 16 units each, from the bottom of the descender up.
+        assert_eq!(
+            (head.x_min(), head.y_min(), head.x_max(), head.y_max()),
+            (0, -16, 64, 48)
+        );
+
+        let style = FontStyle {
+            embolden: 0,
+            oblique: Some(2),
+        };
+        let oblique = build(&test_font(SLASH, 4, 1, style));
+        let head = FontRef::new(&oblique).unwrap().head().unwrap();
+        assert!(
+            head.x_max() > 64,
+            "the top row is sheared past the advance, but xMax is {}",
+            head.x_max()
+        );
+    }
+
+    #[test]
+    fn round_trips_compiled_glyphs() {
+        let source = "'O':\n  @@@@\n  @..@\n  @@@@\n\n' ':\n  ....\n  ....\n  ....\n";
+        let font = test_font(source, 3, 0, FontStyle::default());
+        for compiled in compile(&font, &OpentypeTtfOptions::default()) {
+            let bytes = compiled.to_bytes();
+            let decoded = CompiledGlyph::from_bytes(&bytes).unwrap();
+            assert_eq!(decoded.to_bytes(), bytes);
+            assert_eq!(
+                contour_areas(&decoded),
+                contour_areas(&compiled),
+                "glyph of {} bytes",
+                bytes.len()
+            );
+            assert!(CompiledGlyph::from_bytes(&bytes[..4]).is_none());
+        }
+    }
+
+    #[test]
+    fn reuses_cached_glyphs() {
+        let dir = tempfile::tempdir().unwrap();
+        let cached = OpentypeTtfOptions {
+            cache_dir: Some(dir.path().to_owned()),
+            ..Default::default()
+        };
+        let font = test_font(SLASH, 4, 1, FontStyle::default());
+        let build = |ttf: &OpentypeTtfOptions| {
+            let backend = OpentypeTtfBackend::new(ttf.clone());
+            backend
+                .build(&font, &SilentReporter)
+                .unwrap()
+                .remove(0)
+                .bytes
+        };
+        let uncached = build(&OpentypeTtfOptions::default());
+        assert_eq!(build(&cached), uncached, "filling the cache");
+        assert_eq!(
+            std::fs::read_dir(dir.path()).unwrap().count(),
+            1,
+            "one cache file per font"
+        );
+        assert_eq!(build(&cached), uncached, "reading the cache");
+    }
+
+    #[test]
+    fn reports_progress_of_compiled_and_cached_glyphs() {
+        #[derive(Default)]
+        struct ProgressReporter(std::sync::Mutex<Vec<(String, usize, usize)>>);
+
+        impl BuildReporter for ProgressReporter {
+            fn progress(&self, progress: Progress<'_>) {
+                assert_eq!(progress.font, "Test Regular");
+                let stage = progress.stage.to_owned();
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((stage, progress.done, progress.total));
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let backend = OpentypeTtfBackend::new(OpentypeTtfOptions {
+            cache_dir: Some(dir.path().to_owned()),
+            ..Default::default()
+        });
+        let font = test_font(&format!("{SLASH}\n'A':\n  @\n"), 4, 1, FontStyle::default());
+        let build = || {
+            let reporter = ProgressReporter::default();
+            let artifacts = backend.build(&font, &reporter).unwrap();
+            assert_eq!(artifacts[0].file_name, "Test Regular.ttf");
+            assert_eq!(artifacts[0].mime_type, "font/ttf");
+            reporter.0.into_inner().unwrap()
+        };
+
+        let mut reports = build();
+        assert_eq!(
+            reports.remove(0),
+            ("reusing cached glyphs".to_owned(), 0, 2)
+        );
+        reports.sort();
+        let compiling: Vec<_> = reports
+            .iter()
+            .filter(|(stage, ..)| stage == "compiling glyphs")
+            .map(|&(_, done, total)| (done, total))
+            .collect();
+        assert_eq!(compiling, [(1, 2), (2, 2)]);
+
+        let reports = build();
+        assert!(reports.contains(&("reusing cached glyphs".to_owned(), 2, 2)));
+        assert!(!reports
+            .iter()
+            .any(|(stage, ..)| stage == "compiling glyphs"));
+    }
+
+    #[test]
+    fn keys_glyphs_by_what_they_are_compiled_from() {
+        let font = test_font(SLASH, 4, 1, FontStyle::default());
+        let key = |ttf: &OpentypeTtfOptions| {
+            TtfCompiler {
+                font: &font,
+                options: &font.options,
+                ttf,
+                size_multiplier: 1,
+                reporter: &SilentReporter,
+                file_name: "Test-Regular.ttf",
+                full_name: "Test Regular",
+            }
+            .cache_key(&font.glyphs[0])
+        };
+        let joined = OpentypeTtfOptions::default();
+        let mut split = joined.clone();
+        split.outline.diagonal = DiagonalRule::Split;
+        assert_eq!(key(&joined), key(&joined.clone()));
+        assert_ne!(key(&joined), key(&split));
+    }
+
+    #[test]
+    fn builds_the_same_bytes_every_time() {
+        // enough glyphs to be traced by several threads, each in a shape of its own.
+        let source: String = (0..64u32)
+            .map(|idx| {
+                let rows: String = (0..6)
+                    .map(|row| {
+                        let row: String = (0..6)
+                            .map(|col| {
+                                if (idx >> ((row + col) % 6)) & 1 == 1 {
+                                    '@'
+                                } else {
+                                    '.'
+                                }
+                            })
+                            .collect();
+                        format!("  {row}\n")
+                    })
+                    .collect();
+                format!("u+{:04X}:\n{rows}\n", 0x40 + idx)
+            })
+            .collect();
+        let font = test_font(&source, 6, 1, FontStyle::default());
+        let first = build(&font);
+        for _ in 0..4 {
+            assert!(build(&font) == first);
+        }
+
+        let ttf = OpentypeTtfOptions::default();
+        let sequential = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap()
+            .install(|| compile(&font, &ttf));
+        let parallel = compile(&font, &ttf);
+        assert!(
+            parallel
+                .iter()
+                .zip(&sequential)
+                .all(|(parallel, sequential)| parallel.to_bytes() == sequential.to_bytes()),
+            "compiled glyphs stay in the order of glyph IDs"
+        );
     }
 }
@@ -0,0 +1,406 @@
+use std::{
+    collections::BTreeSet,
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use serde::Deserialize;
+use snafu::prelude::*;
+
+use super::{Artifact, FontOptions};
+
+/// Name of the file listing artifacts written into an output directory.
+pub const OUTPUT_MANIFEST_NAME: &str = ".studio-output";
+
+/// Directory receiving built fonts.
+///
+/// Every file is written to a temporary file next to its destination and renamed into place,
+/// so a file in the directory is either the previous artifact or the complete new one.
+/// Artifacts are tracked in [`OUTPUT_MANIFEST_NAME`], and [`OutputDir::finish`] removes
+/// the ones of the previous build which are not written again.
+/// Files the tool did not write are never touched.
+///
+/// Fonts may be written from several threads at once.
+pub struct OutputDir {
+    path: PathBuf,
+    previous: BTreeSet<String>,
+    written: Mutex<BTreeSet<String>>,
+}
+
+#[derive(Debug, Snafu)]
+pub enum OutputError {
+    #[snafu(display("failed to create output directory {path}", path = path.to_string_lossy()))]
+    CreateDir { path: PathBuf, source: io::Error },
+    #[snafu(display("failed to read {path}", path = path.to_string_lossy()))]
+    ReadManifest { path: PathBuf, source: io::Error },
+    #[snafu(display("expect a plain file name for an output but got `{name}`"))]
+    InvalidName { name: String },
+    #[snafu(display("`{name}` is written more than once in a build"))]
+    Duplicate { name: String },
+    #[snafu(display("failed to write {path}", path = path.to_string_lossy()))]
+    Write { path: PathBuf, source: io::Error },
+    #[snafu(display("failed to remove stale output {path}", path = path.to_string_lossy()))]
+    RemoveStale { path: PathBuf, source: io::Error },
+}
+
+impl OutputDir {
+    /// Opens `path` as an output directory, creating it if missing.
+    pub fn open(path: impl AsRef<Path>) -> Result<OutputDir, OutputError> {
+        let path = path.as_ref().to_owned();
+        fs::create_dir_all(&path).context(CreateDirSnafu { path: &path })?;
+        let manifest_path = path.join(OUTPUT_MANIFEST_NAME);
+        let previous = match fs::read_to_string(&manifest_path) {
+            Ok(content) => content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                // never trust a name that could point outside of the directory.
+                .filter(|line| is_plain_file_name(line))
+                .map(str::to_owned)
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeSet::new(),
+            Err(source) => {
+                return Err(OutputError::ReadManifest {
+                    path: manifest_path,
+                    source,
+                })
+            }
+        };
+        Ok(OutputDir {
+            path,
+            previous,
+            written: Mutex::default(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Atomically writes an artifact named `name` directly inside the directory.
+    pub fn write(&self, name: &str, bytes: impl AsRef<[u8]>) -> Result<(), OutputError> {
+        ensure!(
+            is_plain_file_name(name) && name != OUTPUT_MANIFEST_NAME,
+            InvalidNameSnafu { name }
+        );
+        ensure!(
+            self.written().insert(name.to_owned()),
+            DuplicateSnafu { name }
+        );
+        self.write_atomic(name, bytes.as_ref())?;
+        // record every artifact as soon as it lands, so an interrupted build can be cleaned up.
+        // The lock is held while saving, so the manifest is never written by two threads at once.
+        let written = self.written();
+        self.save_manifest(self.previous.union(&written))
+    }
+
+    /// Atomically writes every artifact, stopping at the first failure.
+    pub fn write_artifacts<'a>(
+        &self,
+        artifacts: impl IntoIterator<Item = &'a Artifact>,
+    ) -> Result<(), OutputError> {
+        for artifact in artifacts {
+            self.write(&artifact.file_name, &artifact.bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Removes artifacts of the previous build which were not written this time,
+    /// and records the written ones for the next build, whose names are returned.
+    pub fn finish(self) -> Result<BTreeSet<String>, OutputError> {
+        let written = self.written().clone();
+        for name in self.previous.difference(&written) {
+            let path = self.path.join(name);
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(OutputError::RemoveStale { path, source: e })
+                }
+                _ => {}
+            }
+        }
+        self.save_manifest(written.iter())?;
+        Ok(written)
+    }
+
+    fn written(&self) -> MutexGuard<'_, BTreeSet<String>> {
+        // a panic while holding the lock leaves the set itself intact.
+        self.written.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn save_manifest<'a>(
+        &self,
+        names: impl Iterator<Item = &'a String>,
+    ) -> Result<(), OutputError> {
+        let mut content = String::from(
+            "# files written by the last build, removed by the next one unless written again\n",
+        );
+        for name in names {
+            content.push_str(name);
+            content.push('\n');
+        }
+        self.write_atomic(OUTPUT_MANIFEST_NAME, content.as_bytes())
+    }
+
+    fn write_atomic(&self, name: &str, bytes: &[u8]) -> Result<(), OutputError> {
+        let path = self.path.join(name);
+        write_atomic(&path, bytes).context(WriteSnafu { path })
+    }
+}
+
+/// Writes `bytes` to a temporary file next to `path` and renames it into place.
+pub(super) fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    // same directory as the destination, so the rename never crosses file systems.
+    let temp_path = path.with_file_name(format!(".{name}.{}.tmp", process::id()));
+    let result = fs::write(&temp_path, bytes).and_then(|()| fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+fn is_plain_file_name(name: &str) -> bool {
+    !name.contains(['/', '\\']) && Path::new(name).file_name() == Some(name.as_ref())
+}
+
+/// Template of output file names without extension, from `file-name` of the `[build]` section.
+///
+/// `{family}` and `{subfamily}` are replaced with the font names,
+/// `{version}` with `major.minor`, and `{{` and `}}` with literal braces.
+/// The default is `{family} {subfamily}`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct FileNameTemplate(Vec<FileNamePart>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FileNamePart {
+    Literal(String),
+    Family,
+    SubFamily,
+    Version,
+}
+
+#[derive(Debug, Snafu)]
+pub enum FileNameTemplateError {
+    #[snafu(display("unknown placeholder `{{{name}}}` in file name"))]
+    UnknownPlaceholder { name: String },
+    #[snafu(display("unclosed `{{` in file name"))]
+    Unclosed,
+    #[snafu(display("unmatched `}}` in file name"))]
+    Unmatched,
+    #[snafu(display("file name must not contain path separators"))]
+    PathSeparator,
+}
+
+impl FileNameTemplate {
+    /// File name for a font with `options`, with `extension` appended.
+    pub fn render(&self, options: &FontOptions, extension: &str) -> String {
+        let mut name = String::new();
+        for part in &self.0 {
+            match part {
+                FileNamePart::Literal(literal) => name.push_str(literal),
+                FileNamePart::Family => name.push_str(&options.family_name),
+                FileNamePart::SubFamily => name.push_str(&options.sub_family_name),
+                FileNamePart::Version => name.push_str(&options.version.to_string()),
+            }
+        }
+        // font names may contain slashes, keep them from escaping the output directory.
+        let mut name = name.replace(['/', '\\'], "_");
+        name.push('.');
+        name.push_str(extension);
+        name
+    }
+}
+
+impl Default for FileNameTemplate {
+    fn default() -> Self {
+        FileNameTemplate(vec![
+            FileNamePart::Family,
+            FileNamePart::Literal(" ".to_owned()),
+            FileNamePart::SubFamily,
+        ])
+    }
+}
+
+impl FromStr for FileNameTemplate {
+    type Err = FileNameTemplateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ensure!(!s.contains(['/', '\\']), PathSeparatorSnafu);
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '{' if chars.next_if_eq(&'{').is_some() => literal.push('{'),
+                '}' if chars.next_if_eq(&'}').is_some() => literal.push('}'),
+                '}' => return UnmatchedSnafu.fail(),
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(ch) => name.push(ch),
+                            None => return UnclosedSnafu.fail(),
+                        }
+                    }
+                    let part = match name.as_str() {
+                        "family" => FileNamePart::Family,
+                        "subfamily" => FileNamePart::SubFamily,
+                        "version" => FileNamePart::Version,
+                        _ => return UnknownPlaceholderSnafu { name }.fail(),
+                    };
+                    if !literal.is_empty() {
+                        parts.push(FileNamePart::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(part);
+                }
+                ch => literal.push(ch),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(FileNamePart::Literal(literal));
+        }
+        Ok(FileNameTemplate(parts))
+    }
+}
+
+impl TryFrom<String> for FileNameTemplate {
+    type Error = FileNameTemplateError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, thread};
+
+    use crate::{backend::FontStyle, font::test_font};
+
+    use super::*;
+
+    fn manifest(dir: &Path) -> Vec<String> {
+        fs::read_to_string(dir.join(OUTPUT_MANIFEST_NAME))
+            .unwrap()
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(str::to_owned)
+            .collect()
+    }
+
+    #[test]
+    fn records_writes_from_every_thread() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = OutputDir::open(dir.path()).unwrap();
+        thread::scope(|scope| {
+            for idx in 0..16 {
+                let output = &output;
+                scope.spawn(move || output.write(&format!("{idx:02}.bin"), [idx]).unwrap());
+            }
+        });
+        assert!(matches!(
+            output.write("03.bin", [0]),
+            Err(OutputError::Duplicate { .. })
+        ));
+        assert_eq!(fs::read(dir.path().join("03.bin")).unwrap(), [3]);
+        output.finish().unwrap();
+
+        let expected: Vec<_> = (0..16).map(|idx| format!("{idx:02}.bin")).collect();
+        assert_eq!(manifest(dir.path()), expected, "in name order");
+    }
+
+    #[test]
+    fn removes_only_stale_artifacts() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("mine.txt"), "").unwrap();
+        let output = OutputDir::open(dir.path()).unwrap();
+        output.write("a.ttf", "old").unwrap();
+        output.write("b.ttf", "old").unwrap();
+        output.finish().unwrap();
+
+        let output = OutputDir::open(dir.path()).unwrap();
+        output.write("a.ttf", "new").unwrap();
+        assert_eq!(manifest(dir.path()), ["a.ttf", "b.ttf"], "until finished");
+        assert_eq!(
+            output.finish().unwrap(),
+            BTreeSet::from(["a.ttf".to_owned()])
+        );
+        assert_eq!(fs::read_to_string(dir.path().join("a.ttf")).unwrap(), "new");
+        assert!(!dir.path().join("b.ttf").exists());
+        assert!(dir.path().join("mine.txt").exists());
+        assert_eq!(manifest(dir.path()), ["a.ttf"]);
+    }
+
+    #[test]
+    fn never_leaves_the_directory() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("out");
+        fs::create_dir(&dir).unwrap();
+        fs::write(root.path().join("keep.txt"), "").unwrap();
+        fs::write(
+            dir.join(OUTPUT_MANIFEST_NAME),
+            "../keep.txt\n/keep.txt\n..\n",
+        )
+        .unwrap();
+        let output = OutputDir::open(&dir).unwrap();
+        for name in [
+            "../a.ttf",
+            "a/b.ttf",
+            "a\\b.ttf",
+            "..",
+            "",
+            OUTPUT_MANIFEST_NAME,
+        ] {
+            assert!(
+                matches!(output.write(name, ""), Err(OutputError::InvalidName { .. })),
+                "{name}"
+            );
+        }
+        output.finish().unwrap();
+        assert!(root.path().join("keep.txt").exists());
+        assert!(manifest(&dir).is_empty());
+    }
+
+    #[test]
+    fn renders_file_names() {
+        let mut options = test_font("", 1, 0, FontStyle::default()).options;
+        options.family_name = "A/B".to_owned();
+        let render = |template: &str| {
+            template
+                .parse::<FileNameTemplate>()
+                .unwrap()
+                .render(&options, "ttf")
+        };
+        assert_eq!(
+            FileNameTemplate::default().render(&options, "bdf"),
+            "A_B Regular.bdf"
+        );
+        assert_eq!(
+            render("{family}-{subfamily}-{version}"),
+            "A_B-Regular-1.0.ttf"
+        );
+        assert_eq!(render("{{{family}}}"), "{A_B}.ttf");
+        assert_eq!(render("plain"), "plain.ttf");
+    }
+
+    #[test]
+    fn rejects_bad_file_name_templates() {
+        let parse = |template: &str| template.parse::<FileNameTemplate>().unwrap_err();
+        assert!(matches!(
+            parse("{style}"),
+            FileNameTemplateError::UnknownPlaceholder { name } if name == "style"
+        ));
+        assert!(matches!(parse("{family"), FileNameTemplateError::Unclosed));
+        assert!(matches!(parse("family}"), FileNameTemplateError::Unmatched));
+        assert!(matches!(
+            parse("a/{family}"),
+            FileNameTemplateError::PathSeparator
+        ));
+        assert!(toml::from_str::<BTreeMap<String, FileNameTemplate>>("a = \"{x}\"").is_err());
+    }
+}
@@ -0,0 +1,758 @@
+use std::io::{self, Write};
+
+use flate2::{Compression, GzBuilder};
+use serde::Deserialize;
+use snafu::prelude::*;
+
+use crate::{
+    diagnostic::{Diagnostic, DiagnosticCode},
+    font::{Font, Glyph},
+};
+
+use super::{
+    xlfd::{glyph_name, Xlfd, XlfdValue},
+    Artifact, BuildReporter, FileNameTemplate, FontBackend, Progress,
+};
+
+const PCF_PROPERTIES: u32 = 1 << 0;
+const PCF_ACCELERATORS: u32 = 1 << 1;
+const PCF_METRICS: u32 = 1 << 2;
+const PCF_BITMAPS: u32 = 1 << 3;
+const PCF_BDF_ENCODINGS: u32 = 1 << 5;
+const PCF_SWIDTHS: u32 = 1 << 6;
+const PCF_GLYPH_NAMES: u32 = 1 << 7;
+const PCF_BDF_ACCELERATORS: u32 = 1 << 8;
+
+const PCF_DEFAULT_FORMAT: u32 = 0x000;
+const PCF_ACCEL_W_INKBOUNDS: u32 = 0x100;
+const PCF_COMPRESSED_METRICS: u32 = 0x100;
+const PCF_BYTE_MSB_FIRST: u32 = 1 << 2;
+const PCF_BIT_MSB_FIRST: u32 = 1 << 3;
+
+/// Code of a glyph missing from [`PCF_BDF_ENCODINGS`].
+const NO_GLYPH: u16 = 0xFFFF;
+
+/// Builds PCF fonts, which X11 core fonts are loaded from, without needing `bdftopcf`.
+///
+/// Like [`BdfBackend`](super::BdfBackend), every inked pixel is set regardless of its color.
+/// Codepoints beyond the Basic Multilingual Plane cannot be encoded and are left out.
+pub struct PcfBackend {
+    options: PcfOptions,
+}
+
+#[derive(Debug, Clone)]
+pub struct PcfOptions {
+    /// Name of the output file, which `.pcf` or `.pcf.gz` is appended to.
+    pub file_name: FileNameTemplate,
+    /// `FOUNDRY` of the XLFD name, left empty if `None`.
+    pub foundry: Option<String>,
+    /// Resolution in dots per inch, which the point size is derived from.
+    pub resolution: u16,
+    /// Order of pixels within a byte of bitmaps.
+    pub bit_order: PcfOrder,
+    /// Order of bytes within integers and scan units of bitmaps.
+    pub byte_order: PcfOrder,
+    /// Bytes every bitmap row is padded to, one of 1, 2, 4 and 8.
+    pub glyph_padding: u8,
+    /// Bytes of bitmaps whose order is swapped as a unit, one of 1, 2 and 4.
+    pub scan_unit: u8,
+    /// Compress the output with gzip, which X servers read as well.
+    pub gzip: bool,
+}
+
+/// Which end of a byte or an integer comes first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PcfOrder {
+    /// Most significant first, like `bdftopcf` does by default.
+    #[default]
+    Msb,
+    Lsb,
+}
+
+/// Defaults of `bdftopcf`.
+impl Default for PcfOptions {
+    fn default() -> Self {
+        PcfOptions {
+            file_name: FileNameTemplate::default(),
+            foundry: None,
+            resolution: 72,
+            bit_order: PcfOrder::Msb,
+            byte_order: PcfOrder::Msb,
+            glyph_padding: 4,
+            scan_unit: 1,
+            gzip: false,
+        }
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum PcfBuildError {
+    #[snafu(display("font height must not be zero"))]
+    FontHeightZero,
+    #[snafu(display("resolution must not be zero"))]
+    ResolutionZero,
+    #[snafu(display("expect glyph padding of 1, 2, 4 or 8 but got {padding}"))]
+    InvalidPadding { padding: u8 },
+    #[snafu(display(
+        "expect a scan unit of 1, 2 or 4 no larger than glyph padding but got {scan_unit}"
+    ))]
+    InvalidScanUnit { scan_unit: u8 },
+    #[snafu(display("expect fewer than 65535 glyphs but got {count}"))]
+    TooManyGlyphs { count: usize },
+    #[snafu(display("failed to compress the font"))]
+    Gzip { source: io::Error },
+}
+
+impl PcfBackend {
+    pub fn new(options: PcfOptions) -> Self {
+        PcfBackend { options }
+    }
+}
+
+impl FontBackend for PcfBackend {
+    type Err = PcfBuildError;
+
+    fn build(&self, font: &Font, reporter: &dyn BuildReporter) -> Result<Vec<Artifact>, Self::Err> {
+        let options = &self.options;
+        ensure!(font.options.height > 0, FontHeightZeroSnafu);
+        ensure!(options.resolution > 0, ResolutionZeroSnafu);
+        ensure!(
+            matches!(options.glyph_padding, 1 | 2 | 4 | 8),
+            InvalidPaddingSnafu {
+                padding: options.glyph_padding
+            }
+        );
+        ensure!(
+            matches!(options.scan_unit, 1 | 2 | 4) && options.scan_unit <= options.glyph_padding,
+            InvalidScanUnitSnafu {
+                scan_unit: options.scan_unit
+            }
+        );
+        ensure!(
+            font.glyphs.len() < NO_GLYPH as usize,
+            TooManyGlyphsSnafu {
+                count: font.glyphs.len()
+            }
+        );
+        let extension = if options.gzip { "pcf.gz" } else { "pcf" };
+        let file_name = options.file_name.render(&font.options, extension);
+        for sequence in font.features.ligatures.keys() {
+            reporter.diagnostic(Diagnostic::warning(
+                DiagnosticCode::UnsupportedFeature,
+                &file_name,
+                None,
+                format!(
+                    "ligature {:?} is left unencoded, PCF has no ligatures",
+                    sequence.iter().collect::<String>()
+                ),
+            ));
+        }
+        let beyond_bmp: Vec<_> = font.cmap.keys().filter(|&&ch| ch > '\u{FFFF}').collect();
+        if let (Some(first), Some(last)) = (beyond_bmp.first(), beyond_bmp.last()) {
+            reporter.diagnostic(Diagnostic::warning(
+                DiagnosticCode::UnsupportedFeature,
+                &file_name,
+                None,
+                format!(
+                    "{} characters from U+{:04X} to U+{:04X} are left unencoded, PCF only encodes 16-bit codes",
+                    beyond_bmp.len(),
+                    **first as u32,
+                    **last as u32
+                ),
+            ));
+        }
+
+        let pcf = PcfWriter::new(font, options).write();
+        let (bytes, mime_type) = if options.gzip {
+            let mut encoder = GzBuilder::new().write(Vec::new(), Compression::best());
+            encoder.write_all(&pcf).context(GzipSnafu)?;
+            (encoder.finish().context(GzipSnafu)?, "application/gzip")
+        } else {
+            (pcf, "application/x-font-pcf")
+        };
+        reporter.progress(Progress {
+            font: &font.options.full_name(),
+            stage: "writing glyphs",
+            done: font.glyphs.len(),
+            total: font.glyphs.len(),
+        });
+        Ok(vec![Artifact {
+            file_name,
+            mime_type,
+            bytes,
+        }])
+    }
+}
+
+/// Metrics of a glyph as X stores them, which cover its ink like `BBX` of BDF.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Metrics {
+    left_side_bearing: i16,
+    right_side_bearing: i16,
+    character_width: i16,
+    ascent: i16,
+    descent: i16,
+}
+
+impl Metrics {
+    fn fields(&self) -> [i16; 5] {
+        [
+            self.left_side_bearing,
+            self.right_side_bearing,
+            self.character_width,
+            self.ascent,
+            self.descent,
+        ]
+    }
+
+    fn zip(self, other: Metrics, f: impl Fn(i16, i16) -> i16) -> Metrics {
+        let [a, b, c, d, e] = self.fields();
+        let [v, w, x, y, z] = other.fields();
+        Metrics {
+            left_side_bearing: f(a, v),
+            right_side_bearing: f(b, w),
+            character_width: f(c, x),
+            ascent: f(d, y),
+            descent: f(e, z),
+        }
+    }
+}
+
+struct PcfWriter<'a> {
+    font: &'a Font,
+    options: &'a PcfOptions,
+    /// Format of every table, besides the flags of each table.
+    format: u32,
+    metrics: Vec<Metrics>,
+}
+
+/// Bytes of a table, whose integers are in the byte order of its format.
+struct Table {
+    bytes: Vec<u8>,
+    order: PcfOrder,
+}
+
+impl Table {
+    /// Starts a table with its format, which is always least significant byte first.
+    fn new(format: u32) -> Table {
+        let order = if format & PCF_BYTE_MSB_FIRST != 0 {
+            PcfOrder::Msb
+        } else {
+            PcfOrder::Lsb
+        };
+        Table {
+            bytes: format.to_le_bytes().to_vec(),
+            order,
+        }
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn i16(&mut self, value: i16) {
+        self.bytes.extend(match self.order {
+            PcfOrder::Msb => value.to_be_bytes(),
+            PcfOrder::Lsb => value.to_le_bytes(),
+        });
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.bytes.extend(match self.order {
+            PcfOrder::Msb => value.to_be_bytes(),
+            PcfOrder::Lsb => value.to_le_bytes(),
+        });
+    }
+
+    fn metrics(&mut self, metrics: Metrics) {
+        for field in metrics.fields() {
+            self.i16(field);
+        }
+        // attributes, which are free for applications.
+        self.i16(0);
+    }
+
+    fn pad_to_4(&mut self) {
+        self.bytes.resize(self.bytes.len().next_multiple_of(4), 0);
+    }
+}
+
+impl<'a> PcfWriter<'a> {
+    fn new(font: &'a Font, options: &'a PcfOptions) -> PcfWriter<'a> {
+        let mut format = PCF_DEFAULT_FORMAT;
+        format |= options.glyph_padding.trailing_zeros();
+        format |= options.scan_unit.trailing_zeros() << 4;
+        if options.byte_order == PcfOrder::Msb {
+            format |= PCF_BYTE_MSB_FIRST;
+        }
+        if options.bit_order == PcfOrder::Msb {
+            format |= PCF_BIT_MSB_FIRST;
+        }
+        let metrics = font
+            .glyphs
+            .iter()
+            .map(|glyph| {
+                let character_width = glyph.metrics.advance as i16;
+                match font.ink_box(glyph) {
+                    Some(ink) => Metrics {
+                        left_side_bearing: ink.x as i16,
+                        right_side_bearing: (ink.x + ink.width as i32) as i16,
+                        character_width,
+                        ascent: (ink.y + ink.height as i32) as i16,
+                        descent: -ink.y as i16,
+                    },
+                    None => Metrics {
+                        character_width,
+                        ..Metrics::default()
+                    },
+                }
+            })
+            .collect();
+        PcfWriter {
+            font,
+            options,
+            format,
+            metrics,
+        }
+    }
+
+    fn write(&self) -> Vec<u8> {
+        let tables = [
+            (PCF_PROPERTIES, self.properties()),
+            (PCF_ACCELERATORS, self.accelerators()),
+            (PCF_METRICS, self.metrics_table()),
+            (PCF_BITMAPS, self.bitmaps()),
+            (PCF_BDF_ENCODINGS, self.encodings()),
+            (PCF_SWIDTHS, self.swidths()),
+            (PCF_GLYPH_NAMES, self.glyph_names()),
+            (PCF_BDF_ACCELERATORS, self.accelerators()),
+        ];
+        // the table of contents is always least significant byte first.
+        let mut bytes = b"\x01fcp".to_vec();
+        bytes.extend((tables.len() as u32).to_le_bytes());
+        let mut offset = bytes.len() + tables.len() * 16;
+        for (kind, table) in &tables {
+            let format = u32::from_le_bytes(table.bytes[..4].try_into().unwrap());
+            for value in [*kind, format, table.bytes.len() as u32, offset as u32] {
+                bytes.extend(value.to_le_bytes());
+            }
+            offset += table.bytes.len();
+        }
+        for (_, table) in tables {
+            bytes.extend(table.bytes);
+        }
+        bytes
+    }
+
+    fn properties(&self) -> Table {
+        let xlfd = Xlfd::new(
+            self.font,
+            self.options.foundry.as_deref(),
+            self.options.resolution,
+        );
+        let mut properties = vec![("FONT", XlfdValue::String(xlfd.name))];
+        properties.extend(xlfd.properties);
+
+        let mut strings = Vec::new();
+        let mut add_string = |value: &str| {
+            let offset = strings.len() as i32;
+            strings.extend(value.as_bytes());
+            strings.push(0);
+            offset
+        };
+        let mut table = Table::new(self.format);
+        table.i32(properties.len() as i32);
+        for (name, value) in &properties {
+            table.i32(add_string(name));
+            match value {
+                XlfdValue::String(value) => {
+                    table.u8(1);
+                    table.i32(add_string(value));
+                }
+                XlfdValue::Integer(value) => {
+                    table.u8(0);
+                    table.i32(*value);
+                }
+            }
+        }
+        table.pad_to_4();
+        table.i32(strings.len() as i32);
+        table.bytes.extend(strings);
+        table.pad_to_4();
+        table
+    }
+
+    fn accelerators(&self) -> Table {
+        let options = &self.font.options;
+        let font_ascent = self.font.baseline() as i16;
+        let font_descent = options.descender as i16;
+        let min = self
+            .metrics
+            .iter()
+            .copied()
+            .reduce(|a, b| a.zip(b, i16::min));
+        let max = self
+            .metrics
+            .iter()
+            .copied()
+            .reduce(|a, b| a.zip(b, i16::max));
+        let (min, max) = (min.unwrap_or_default(), max.unwrap_or_default());
+        let max_overlap = self
+            .metrics
+            .iter()
+            .map(|metrics| metrics.right_side_bearing - metrics.character_width)
+            .max()
+            .unwrap_or(0);
+        let constant_metrics = min == max;
+        let terminal_font = constant_metrics
+            && max.left_side_bearing == 0
+            && max.right_side_bearing == max.character_width
+            && max.ascent == font_ascent
+            && max.descent == font_descent;
+        let ink_inside = self.metrics.iter().all(|metrics| {
+            metrics.left_side_bearing >= 0
+                && metrics.right_side_bearing <= metrics.character_width
+                && metrics.ascent <= font_ascent
+                && metrics.descent <= font_descent
+        });
+
+        let mut table = Table::new(self.format | PCF_ACCEL_W_INKBOUNDS);
+        for flag in [
+            max_overlap <= min.left_side_bearing,
+            constant_metrics,
+            terminal_font,
+            min.character_width == max.character_width,
+            ink_inside,
+            // ink metrics are the metrics themselves.
+            false,
+            // left to right.
+            false,
+        ] {
+            table.u8(flag as u8);
+        }
+        table.u8(0);
+        table.i32(font_ascent as i32);
+        table.i32(font_descent as i32);
+        table.i32(max_overlap as i32);
+        for bounds in [min, max, min, max] {
+            table.metrics(bounds);
+        }
+        table
+    }
+
+    fn metrics_table(&self) -> Table {
+        let is_compressible = self
+            .metrics
+            .iter()
+            .flat_map(Metrics::fields)
+            .all(|field| (-128..=127).contains(&field));
+        if !is_compressible {
+            let mut table = Table::new(self.format);
+            table.i32(self.metrics.len() as i32);
+            for metrics in &self.metrics {
+                table.metrics(*metrics);
+            }
+            return table;
+        }
+        let mut table = Table::new(self.format | PCF_COMPRESSED_METRICS);
+        table.i16(self.metrics.len() as i16);
+        for metrics in &self.metrics {
+            for field in metrics.fields() {
+                table.u8((field + 0x80) as u8);
+            }
+        }
+        table.pad_to_4();
+        table
+    }
+
+    fn bitmaps(&self) -> Table {
+        let padding = self.options.glyph_padding as usize;
+        let row_len = |metrics: &Metrics, padding: usize| {
+            let width = (metrics.right_side_bearing - metrics.left_side_bearing) as usize;
+            width.div_ceil(8 * padding) * padding
+        };
+        let height = |metrics: &Metrics| (metrics.ascent + metrics.descent) as usize;
+
+        let mut offsets = Vec::new();
+        let mut data = Vec::new();
+        for (glyph, metrics) in self.font.glyphs.iter().zip(&self.metrics) {
+            offsets.push(data.len() as i32);
+            if let Some(ink) = self.font.ink_box(glyph) {
+                let row_len = row_len(metrics, padding);
+                for row in ink.row..ink.row + ink.height {
+                    data.extend(self.bitmap_row(glyph, row, ink.col, ink.width, row_len));
+                }
+            }
+        }
+
+        let mut table = Table::new(self.format);
+        table.i32(self.metrics.len() as i32);
+        for offset in offsets {
+            table.i32(offset);
+        }
+        // the size of bitmaps for every padding, as readers may want another one.
+        for padding in [1, 2, 4, 8] {
+            let size: usize = self
+                .metrics
+                .iter()
+                .map(|metrics| row_len(metrics, padding) * height(metrics))
+                .sum();
+            table.i32(size as i32);
+        }
+        table.bytes.extend(data);
+        table.pad_to_4();
+        table
+    }
+
+    /// A row of the bitmap in the bit and byte order of the options.
+    fn bitmap_row(
+        &self,
+        glyph: &Glyph,
+        row: usize,
+        col: usize,
+        width: usize,
+        len: usize,
+    ) -> Vec<u8> {
+        let mut bytes = vec![0u8; len];
+        for x in 0..width {
+            if glyph.is_inked(row, col + x) {
+                bytes[x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        if self.options.bit_order == PcfOrder::Lsb {
+            for byte in &mut bytes {
+                *byte = byte.reverse_bits();
+            }
+        }
+        // bytes are laid out for the bit order, and swapped within scan units if the byte order differs.
+        if self.options.bit_order != self.options.byte_order {
+            for unit in bytes.chunks_mut(self.options.scan_unit as usize) {
+                unit.reverse();
+            }
+        }
+        bytes
+    }
+
+    fn encodings(&self) -> Table {
+        let codes: Vec<(u16, u16)> = self
+            .font
+            .cmap
+            .iter()
+            .filter_map(|(&ch, id)| Some((u16::try_from(ch as u32).ok()?, id.0 as u16)))
+            .collect();
+        let byte1 = |code: u16| (code >> 8) as i16;
+        let byte2 = |code: u16| (code & 0xFF) as i16;
+        let range = |f: &dyn Fn(u16) -> i16| {
+            let values = codes.iter().map(|&(code, _)| f(code));
+            (values.clone().min().unwrap_or(0), values.max().unwrap_or(0))
+        };
+        let (min_byte1, max_byte1) = range(&byte1);
+        let (min_byte2, max_byte2) = range(&byte2);
+        let columns = (max_byte2 - min_byte2 + 1) as usize;
+        let rows = (max_byte1 - min_byte1 + 1) as usize;
+        let mut indices = vec![NO_GLYPH; columns * rows];
+        for &(code, glyph) in &codes {
+            let row = (byte1(code) - min_byte1) as usize;
+            let column = (byte2(code) - min_byte2) as usize;
+            indices[row * columns + column] = glyph;
+        }
+        let default_char = if self.font.cmap.contains_key(&'\u{FFFD}') {
+            0xFFFD
+        } else {
+            0
+        };
+
+        let mut table = Table::new(self.format);
+        for value in [min_byte2, max_byte2, min_byte1, max_byte1] {
+            table.i16(value);
+        }
+        table.i16(default_char as i16);
+        for index in indices {
+            table.i16(index as i16);
+        }
+        table.pad_to_4();
+        table
+    }
+
+    fn swidths(&self) -> Table {
+        let pixel_size = self.font.options.height as usize;
+        let mut table = Table::new(self.format);
+        table.i32(self.font.glyphs.len() as i32);
+        for glyph in &self.font.glyphs {
+            let advance = glyph.metrics.advance;
+            table.i32(((advance * 1000 + pixel_size / 2) / pixel_size) as i32);
+        }
+        table
+    }
+
+    fn glyph_names(&self) -> Table {
+        let mut strings = Vec::new();
+        let mut table = Table::new(self.format);
+        table.i32(self.font.glyphs.len() as i32);
+        for glyph in &self.font.glyphs {
+            table.i32(strings.len() as i32);
+            strings.extend(glyph_name(self.font, glyph).as_bytes());
+            strings.push(0);
+        }
+        table.i32(strings.len() as i32);
+        table.bytes.extend(strings);
+        table.pad_to_4();
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use crate::{
+        backend::{FontStyle, SilentReporter},
+        font::test_font,
+    };
+
+    use super::*;
+
+    const SOURCE: &str = "'A':\n  .@.\n  @.@\n  @@@\n  ...\n\n\
+                          'j':\n  ..@\n  ...\n  ..@\n  @@.\n\n\
+                          \"dot\":\n  ...\n  .@.\n  ...\n  ...\n";
+
+    fn build(font: &Font, options: PcfOptions) -> Vec<u8> {
+        let mut artifacts = PcfBackend::new(options)
+            .build(font, &SilentReporter)
+            .unwrap();
+        artifacts.remove(0).bytes
+    }
+
+    /// Tables of a PCF file by their type, in the order of the table of contents.
+    fn tables(pcf: &[u8]) -> Vec<(u32, &[u8])> {
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(pcf[offset..offset + 4].try_into().unwrap());
+        assert_eq!(&pcf[..4], b"\x01fcp");
+        (0..u32_at(4) as usize)
+            .map(|idx| {
+                let entry = 8 + idx * 16;
+                let (size, offset) = (u32_at(entry + 8) as usize, u32_at(entry + 12) as usize);
+                assert_eq!(
+                    u32_at(offset),
+                    u32_at(entry + 4),
+                    "format is repeated in the table"
+                );
+                (u32_at(entry), &pcf[offset..offset + size])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lays_out_tables_back_to_back() {
+        let font = test_font(SOURCE, 4, 1, FontStyle::default());
+        let pcf = build(&font, PcfOptions::default());
+        let tables = tables(&pcf);
+        let kinds: Vec<_> = tables.iter().map(|(kind, _)| *kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                PCF_PROPERTIES,
+                PCF_ACCELERATORS,
+                PCF_METRICS,
+                PCF_BITMAPS,
+                PCF_BDF_ENCODINGS,
+                PCF_SWIDTHS,
+                PCF_GLYPH_NAMES,
+                PCF_BDF_ACCELERATORS,
+            ]
+        );
+        let sizes: usize = tables.iter().map(|(_, table)| table.len()).sum();
+        assert_eq!(8 + 8 * 16 + sizes, pcf.len());
+        assert!(tables.iter().all(|(_, table)| table.len() % 4 == 0));
+
+        // glyph 0 is 'A' at column 0 and glyph 1 is 'j' at column 41 of the single row.
+        let encodings = tables[4].1;
+        let i16_at = |offset: usize| i16::from_be_bytes([encodings[offset], encodings[offset + 1]]);
+        assert_eq!(
+            [i16_at(4), i16_at(6), i16_at(8), i16_at(10)],
+            [65, 106, 0, 0]
+        );
+        let indices: Vec<_> = (0..42).map(|idx| i16_at(14 + idx * 2) as u16).collect();
+        assert_eq!((indices[0], indices[41]), (0, 1));
+        assert!(indices[1..41].iter().all(|&index| index == NO_GLYPH));
+
+        let names = tables[6].1;
+        assert!(names.ends_with(b"uni0041\0uni006A\0dot\0"));
+    }
+
+    #[test]
+    fn writes_bitmaps_in_the_requested_order() {
+        let font = test_font(SOURCE, 4, 1, FontStyle::default());
+        let rows = |options: PcfOptions, len: usize| {
+            let writer = PcfWriter::new(&font, &options);
+            let glyph = &font.glyphs[0];
+            (0..3)
+                .map(|row| writer.bitmap_row(glyph, row, 0, 3, len))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            rows(PcfOptions::default(), 1),
+            [[0x40], [0xA0], [0xE0]],
+            "most significant bit first"
+        );
+        let lsb = PcfOptions {
+            bit_order: PcfOrder::Lsb,
+            byte_order: PcfOrder::Lsb,
+            ..Default::default()
+        };
+        assert_eq!(rows(lsb, 1), [[0x02], [0x05], [0x07]]);
+        let swapped = PcfOptions {
+            byte_order: PcfOrder::Lsb,
+            glyph_padding: 2,
+            scan_unit: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            rows(swapped, 2),
+            [[0x00, 0x40], [0x00, 0xA0], [0x00, 0xE0]],
+            "bytes swapped within scan units"
+        );
+    }
+
+    #[test]
+    fn compresses_with_gzip() {
+        let font = test_font(SOURCE, 4, 1, FontStyle::default());
+        let plain = build(&font, PcfOptions::default());
+        let gzip = build(
+            &font,
+            PcfOptions {
+                gzip: true,
+                ..Default::default()
+            },
+        );
+        let mut decompressed = Vec::new();
+        GzDecoder::new(&gzip[..])
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, plain);
+    }
+
+    #[test]
+    fn rejects_invalid_layouts() {
+        let font = test_font(SOURCE, 4, 1, FontStyle::default());
+        let build = |options| PcfBackend::new(options).build(&font, &SilentReporter);
+        assert!(matches!(
+            build(PcfOptions {
+                glyph_padding: 3,
+                ..Default::default()
+            }),
+            Err(PcfBuildError::InvalidPadding { padding: 3 })
+        ));
+        assert!(matches!(
+            build(PcfOptions {
+                glyph_padding: 1,
+                scan_unit: 2,
+                ..Default::default()
+            }),
+            Err(PcfBuildError::InvalidScanUnit { scan_unit: 2 })
+        ));
+    }
+}
@@ -0,0 +1,392 @@
+use serde::Deserialize;
+use snafu::prelude::*;
+
+use crate::{
+    diagnostic::{Diagnostic, DiagnosticCode},
+    font::{Font, Glyph},
+};
+
+use super::{Artifact, BuildReporter, FileNameTemplate, FontBackend, Progress};
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_MODE512: u8 = 0x01;
+const PSF1_MODEHASTAB: u8 = 0x02;
+const PSF1_MODEHASSEQ: u8 = 0x04;
+const PSF1_SEPARATOR: u16 = 0xFFFF;
+const PSF1_STARTSEQ: u16 = 0xFFFE;
+
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xb5, 0x4a, 0x86];
+const PSF2_HEADER_SIZE: u32 = 32;
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+const PSF2_SEPARATOR: u8 = 0xFF;
+const PSF2_STARTSEQ: u8 = 0xFE;
+
+/// Builds PC Screen Fonts, which the Linux console loads with `setfont`.
+///
+/// Every glyph is drawn into a fixed cell as high as the font, with its origin at the left edge,
+/// and pixels outside of the cell are clipped with a warning.
+/// The Unicode table maps every glyph to its codepoints and character sequences.
+pub struct PsfBackend {
+    options: PsfOptions,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PsfOptions {
+    /// Name of the output file, which `.psf` is appended to.
+    pub file_name: FileNameTemplate,
+    pub version: PsfVersion,
+    /// Width of the cell in pixels, the widest advance of the font if `None`.
+    pub cell_width: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PsfVersion {
+    /// The original format with 256 or 512 glyphs 8 pixels wide,
+    /// which every console tool reads.
+    Psf1,
+    /// Any number of glyphs of any size.
+    #[default]
+    Psf2,
+}
+
+#[derive(Debug, Snafu)]
+pub enum PsfBuildError {
+    #[snafu(display("font height must not be zero"))]
+    FontHeightZero,
+    #[snafu(display("cell width must not be zero"))]
+    CellWidthZero,
+    #[snafu(display("PSF1 cells must be at most 8 pixels wide but got {width}"))]
+    Psf1CellTooWide { width: usize },
+    #[snafu(display("PSF1 cells must be at most 255 pixels high but got {height}"))]
+    Psf1CellTooTall { height: usize },
+    #[snafu(display("PSF1 fonts hold at most 512 glyphs but got {count}"))]
+    Psf1TooManyGlyphs { count: usize },
+}
+
+impl PsfBackend {
+    pub fn new(options: PsfOptions) -> Self {
+        PsfBackend { options }
+    }
+}
+
+impl FontBackend for PsfBackend {
+    type Err = PsfBuildError;
+
+    fn build(&self, font: &Font, reporter: &dyn BuildReporter) -> Result<Vec<Artifact>, Self::Err> {
+        let height = font.options.height as usize;
+        let width = match self.options.cell_width {
+            Some(width) => width as usize,
+            None => font.max_advance(),
+        };
+        ensure!(height > 0, FontHeightZeroSnafu);
+        ensure!(width > 0, CellWidthZeroSnafu);
+        if self.options.version == PsfVersion::Psf1 {
+            ensure!(width <= 8, Psf1CellTooWideSnafu { width });
+            ensure!(height <= 255, Psf1CellTooTallSnafu { height });
+            ensure!(
+                font.glyphs.len() <= 512,
+                Psf1TooManyGlyphsSnafu {
+                    count: font.glyphs.len()
+                }
+            );
+        }
+        let file_name = self.options.file_name.render(&font.options, "psf");
+        let writer = PsfWriter {
+            font,
+            width,
+            height,
+        };
+        for glyph in &font.glyphs {
+            if !writer.fits(glyph) {
+                reporter.diagnostic(Diagnostic::warning(
+                    DiagnosticCode::GlyphClipped,
+                    &file_name,
+                    None,
+                    format!(
+                        "glyph {} does not fit the {width}x{height} cell and is clipped",
+                        glyph.display_labels()
+                    ),
+                ));
+            }
+        }
+
+        let bytes = match self.options.version {
+            PsfVersion::Psf1 => {
+                let unencodable: Vec<_> = font
+                    .cmap
+                    .keys()
+                    .copied()
+                    .chain(font.features.ligatures.keys().flatten().copied())
+                    .filter(|&ch| ch > '\u{FFFF}')
+                    .collect();
+                if !unencodable.is_empty() {
+                    reporter.diagnostic(Diagnostic::warning(
+                        DiagnosticCode::UnsupportedFeature,
+                        &file_name,
+                        None,
+                        format!(
+                            "{} characters beyond U+FFFF are left out of the Unicode table, \
+                             PSF1 only encodes 16-bit codes",
+                            unencodable.len()
+                        ),
+                    ));
+                }
+                writer.psf1()
+            }
+            PsfVersion::Psf2 => writer.psf2(),
+        };
+        reporter.progress(Progress {
+            font: &font.options.full_name(),
+            stage: "writing glyphs",
+            done: font.glyphs.len(),
+            total: font.glyphs.len(),
+        });
+        Ok(vec![Artifact {
+            file_name,
+            mime_type: "application/x-font-linux-psf",
+            bytes,
+        }])
+    }
+}
+
+struct PsfWriter<'a> {
+    font: &'a Font,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> PsfWriter<'a> {
+    /// Whether every inked pixel of `glyph` is inside of the cell.
+    fn fits(&self, glyph: &Glyph) -> bool {
+        self.font.ink_box(glyph).is_none_or(|ink| {
+            ink.x >= 0
+                && ink.x as usize + ink.width <= self.width
+                && ink.row + ink.height <= self.height
+        })
+    }
+
+    /// Rows of the cell as bytes, most significant bit first and padded to whole bytes.
+    fn bitmap(&self, glyph: &Glyph, row_len: usize) -> Vec<u8> {
+        let overhang = glyph.metrics.overhang;
+        let mut bytes = vec![0u8; row_len * self.height];
+        for row in 0..self.height {
+            for x in 0..self.width {
+                if glyph.is_inked(row, x + overhang) {
+                    bytes[row * row_len + x / 8] |= 0x80 >> (x % 8);
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Codepoints and then character sequences of `glyph`, as the Unicode table lists them.
+    fn mappings(&self, glyph: &Glyph) -> (Vec<char>, Vec<&'a [char]>) {
+        let codepoints = self.font.codepoints(glyph.id).collect();
+        let sequences = self
+            .font
+            .features
+            .ligatures
+            .iter()
+            .filter(|(_, id)| **id == glyph.id)
+            .map(|(sequence, _)| sequence.as_slice())
+            .collect();
+        (codepoints, sequences)
+    }
+
+    fn psf1(&self) -> Vec<u8> {
+        let count = if self.font.glyphs.len() > 256 {
+            512
+        } else {
+            256
+        };
+        let has_sequences = !self.font.features.ligatures.is_empty();
+        let mut mode = PSF1_MODEHASTAB;
+        if count == 512 {
+            mode |= PSF1_MODE512;
+        }
+        if has_sequences {
+            mode |= PSF1_MODEHASSEQ;
+        }
+        let mut bytes = PSF1_MAGIC.to_vec();
+        bytes.push(mode);
+        bytes.push(self.height as u8);
+        for glyph in &self.font.glyphs {
+            bytes.extend(self.bitmap(glyph, 1));
+        }
+        // unused slots are left blank.
+        bytes.resize(
+            bytes.len() + (count - self.font.glyphs.len()) * self.height,
+            0,
+        );
+
+        let encode = |bytes: &mut Vec<u8>, chars: &[char]| -> bool {
+            let Ok(units) = chars
+                .iter()
+                .map(|&ch| u16::try_from(ch as u32))
+                .collect::<Result<Vec<_>, _>>()
+            else {
+                return false;
+            };
+            for unit in units {
+                bytes.extend(unit.to_le_bytes());
+            }
+            true
+        };
+        for glyph in &self.font.glyphs {
+            let (codepoints, sequences) = self.mappings(glyph);
+            for ch in codepoints {
+                encode(&mut bytes, &[ch]);
+            }
+            for sequence in sequences {
+                let mut entry = PSF1_STARTSEQ.to_le_bytes().to_vec();
+                if encode(&mut entry, sequence) {
+                    bytes.extend(entry);
+                }
+            }
+            bytes.extend(PSF1_SEPARATOR.to_le_bytes());
+        }
+        for _ in self.font.glyphs.len()..count {
+            bytes.extend(PSF1_SEPARATOR.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn psf2(&self) -> Vec<u8> {
+        let row_len = self.width.div_ceil(8);
+        let mut bytes = PSF2_MAGIC.to_vec();
+        for value in [
+            0,
+            PSF2_HEADER_SIZE,
+            PSF2_HAS_UNICODE_TABLE,
+            self.font.glyphs.len() as u32,
+            (row_len * self.height) as u32,
+            self.height as u32,
+            self.width as u32,
+        ] {
+            bytes.extend(value.to_le_bytes());
+        }
+        for glyph in &self.font.glyphs {
+            bytes.extend(self.bitmap(glyph, row_len));
+        }
+
+        let mut buf = [0; 4];
+        for glyph in &self.font.glyphs {
+            let (codepoints, sequences) = self.mappings(glyph);
+            for ch in codepoints {
+                bytes.extend(ch.encode_utf8(&mut buf).as_bytes());
+            }
+            for sequence in sequences {
+                bytes.push(PSF2_STARTSEQ);
+                for ch in sequence {
+                    bytes.extend(ch.encode_utf8(&mut buf).as_bytes());
+                }
+            }
+            bytes.push(PSF2_SEPARATOR);
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backend::{CollectingReporter, FontStyle},
+        font::test_font,
+    };
+
+    use super::*;
+
+    /// 'A' also mapped to the Greek capital alpha, and a ligature of `ab`.
+    const SOURCE: &str = "'A':\nu+0391:\n  .@.\n  @.@\n  @@@\n  ...\n\n\
+                          'ab':\n  @@@\n  ...\n  @@@\n  ...\n";
+
+    fn build(options: PsfOptions) -> (Vec<u8>, CollectingReporter) {
+        let font = test_font(SOURCE, 4, 1, FontStyle::default());
+        let reporter = CollectingReporter::default();
+        let mut artifacts = PsfBackend::new(options).build(&font, &reporter).unwrap();
+        (artifacts.remove(0).bytes, reporter)
+    }
+
+    #[test]
+    fn writes_psf2_with_unicode_table() {
+        let (psf, reporter) = build(PsfOptions::default());
+        assert!(reporter.codes().is_empty());
+        let header: Vec<_> = psf[4..32]
+            .chunks(4)
+            .map(|value| u32::from_le_bytes(value.try_into().unwrap()))
+            .collect();
+        assert_eq!(header, [0, 32, PSF2_HAS_UNICODE_TABLE, 2, 4, 4, 3]);
+        assert_eq!(
+            &psf[32..40],
+            [0x40, 0xA0, 0xE0, 0x00, 0xE0, 0x00, 0xE0, 0x00]
+        );
+        assert_eq!(&psf[40..], b"A\xCE\x91\xFF\xFEab\xFF");
+    }
+
+    #[test]
+    fn pads_psf1_to_256_glyphs() {
+        let (psf, _) = build(PsfOptions {
+            version: PsfVersion::Psf1,
+            ..Default::default()
+        });
+        assert_eq!(
+            psf[..4],
+            [
+                PSF1_MAGIC[0],
+                PSF1_MAGIC[1],
+                PSF1_MODEHASTAB | PSF1_MODEHASSEQ,
+                4
+            ]
+        );
+        let (bitmaps, table) = psf[4..].split_at(256 * 4);
+        assert_eq!(
+            bitmaps[..8],
+            [0x40, 0xA0, 0xE0, 0x00, 0xE0, 0x00, 0xE0, 0x00]
+        );
+        assert!(bitmaps[8..].iter().all(|&byte| byte == 0));
+        let units: Vec<_> = table
+            .chunks(2)
+            .map(|unit| u16::from_le_bytes(unit.try_into().unwrap()))
+            .collect();
+        assert_eq!(
+            units[..8],
+            [
+                0x41,
+                0x391,
+                PSF1_SEPARATOR,
+                PSF1_STARTSEQ,
+                0x61,
+                0x62,
+                PSF1_SEPARATOR,
+                PSF1_SEPARATOR
+            ]
+        );
+        assert_eq!(units.len(), 5 + 256, "a separator for every slot");
+    }
+
+    #[test]
+    fn clips_glyphs_to_the_cell() {
+        let (psf, reporter) = build(PsfOptions {
+            cell_width: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(reporter.codes(), [DiagnosticCode::GlyphClipped; 2]);
+        assert_eq!(&psf[32..36], [0x40, 0x80, 0xC0, 0x00]);
+    }
+
+    #[test]
+    fn rejects_wide_psf1_cells() {
+        let font = test_font(SOURCE, 4, 1, FontStyle::default());
+        let options = PsfOptions {
+            version: PsfVersion::Psf1,
+            cell_width: Some(9),
+            ..Default::default()
+        };
+        let result = PsfBackend::new(options).build(&font, &CollectingReporter::default());
+        assert!(matches!(
+            result,
+            Err(PsfBuildError::Psf1CellTooWide { width: 9 })
+        ));
+    }
+}
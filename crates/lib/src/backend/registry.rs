@@ -0,0 +1,474 @@
+use std::{collections::BTreeMap, error::Error, path::Path};
+
+use serde::{de::DeserializeOwned, Deserialize};
+use snafu::prelude::*;
+
+use crate::{font::Font, glyph::DiagonalRule, project::BuildTarget, OutlineSettings};
+
+use super::{
+    Artifact, BdfBackend, BdfOptions, BuildReporter, FileNameTemplate, FntVersion, FonBackend,
+    FonOptions, FontBackend, HexBackend, HexOptions, OpentypeTtfBackend, OpentypeTtfOptions,
+    PcfBackend, PcfOptions, PcfOrder, PsfBackend, PsfOptions, PsfVersion,
+};
+
+/// [`FontBackend`] with its error type erased, so backends of any format can be stored together.
+pub trait AnyFontBackend: Send + Sync {
+    fn build_any(
+        &self,
+        font: &Font,
+        reporter: &dyn BuildReporter,
+    ) -> Result<Vec<Artifact>, Box<dyn Error + Send + Sync>>;
+}
+
+impl<T> AnyFontBackend for T
+where
+    T: FontBackend + Send + Sync,
+    T::Err: Send + Sync + 'static,
+{
+    fn build_any(
+        &self,
+        font: &Font,
+        reporter: &dyn BuildReporter,
+    ) -> Result<Vec<Artifact>, Box<dyn Error + Send + Sync>> {
+        Ok(self.build(font, reporter)?)
+    }
+}
+
+/// Settings of the project a backend is made for, besides the options of its target.
+pub struct TargetContext<'a> {
+    /// Output file names, the one of the target if set or of `[build]` otherwise.
+    pub file_name: FileNameTemplate,
+    pub outline: &'a OutlineSettings,
+    /// Directory to keep compiled glyphs in between builds, no caching if `None`.
+    pub cache_dir: Option<&'a Path>,
+}
+
+/// Makes a backend from the format-specific options of a target.
+pub type BackendFactory =
+    fn(&toml::Table, &TargetContext) -> Result<Box<dyn AnyFontBackend>, toml::de::Error>;
+
+#[derive(Clone, Copy)]
+pub struct BackendEntry {
+    pub factory: BackendFactory,
+    /// Whether the format has outlines, so sub-families differing only in pixel shape
+    /// from `[outline.styles]` are worth building.
+    pub has_outlines: bool,
+}
+
+/// Backends by the format name used in `[[build.target]]`.
+#[derive(Clone, Default)]
+pub struct BackendRegistry {
+    entries: BTreeMap<&'static str, BackendEntry>,
+}
+
+#[derive(Debug, Snafu)]
+pub enum BackendRegistryError {
+    #[snafu(display("unknown build format `{format}`, expected one of {expected}"))]
+    UnknownFormat { format: String, expected: String },
+    #[snafu(display("invalid options for the `{format}` target"))]
+    InvalidOptions {
+        format: String,
+        source: toml::de::Error,
+    },
+}
+
+impl BackendRegistry {
+    /// Registry of every backend in this crate.
+    pub fn builtin() -> BackendRegistry {
+        let mut registry = BackendRegistry::default();
+        registry.register(
+            "ttf",
+            BackendEntry {
+                factory: |options, context| {
+                    Ok(Box::new(OpentypeTtfBackend::new(
+                        parse_target_options::<TtfTargetOptions>(options)?.resolve(context),
+                    )))
+                },
+                has_outlines: true,
+            },
+        );
+        registry.register(
+            "bdf",
+            BackendEntry {
+                factory: |options, context| {
+                    Ok(Box::new(BdfBackend::new(
+                        parse_target_options::<BdfTargetOptions>(options)?.resolve(context),
+                    )))
+                },
+                has_outlines: false,
+            },
+        );
+        registry.register(
+            "pcf",
+            BackendEntry {
+                factory: |options, context| {
+                    Ok(Box::new(PcfBackend::new(
+                        parse_target_options::<PcfTargetOptions>(options)?.resolve(context),
+                    )))
+                },
+                has_outlines: false,
+            },
+        );
+        registry.register(
+            "psf",
+            BackendEntry {
+                factory: |options, context| {
+                    Ok(Box::new(PsfBackend::new(
+                        parse_target_options::<PsfTargetOptions>(options)?.resolve(context),
+                    )))
+                },
+                has_outlines: false,
+            },
+        );
+        registry.register(
+            "fon",
+            BackendEntry {
+                factory: |options, context| {
+                    Ok(Box::new(FonBackend::new(
+                        parse_target_options::<FonTargetOptions>(options)?.resolve(context),
+                    )))
+                },
+                has_outlines: false,
+            },
+        );
+        registry.register(
+            "hex",
+            BackendEntry {
+                factory: |options, context| {
+                    parse_target_options::<NoTargetOptions>(options)?;
+                    Ok(Box::new(HexBackend::new(HexOptions {
+                        file_name: context.file_name.clone(),
+                    })))
+                },
+                has_outlines: false,
+            },
+        );
+        registry
+    }
+
+    /// Adds a backend for `format`, replacing the one registered before if any.
+    pub fn register(&mut self, format: &'static str, entry: BackendEntry) {
+        self.entries.insert(format, entry);
+    }
+
+    pub fn get(&self, format: &str) -> Result<&BackendEntry, BackendRegistryError> {
+        self.entries.get(format).context(UnknownFormatSnafu {
+            format,
+            expected: self
+                .formats()
+                .map(|format| format!("`{format}`"))
+                .collect::<Vec<_>>()
+                .join(", "),
+        })
+    }
+
+    /// Makes the backend of `target`.
+    pub fn create(
+        &self,
+        target: &BuildTarget,
+        context: &TargetContext,
+    ) -> Result<Box<dyn AnyFontBackend>, BackendRegistryError> {
+        let entry = self.get(&target.format)?;
+        (entry.factory)(&target.options, context).context(InvalidOptionsSnafu {
+            format: &target.format,
+        })
+    }
+
+    pub fn formats(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.entries.keys().copied()
+    }
+}
+
+/// Deserializes the format-specific options of a target, for use in a [`BackendFactory`].
+pub fn parse_target_options<T: DeserializeOwned>(
+    options: &toml::Table,
+) -> Result<T, toml::de::Error> {
+    toml::Value::Table(options.clone()).try_into()
+}
+
+/// Options of a `ttf` target, which override `[outline]` of the project.
+///
+/// ```toml
+/// [[build.target]]
+/// format = "ttf"
+/// diagonal = "split"
+/// remove-overlaps = true
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct TtfTargetOptions {
+    diagonal: Option<DiagonalRule>,
+    remove_overlaps: Option<bool>,
+}
+
+impl TtfTargetOptions {
+    fn resolve(self, context: &TargetContext) -> OpentypeTtfOptions {
+        let mut outline = context.outline.clone();
+        if let Some(diagonal) = self.diagonal {
+            outline.diagonal = diagonal;
+        }
+        if let Some(remove_overlaps) = self.remove_overlaps {
+            outline.remove_overlaps = remove_overlaps;
+        }
+        OpentypeTtfOptions {
+            outline,
+            file_name: context.file_name.clone(),
+            cache_dir: context.cache_dir.map(Path::to_owned),
+        }
+    }
+}
+
+/// Options of a `bdf` target.
+///
+/// ```toml
+/// [[build.target]]
+/// format = "bdf"
+/// foundry = "Misc"
+/// resolution = 75
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct BdfTargetOptions {
+    foundry: Option<String>,
+    resolution: Option<u16>,
+}
+
+impl BdfTargetOptions {
+    fn resolve(self, context: &TargetContext) -> BdfOptions {
+        let defaults = BdfOptions::default();
+        BdfOptions {
+            file_name: context.file_name.clone(),
+            foundry: self.foundry,
+            resolution: self.resolution.unwrap_or(defaults.resolution),
+        }
+    }
+}
+
+/// Options of a `pcf` target, which default to those of `bdftopcf`.
+///
+/// ```toml
+/// [[build.target]]
+/// format = "pcf"
+/// bit-order = "lsb"
+/// byte-order = "lsb"
+/// glyph-padding = 1
+/// gzip = true
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct PcfTargetOptions {
+    foundry: Option<String>,
+    resolution: Option<u16>,
+    bit_order: Option<PcfOrder>,
+    byte_order: Option<PcfOrder>,
+    glyph_padding: Option<u8>,
+    scan_unit: Option<u8>,
+    #[serde(default)]
+    gzip: bool,
+}
+
+impl PcfTargetOptions {
+    fn resolve(self, context: &TargetContext) -> PcfOptions {
+        let defaults = PcfOptions::default();
+        PcfOptions {
+            file_name: context.file_name.clone(),
+            foundry: self.foundry,
+            resolution: self.resolution.unwrap_or(defaults.resolution),
+            bit_order: self.bit_order.unwrap_or(defaults.bit_order),
+            byte_order: self.byte_order.unwrap_or(defaults.byte_order),
+            glyph_padding: self.glyph_padding.unwrap_or(defaults.glyph_padding),
+            scan_unit: self.scan_unit.unwrap_or(defaults.scan_unit),
+            gzip: self.gzip,
+        }
+    }
+}
+
+/// Options of a `psf` target.
+///
+/// ```toml
+/// [[build.target]]
+/// format = "psf"
+/// version = "psf1"
+/// cell-width = 8
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct PsfTargetOptions {
+    version: Option<PsfVersion>,
+    cell_width: Option<u8>,
+}
+
+impl PsfTargetOptions {
+    fn resolve(self, context: &TargetContext) -> PsfOptions {
+        PsfOptions {
+            file_name: context.file_name.clone(),
+            version: self.version.unwrap_or_default(),
+            cell_width: self.cell_width,
+        }
+    }
+}
+
+/// Options of a `fon` target.
+///
+/// ```toml
+/// [[build.target]]
+/// format = "fon"
+/// version = "3.0"
+/// code-page = "windows-1251"
+/// resolution = 96
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct FonTargetOptions {
+    version: Option<FntVersion>,
+    code_page: Option<String>,
+    resolution: Option<u16>,
+}
+
+impl FonTargetOptions {
+    fn resolve(self, context: &TargetContext) -> FonOptions {
+        let defaults = FonOptions::default();
+        FonOptions {
+            file_name: context.file_name.clone(),
+            version: self.version,
+            code_page: self.code_page.unwrap_or(defaults.code_page),
+            resolution: self.resolution.unwrap_or(defaults.resolution),
+        }
+    }
+}
+
+/// Options of a target which only takes `file-name`, so anything else is an error.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoTargetOptions {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backend::{FontStyle, SilentReporter},
+        font::test_font,
+    };
+
+    use super::*;
+
+    fn target(source: &str) -> BuildTarget {
+        toml::from_str(source).unwrap()
+    }
+
+    fn context(outline: &OutlineSettings) -> TargetContext<'_> {
+        TargetContext {
+            file_name: "out".parse().unwrap(),
+            outline,
+            cache_dir: None,
+        }
+    }
+
+    #[test]
+    fn registers_every_builtin_format() {
+        let registry = BackendRegistry::builtin();
+        assert_eq!(
+            Vec::from_iter(registry.formats()),
+            ["bdf", "fon", "hex", "pcf", "psf", "ttf"]
+        );
+        let with_outlines: Vec<_> = registry
+            .formats()
+            .filter(|format| registry.get(format).unwrap().has_outlines)
+            .collect();
+        assert_eq!(with_outlines, ["ttf"]);
+    }
+
+    #[test]
+    fn creates_backends_of_targets() {
+        let registry = BackendRegistry::builtin();
+        let outline = OutlineSettings::default();
+        let backend = registry
+            .create(
+                &target("format = \"bdf\"\nfoundry = \"Misc\""),
+                &context(&outline),
+            )
+            .unwrap();
+        let font = test_font("'A':\n  @\n", 1, 0, FontStyle::default());
+        let artifacts = backend.build_any(&font, &SilentReporter).unwrap();
+        assert_eq!(artifacts[0].file_name, "out.bdf");
+        let bdf = String::from_utf8(artifacts[0].bytes.clone()).unwrap();
+        assert!(bdf.contains("\nFONT -Misc-Test-"), "{bdf}");
+    }
+
+    #[test]
+    fn reports_unknown_formats_and_options() {
+        let registry = BackendRegistry::builtin();
+        let outline = OutlineSettings::default();
+        let error = registry
+            .create(&target("format = \"otf\""), &context(&outline))
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "unknown build format `otf`, expected one of `bdf`, `fon`, `hex`, `pcf`, `psf`, `ttf`"
+        );
+        for source in [
+            "format = \"ttf\"\nfoundry = \"Misc\"",
+            "format = \"bdf\"\nresolution = \"high\"",
+            "format = \"pcf\"\nbit-order = \"middle\"",
+            "format = \"psf\"\nversion = \"psf3\"",
+            "format = \"fon\"\nversion = \"1.0\"",
+            "format = \"hex\"\nfoundry = \"Misc\"",
+            "format = \"ttf\"\nshape = \"dot\"",
+        ] {
+            assert!(
+                matches!(
+                    registry.create(&target(source), &context(&outline)),
+                    Err(BackendRegistryError::InvalidOptions { .. })
+                ),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn fills_target_options_with_defaults() {
+        let outline = OutlineSettings {
+            remove_overlaps: true,
+            ..OutlineSettings::default()
+        };
+        let options: PcfTargetOptions =
+            parse_target_options(&toml::from_str("bit-order = \"lsb\"\ngzip = true").unwrap())
+                .unwrap();
+        let options = options.resolve(&context(&outline));
+        assert_eq!(
+            (options.bit_order, options.byte_order, options.glyph_padding),
+            (PcfOrder::Lsb, PcfOrder::Msb, 4)
+        );
+        assert!(options.gzip);
+
+        let options: TtfTargetOptions =
+            parse_target_options(&toml::from_str("diagonal = \"split\"").unwrap()).unwrap();
+        let options = options.resolve(&context(&outline));
+        assert_eq!(options.outline.diagonal, DiagonalRule::Split);
+        assert!(options.outline.remove_overlaps, "kept from the project");
+        assert_eq!(options.file_name, "out".parse().unwrap());
+    }
+
+    #[test]
+    fn replaces_registered_backends() {
+        let mut registry = BackendRegistry::builtin();
+        let ttf = *registry.get("ttf").unwrap();
+        registry.register("outline", ttf);
+        registry.register(
+            "ttf",
+            BackendEntry {
+                has_outlines: false,
+                ..ttf
+            },
+        );
+        assert_eq!(
+            Vec::from_iter(registry.formats()),
+            ["bdf", "fon", "hex", "outline", "pcf", "psf", "ttf"]
+        );
+        assert!(!registry.get("ttf").unwrap().has_outlines);
+        let outline = OutlineSettings::default();
+        assert!(registry
+            .create(&target("format = \"outline\""), &context(&outline))
+            .is_ok());
+    }
+}
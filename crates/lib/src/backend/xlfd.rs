@@ -0,0 +1,194 @@
+use yaff::SemanticGlyphLabel;
+
+use crate::font::{Font, Glyph};
+
+/// Name and properties of a font after the X Logical Font Description conventions,
+/// which BDF and PCF share.
+pub(super) struct Xlfd {
+    /// Like `-Misc-Bitkodi-Medium-R-Normal--8-80-72-72-M-40-ISO10646-1`.
+    pub(super) name: String,
+    /// Point size in decipoints.
+    pub(super) point_size: u32,
+    pub(super) properties: Vec<(&'static str, XlfdValue)>,
+}
+
+pub(super) enum XlfdValue {
+    String(String),
+    Integer(i32),
+}
+
+impl Xlfd {
+    pub(super) fn new(font: &Font, foundry: Option<&str>, resolution: u16) -> Xlfd {
+        let options = &font.options;
+        let resolution = resolution as u32;
+        let pixel_size = options.height as u32;
+        let point_size = (pixel_size * 720 + resolution / 2) / resolution;
+        let advances: Vec<_> = font
+            .glyphs
+            .iter()
+            .map(|glyph| glyph.metrics.advance)
+            .collect();
+        let is_monospaced = advances.windows(2).all(|pair| pair[0] == pair[1]);
+        // in tenths of pixels.
+        let average_width = match advances.len() {
+            0 => 0,
+            len => (advances.iter().sum::<usize>() * 10 + len / 2) / len,
+        };
+        let weight_class = options.weight_class();
+        let weight = weight_name(weight_class);
+        let slant = if options.style.is_italic() { "O" } else { "R" };
+        let spacing = if is_monospaced { "M" } else { "P" };
+        // properties repeat the fields of the name, so they are cleaned up the same way.
+        let foundry = field(foundry.unwrap_or(""));
+        let family = field(&options.family_name);
+        let name = format!(
+            "-{foundry}-{family}-{weight}-{slant}-Normal--{pixel_size}-{point_size}-{resolution}-{resolution}-{spacing}-{average_width}-ISO10646-1"
+        );
+
+        let string = |value: &str| XlfdValue::String(value.to_owned());
+        let integer = |value: u32| XlfdValue::Integer(value as i32);
+        let mut properties = vec![
+            ("FOUNDRY", string(&foundry)),
+            ("FAMILY_NAME", string(&family)),
+            ("WEIGHT_NAME", string(weight)),
+            ("RELATIVE_WEIGHT", integer(weight_class as u32 / 10)),
+            ("SLANT", string(slant)),
+            ("SETWIDTH_NAME", string("Normal")),
+            ("ADD_STYLE_NAME", string("")),
+            ("PIXEL_SIZE", integer(pixel_size)),
+            ("POINT_SIZE", integer(point_size)),
+            ("RESOLUTION_X", integer(resolution)),
+            ("RESOLUTION_Y", integer(resolution)),
+            ("SPACING", string(spacing)),
+            ("AVERAGE_WIDTH", integer(average_width as u32)),
+            ("CHARSET_REGISTRY", string("ISO10646")),
+            ("CHARSET_ENCODING", string("1")),
+            ("FACE_NAME", string(&options.full_name())),
+            ("FONT_VERSION", string(&options.version.to_string())),
+            ("FONT_ASCENT", integer(font.baseline() as u32)),
+            ("FONT_DESCENT", integer(options.descender as u32)),
+        ];
+        if let Some(copyright) = &options.copyright_notice {
+            properties.push(("COPYRIGHT", string(copyright)));
+        }
+        Xlfd {
+            name,
+            point_size,
+            properties,
+        }
+    }
+}
+
+/// XLFD weight name of an OpenType weight class, where the normal weight is `Medium`.
+fn weight_name(weight_class: u16) -> &'static str {
+    match weight_class {
+        ..=149 => "Thin",
+        150..=249 => "ExtraLight",
+        250..=349 => "Light",
+        350..=549 => "Medium",
+        550..=649 => "SemiBold",
+        650..=749 => "Bold",
+        750..=849 => "ExtraBold",
+        _ => "Black",
+    }
+}
+
+/// Field of an XLFD name, which must not contain the `-` separating fields.
+fn field(value: &str) -> String {
+    value.replace(['-', '\r', '\n'], " ")
+}
+
+/// Glyph name of a codepoint after the Adobe Glyph List conventions, like `uni0041`.
+pub(super) fn unicode_glyph_name(ch: char) -> String {
+    match ch as u32 {
+        code @ 0..=0xFFFF => format!("uni{code:04X}"),
+        code => format!("u{code:X}"),
+    }
+}
+
+/// Name of `glyph` after its first codepoint, or its first label if it has none.
+pub(super) fn glyph_name(font: &Font, glyph: &Glyph) -> String {
+    if let Some(ch) = font.codepoints(glyph.id).next() {
+        return unicode_glyph_name(ch);
+    }
+    let name = glyph
+        .labels
+        .iter()
+        .map(|label| match label {
+            SemanticGlyphLabel::Tag(tag) => tag.clone(),
+            SemanticGlyphLabel::CharSequence(vec) => vec
+                .iter()
+                .map(|&ch| unicode_glyph_name(ch))
+                .collect::<Vec<_>>()
+                .join("_"),
+        })
+        .next()
+        .unwrap_or_else(|| format!("glyph{}", glyph.id.0));
+    // names are written up to the end of a line in BDF.
+    name.replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{backend::FontStyle, font::test_font};
+
+    use super::*;
+
+    #[test]
+    fn names_fonts() {
+        let mut font = test_font("'A':\n  @.\n\n'B':\n  @@@\n", 1, 0, FontStyle::default());
+        font.options.family_name = "Pixel-Sans".to_owned();
+        let xlfd = Xlfd::new(&font, Some("My\nFoundry"), 75);
+        assert_eq!(
+            xlfd.name,
+            "-My Foundry-Pixel Sans-Medium-R-Normal--1-10-75-75-P-25-ISO10646-1"
+        );
+        assert_eq!(xlfd.point_size, 10);
+        assert!(!xlfd.properties.iter().any(|(key, _)| *key == "COPYRIGHT"));
+
+        font.options.copyright_notice = Some("(c) Test".to_owned());
+        let xlfd = Xlfd::new(&font, None, 72);
+        assert!(xlfd.name.starts_with("--Pixel Sans-"));
+        assert!(matches!(
+            xlfd.properties.last(),
+            Some(("COPYRIGHT", XlfdValue::String(copyright))) if copyright == "(c) Test"
+        ));
+    }
+
+    #[test]
+    fn names_weights() {
+        let names: Vec<_> = [1, 149, 150, 300, 400, 549, 600, 700, 800, 850, 1000]
+            .into_iter()
+            .map(weight_name)
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "Thin",
+                "Thin",
+                "ExtraLight",
+                "Light",
+                "Medium",
+                "Medium",
+                "SemiBold",
+                "Bold",
+                "ExtraBold",
+                "Black",
+                "Black"
+            ]
+        );
+    }
+
+    #[test]
+    fn names_glyphs() {
+        let source = "'A':\nu+0042:\n  @\n\n\"smiley\":\n  @\n\n'fi':\n  @\n\n\
+                      u+1F600:\n  @\n";
+        let font = test_font(source, 1, 0, FontStyle::default());
+        let names: Vec<_> = font
+            .glyphs
+            .iter()
+            .map(|glyph| glyph_name(&font, glyph))
+            .collect();
+        assert_eq!(names, ["uni0041", "u1F600", "smiley", "uni0066_uni0069"]);
+    }
+}
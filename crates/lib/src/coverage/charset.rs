@@ -0,0 +1,304 @@
+use std::{
+    collections::BTreeSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use snafu::prelude::*;
+
+/// A named set of characters to check the coverage against.
+#[derive(Debug, Clone)]
+pub struct Charset {
+    pub name: String,
+    pub chars: BTreeSet<char>,
+}
+
+#[derive(Debug, Snafu)]
+pub enum CharsetParseError {
+    #[snafu(display("line {line}: invalid codepoint `{token}`"))]
+    InvalidCodepoint { line: usize, token: String },
+    #[snafu(display("line {line}: range `{token}` is reversed"))]
+    ReversedRange { line: usize, token: String },
+}
+
+#[derive(Debug, Snafu)]
+pub enum CharsetLoadError {
+    #[snafu(display("failed to read {path}", path = path.to_string_lossy()))]
+    Io { path: PathBuf, source: io::Error },
+    #[snafu(display("failed to parse {path}", path = path.to_string_lossy()))]
+    Parse {
+        path: PathBuf,
+        source: CharsetParseError,
+    },
+}
+
+/// Windows Glyph List 4.
+const WGL4_RANGES: &[(u32, u32)] = &[
+    (0x0020, 0x007E),
+    (0x00A0, 0x017F),
+    (0x0192, 0x0192),
+    (0x01FA, 0x01FF),
+    (0x02C6, 0x02C7),
+    (0x02C9, 0x02C9),
+    (0x02D8, 0x02DD),
+    (0x0384, 0x038A),
+    (0x038C, 0x038C),
+    (0x038E, 0x03A1),
+    (0x03A3, 0x03CE),
+    (0x0401, 0x040C),
+    (0x040E, 0x044F),
+    (0x0451, 0x045C),
+    (0x045E, 0x045F),
+    (0x0490, 0x0491),
+    (0x1E80, 0x1E85),
+    (0x1EF2, 0x1EF3),
+    (0x2013, 0x2015),
+    (0x2017, 0x201E),
+    (0x2020, 0x2022),
+    (0x2026, 0x2026),
+    (0x2030, 0x2030),
+    (0x2032, 0x2033),
+    (0x2039, 0x203A),
+    (0x203C, 0x203C),
+    (0x203E, 0x203E),
+    (0x2044, 0x2044),
+    (0x207F, 0x207F),
+    (0x20A3, 0x20A4),
+    (0x20A7, 0x20A7),
+    (0x20AC, 0x20AC),
+    (0x2105, 0x2105),
+    (0x2113, 0x2113),
+    (0x2116, 0x2116),
+    (0x2122, 0x2122),
+    (0x2126, 0x2126),
+    (0x212E, 0x212E),
+    (0x215B, 0x215E),
+    (0x2190, 0x2195),
+    (0x21A8, 0x21A8),
+    (0x2202, 0x2202),
+    (0x2206, 0x2206),
+    (0x220F, 0x220F),
+    (0x2211, 0x2212),
+    (0x2215, 0x2215),
+    (0x2219, 0x221A),
+    (0x221E, 0x221F),
+    (0x2229, 0x2229),
+    (0x222B, 0x222B),
+    (0x2248, 0x2248),
+    (0x2260, 0x2261),
+    (0x2264, 0x2265),
+    (0x2302, 0x2302),
+    (0x2310, 0x2310),
+    (0x2320, 0x2321),
+    (0x2500, 0x2500),
+    (0x2502, 0x2502),
+    (0x250C, 0x250C),
+    (0x2510, 0x2510),
+    (0x2514, 0x2514),
+    (0x2518, 0x2518),
+    (0x251C, 0x251C),
+    (0x2524, 0x2524),
+    (0x252C, 0x252C),
+    (0x2534, 0x2534),
+    (0x253C, 0x253C),
+    (0x2550, 0x256C),
+    (0x2580, 0x2580),
+    (0x2584, 0x2584),
+    (0x2588, 0x2588),
+    (0x258C, 0x258C),
+    (0x2590, 0x2593),
+    (0x25A0, 0x25A1),
+    (0x25AA, 0x25AC),
+    (0x25B2, 0x25B2),
+    (0x25BA, 0x25BA),
+    (0x25BC, 0x25BC),
+    (0x25C4, 0x25C4),
+    (0x25CA, 0x25CB),
+    (0x25CF, 0x25CF),
+    (0x25D8, 0x25D9),
+    (0x25E6, 0x25E6),
+    (0x263A, 0x263C),
+    (0x2640, 0x2640),
+    (0x2642, 0x2642),
+    (0x2660, 0x2660),
+    (0x2663, 0x2663),
+    (0x2665, 0x2666),
+    (0x266A, 0x266B),
+    (0xFB01, 0xFB02),
+];
+
+fn from_ranges(ranges: &[(u32, u32)]) -> BTreeSet<char> {
+    ranges
+        .iter()
+        .flat_map(|&(start, end)| (start..=end).filter_map(char::from_u32))
+        .collect()
+}
+
+impl Charset {
+    pub fn new(name: impl AsRef<str>, chars: impl IntoIterator<Item = char>) -> Charset {
+        Charset {
+            name: name.as_ref().to_owned(),
+            chars: chars.into_iter().collect(),
+        }
+    }
+
+    /// Printable characters of ISO/IEC 8859-1.
+    pub fn latin_1() -> Charset {
+        Charset::new("Latin-1", from_ranges(&[(0x20, 0x7E), (0xA0, 0xFF)]))
+    }
+
+    pub fn wgl4() -> Charset {
+        Charset::new("WGL4", from_ranges(WGL4_RANGES))
+    }
+
+    /// Every character encodable in KS X 1001, the Korean national standard behind EUC-KR.
+    pub fn ks_x_1001() -> Charset {
+        let mut chars = BTreeSet::new();
+        for lead in 0xA1u8..=0xFE {
+            for trail in 0xA1u8..=0xFE {
+                let bytes = [lead, trail];
+                let Some(decoded) =
+                    encoding_rs::EUC_KR.decode_without_bom_handling_and_without_replacement(&bytes)
+                else {
+                    continue;
+                };
+                chars.extend(decoded.chars());
+            }
+        }
+        Charset::new("KS X 1001", chars)
+    }
+
+    /// Charsets shipped with the studio.
+    pub fn bundled() -> Vec<Charset> {
+        vec![Charset::latin_1(), Charset::wgl4(), Charset::ks_x_1001()]
+    }
+
+    /// Parses a charset file.
+    ///
+    /// Each whitespace-separated token is either `U+XXXX`, a range like `U+AC00..U+D7A3`,
+    /// or literal characters. Everything after `#` is a comment; write `U+0023` for the `#` itself.
+    pub fn parse(name: impl AsRef<str>, content: &str) -> Result<Charset, CharsetParseError> {
+        fn parse_codepoint(line: usize, token: &str) -> Result<char, CharsetParseError> {
+            token
+                .strip_prefix("U+")
+                .or_else(|| token.strip_prefix("u+"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .and_then(char::from_u32)
+                .context(InvalidCodepointSnafu { line, token })
+        }
+
+        let mut chars = BTreeSet::new();
+        for (idx, line) in content.lines().enumerate() {
+            let line_no = idx + 1;
+            let line = line.split_once('#').map_or(line, |(line, _)| line);
+            for token in line.split_whitespace() {
+                if let Some((start, end)) = token.split_once("..") {
+                    let start = parse_codepoint(line_no, start)?;
+                    let end = parse_codepoint(line_no, end)?;
+                    ensure!(
+                        start <= end,
+                        ReversedRangeSnafu {
+                            line: line_no,
+                            token
+                        }
+                    );
+                    chars.extend(start..=end);
+                } else if token.starts_with("U+") || token.starts_with("u+") {
+                    chars.insert(parse_codepoint(line_no, token)?);
+                } else {
+                    chars.extend(token.chars());
+                }
+            }
+        }
+        Ok(Charset::new(name, chars))
+    }
+
+    /// Loads a charset file, named after its file stem.
+    pub fn load(path: impl AsRef<Path>) -> Result<Charset, CharsetLoadError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).context(IoSnafu { path })?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+        Charset::parse(name, &content).context(ParseSnafu { path })
+    }
+
+    /// Loads every `*.txt` charset file directly inside `dir`, sorted by file name.
+    /// A missing directory is treated as having no charsets.
+    pub fn load_dir(dir: impl AsRef<Path>) -> Result<Vec<Charset>, CharsetLoadError> {
+        let dir = dir.as_ref();
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(source) => {
+                return Err(CharsetLoadError::Io {
+                    path: dir.to_owned(),
+                    source,
+                })
+            }
+        };
+        let mut paths = entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .context(IoSnafu { path: dir })?;
+        paths.retain(|path| path.extension().is_some_and(|ext| ext == "txt"));
+        paths.sort();
+        paths.into_iter().map(Charset::load).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_codepoints_ranges_and_literals() {
+        let content = "# punctuation\nU+0021 u+003F..U+0040 # and `#` as U+0023\n가나 U+0023\n";
+        let charset = Charset::parse("custom", content).unwrap();
+        assert_eq!(charset.name, "custom");
+        assert_eq!(charset.chars.into_iter().collect::<String>(), "!#?@가나");
+    }
+
+    #[test]
+    fn reports_bad_tokens_with_lines() {
+        let error = Charset::parse("custom", "A\nU+0041..U+0020\n").unwrap_err();
+        assert!(matches!(
+            error,
+            CharsetParseError::ReversedRange { line: 2, .. }
+        ));
+        let error = Charset::parse("custom", "U+D800\n").unwrap_err();
+        assert!(matches!(
+            error,
+            CharsetParseError::InvalidCodepoint { line: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn bundles_standard_charsets() {
+        let latin_1 = Charset::latin_1();
+        assert_eq!(latin_1.chars.len(), 95 + 96);
+        assert!(!latin_1.chars.contains(&'\u{7F}'));
+        let ks_x_1001 = Charset::ks_x_1001();
+        // 2350 precomposed Hangul syllables and 4888 Hanja, besides symbols.
+        assert!(ks_x_1001.chars.contains(&'가') && ks_x_1001.chars.contains(&'一'));
+        assert!(!ks_x_1001.chars.contains(&'똠'));
+    }
+
+    #[test]
+    fn loads_only_txt_files_in_name_order() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("b.txt"), "B").unwrap();
+        fs::write(dir.path().join("a.txt"), "A").unwrap();
+        fs::write(dir.path().join("notes.md"), "C").unwrap();
+        let names: Vec<_> = Charset::load_dir(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|charset| charset.name)
+            .collect();
+        assert_eq!(names, ["a", "b"]);
+        assert!(Charset::load_dir(dir.path().join("missing"))
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,286 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::PathBuf,
+};
+
+use unicode_blocks::{find_unicode_block, UnicodeBlock};
+use yaff::SemanticGlyphLabel;
+
+use crate::{project::resolve_char, Project};
+
+mod charset;
+
+pub use charset::{Charset, CharsetLoadError, CharsetParseError};
+
+/// Coverage of a single Unicode block or charset.
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    pub name: String,
+    pub covered: usize,
+    pub total: usize,
+    pub missing: Vec<char>,
+}
+
+/// A glyph whose file lives under `src/blocks/<Block>` of another block.
+#[derive(Debug, Clone)]
+pub struct MisfiledGlyph {
+    pub ch: char,
+    pub path: PathBuf,
+    pub filed_under: String,
+    /// `None` if the character does not belong to any Unicode block.
+    pub expected: Option<&'static str>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CoverageReport {
+    pub blocks: Vec<Coverage>,
+    pub charsets: Vec<Coverage>,
+    pub misfiled: Vec<MisfiledGlyph>,
+}
+
+impl Coverage {
+    fn new(name: impl AsRef<str>, expected: &BTreeSet<char>, chars: &BTreeSet<char>) -> Coverage {
+        let missing: Vec<_> = expected.difference(chars).copied().collect();
+        Coverage {
+            name: name.as_ref().to_owned(),
+            covered: expected.len() - missing.len(),
+            total: expected.len(),
+            missing,
+        }
+    }
+
+    /// Returns the covered ratio in percent, an empty set is considered fully covered.
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            100.0
+        } else {
+            self.covered as f64 * 100.0 / self.total as f64
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+impl CoverageReport {
+    /// Checks `project` against every Unicode block it touches (by glyph or by `src/blocks/<Block>` directory)
+    /// and against each of `charsets`.
+    pub fn new(project: &Project, charsets: &[Charset]) -> CoverageReport {
+        let chars = project_chars(project);
+
+        let mut misfiled = Vec::new();
+        let mut block_names = BTreeSet::new();
+        for file in &project.files {
+            let Some(filed_under) = &file.block else {
+                continue;
+            };
+            block_names.insert(filed_under.clone());
+            for glyph in file.document.list_glyph() {
+                for ch in resolve_chars(glyph.labels.iter().flat_map(|label| label.to_semantic())) {
+                    let expected = find_unicode_block(ch);
+                    if expected.is_some_and(|block| block.name().eq_ignore_ascii_case(filed_under))
+                    {
+                        continue;
+                    }
+                    misfiled.push(MisfiledGlyph {
+                        ch,
+                        path: file.path.clone(),
+                        filed_under: filed_under.clone(),
+                        expected: expected.map(|block| block.name()),
+                    });
+                }
+            }
+        }
+        misfiled.sort_by(|a, b| (&a.path, a.ch).cmp(&(&b.path, b.ch)));
+
+        let mut blocks = BTreeMap::new();
+        for ch in &chars {
+            if let Some(block) = find_unicode_block(*ch) {
+                blocks.insert(block.start(), block);
+            }
+        }
+        for name in &block_names {
+            if let Some(block) = find_block_by_name(name) {
+                blocks.insert(block.start(), block);
+            }
+        }
+        let blocks = blocks
+            .into_values()
+            .map(|block| {
+                let expected = (block.start()..=block.end())
+                    .filter_map(char::from_u32)
+                    .filter(|ch| unicode_names2::name(*ch).is_some() || chars.contains(ch))
+                    .collect();
+                Coverage::new(block.name(), &expected, &chars)
+            })
+            .collect();
+
+        let charsets = charsets
+            .iter()
+            .map(|charset| Coverage::new(&charset.name, &charset.chars, &chars))
+            .collect();
+
+        CoverageReport {
+            blocks,
+            charsets,
+            misfiled,
+        }
+    }
+}
+
+impl Project {
+    /// Reports coverage against the bundled charsets and the custom charset files in `charsets/` of the project.
+    pub fn coverage(&self) -> Result<CoverageReport, CharsetLoadError> {
+        let mut charsets = Charset::bundled();
+        charsets.extend(Charset::load_dir(self.path.join("charsets"))?);
+        Ok(CoverageReport::new(self, &charsets))
+    }
+}
+
+/// Collects every single character the project has a glyph for.
+fn project_chars(project: &Project) -> BTreeSet<char> {
+    resolve_chars(
+        project
+            .list_glyph()
+            .flat_map(|glyph| glyph.labels.iter().flat_map(|label| label.to_semantic())),
+    )
+    .collect()
+}
+
+fn resolve_chars(labels: impl Iterator<Item = SemanticGlyphLabel>) -> impl Iterator<Item = char> {
+    labels.filter_map(|label| resolve_char(&label))
+}
+
+fn find_block_by_name(name: &str) -> Option<UnicodeBlock> {
+    let mut codepoint = 0u32;
+    while codepoint <= char::MAX as u32 {
+        match char::from_u32(codepoint).and_then(find_unicode_block) {
+            Some(block) if block.name().eq_ignore_ascii_case(name) => return Some(block),
+            Some(block) => codepoint = block.end() + 1,
+            // every block starts at a multiple of 16.
+            None => codepoint = (codepoint & !0xF) + 0x10,
+        }
+    }
+    None
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}/{} ({:.1}%)",
+            self.name,
+            self.covered,
+            self.total,
+            self.percent()
+        )?;
+        if !self.missing.is_empty() {
+            write!(f, "\n  missing:")?;
+            for ch in &self.missing {
+                write!(f, " U+{:04X}", *ch as u32)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MisfiledGlyph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "U+{:04X} in {} is filed under \"{}\" but belongs to {}",
+            self.ch as u32,
+            self.path.to_string_lossy(),
+            self.filed_under,
+            self.expected
+                .map_or_else(|| "no block".to_owned(), |name| format!("\"{name}\""))
+        )
+    }
+}
+
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# Unicode blocks")?;
+        for coverage in &self.blocks {
+            writeln!(f, "{coverage}")?;
+        }
+        if !self.charsets.is_empty() {
+            writeln!(f, "\n# Charsets")?;
+            for coverage in &self.charsets {
+                writeln!(f, "{coverage}")?;
+            }
+        }
+        if !self.misfiled.is_empty() {
+            writeln!(f, "\n# Misfiled glyphs")?;
+            for glyph in &self.misfiled {
+                writeln!(f, "{glyph}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::project::temp_project;
+
+    use super::*;
+
+    #[test]
+    fn reports_blocks_charsets_and_misfiled_glyphs() {
+        let dir = temp_project(&[
+            ("project.toml", ""),
+            (
+                "src/blocks/Basic Latin/letters.yaff",
+                "'A':\n  @\n\n'é':\n  @\n",
+            ),
+            ("src/blocks/Arrows/empty.yaff", ""),
+            ("charsets/ab.txt", "AB"),
+        ]);
+        let (project, diagnostics) = Project::load(dir.path());
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        let report = project.coverage().unwrap();
+
+        let blocks: Vec<_> = report
+            .blocks
+            .iter()
+            .map(|block| (block.name.as_str(), block.covered, block.total))
+            .collect();
+        // control characters have no names and are not expected.
+        assert_eq!(
+            blocks,
+            [
+                ("Basic Latin", 1, 95),
+                ("Latin-1 Supplement", 1, 96),
+                ("Arrows", 0, 112),
+            ],
+            "blocks of glyphs and of directories"
+        );
+        let ab = report.charsets.last().unwrap();
+        assert_eq!(
+            (ab.name.as_str(), ab.missing.as_slice()),
+            ("ab", &['B'][..])
+        );
+        assert_eq!(ab.to_string(), "ab: 1/2 (50.0%)\n  missing: U+0042");
+
+        let [misfiled] = &report.misfiled[..] else {
+            panic!(
+                "expect a single misfiled glyph but got {:?}",
+                report.misfiled
+            );
+        };
+        assert_eq!(
+            (misfiled.ch, misfiled.expected),
+            ('é', Some("Latin-1 Supplement"))
+        );
+    }
+
+    #[test]
+    fn considers_empty_sets_covered() {
+        let coverage = Coverage::new("empty", &BTreeSet::new(), &BTreeSet::from(['A']));
+        assert!(coverage.is_complete());
+        assert_eq!(coverage.percent(), 100.0);
+    }
+}
@@ -0,0 +1,465 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::Deserialize;
+use snafu::prelude::*;
+use yaff::{GlyphDefinition, GlyphLabel};
+
+use crate::{glyph::BitmapMatrix, project::resolve_char, source_file::SourceFile};
+
+/// A rule describing how a glyph is made out of another glyph.
+///
+/// It is written in `project.toml` like:
+///
+/// ```toml
+/// [derive]
+/// ')' = "mirror-h('(')"
+/// '⊃' = "rotate-cw('∪')"
+/// 'q' = "mirror-v(mirror-h('b'))"
+/// '_' = "shift('-', 0, 3)"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum DeriveExpr {
+    Glyph(char),
+    Transform {
+        transform: GlyphTransform,
+        source: Box<DeriveExpr>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GlyphTransform {
+    MirrorHorizontal,
+    MirrorVertical,
+    RotateCw,
+    RotateCcw,
+    Rotate180,
+    Shift { dx: isize, dy: isize },
+}
+
+#[derive(Debug, Snafu)]
+pub enum DeriveExprParseError {
+    #[snafu(display("unexpected end of expression, expected {expected}"))]
+    UnexpectedEnd { expected: &'static str },
+    #[snafu(display("unexpected {found:?} at {offset}, expected {expected}"))]
+    Unexpected {
+        offset: usize,
+        found: char,
+        expected: &'static str,
+    },
+    #[snafu(display("unknown transform `{name}`"))]
+    UnknownTransform { name: String },
+    #[snafu(display("`{name}` takes {expected} number argument(s) but got {got}"))]
+    ArgumentCount {
+        name: String,
+        expected: usize,
+        got: usize,
+    },
+    #[snafu(display("invalid codepoint U+{codepoint}"))]
+    InvalidCodepoint { codepoint: String },
+}
+
+#[derive(Debug, Snafu)]
+pub enum DeriveError {
+    #[snafu(display("cannot derive {target:?}: source glyph {source_char:?} is not defined"))]
+    MissingSource { target: char, source_char: char },
+    #[snafu(display("cannot derive {target:?}: rules refer to each other in a cycle"))]
+    Cycle { target: char },
+}
+
+impl DeriveError {
+    /// Character whose rule failed.
+    pub fn target(&self) -> char {
+        match self {
+            DeriveError::MissingSource { target, .. } | DeriveError::Cycle { target } => *target,
+        }
+    }
+}
+
+impl TryFrom<String> for DeriveExpr {
+    type Error = DeriveExprParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl std::str::FromStr for DeriveExpr {
+    type Err = DeriveExprParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = ExprParser {
+            input: s,
+            offset: 0,
+        };
+        let expr = parser.parse_expr()?;
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(expr),
+            Some(found) => Err(DeriveExprParseError::Unexpected {
+                offset: parser.offset,
+                found,
+                expected: "end of expression",
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for DeriveExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeriveExpr::Glyph(ch) => write!(f, "'{ch}'"),
+            DeriveExpr::Transform { transform, source } => match transform {
+                GlyphTransform::MirrorHorizontal => write!(f, "mirror-h({source})"),
+                GlyphTransform::MirrorVertical => write!(f, "mirror-v({source})"),
+                GlyphTransform::RotateCw => write!(f, "rotate-cw({source})"),
+                GlyphTransform::RotateCcw => write!(f, "rotate-ccw({source})"),
+                GlyphTransform::Rotate180 => write!(f, "rotate-180({source})"),
+                GlyphTransform::Shift { dx, dy } => write!(f, "shift({source}, {dx}, {dy})"),
+            },
+        }
+    }
+}
+
+struct ExprParser<'a> {
+    input: &'a str,
+    offset: usize,
+}
+
+impl ExprParser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.offset..].chars().next()
+    }
+
+    fn bump(&mut self, expected: &'static str) -> Result<char, DeriveExprParseError> {
+        let ch = self.peek().context(UnexpectedEndSnafu { expected })?;
+        self.offset += ch.len_utf8();
+        Ok(ch)
+    }
+
+    fn expect(&mut self, ch: char, expected: &'static str) -> Result<(), DeriveExprParseError> {
+        self.skip_whitespace();
+        let offset = self.offset;
+        match self.bump(expected)? {
+            found if found == ch => Ok(()),
+            found => Err(DeriveExprParseError::Unexpected {
+                offset,
+                found,
+                expected,
+            }),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(ch) = self.peek().filter(|ch| ch.is_whitespace()) {
+            self.offset += ch.len_utf8();
+        }
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &str {
+        let begin = self.offset;
+        while let Some(ch) = self.peek().filter(|ch| f(*ch)) {
+            self.offset += ch.len_utf8();
+        }
+        &self.input[begin..self.offset]
+    }
+
+    fn parse_expr(&mut self) -> Result<DeriveExpr, DeriveExprParseError> {
+        self.skip_whitespace();
+        let offset = self.offset;
+        match self.peek() {
+            Some('\'') => {
+                self.offset += 1;
+                let ch = self.bump("character")?;
+                self.expect('\'', "closing quote")?;
+                Ok(DeriveExpr::Glyph(ch))
+            }
+            Some('u' | 'U') if self.input[offset..].get(1..2) == Some("+") => {
+                self.offset += 2;
+                let codepoint = self.take_while(|ch| ch.is_ascii_hexdigit()).to_owned();
+                u32::from_str_radix(&codepoint, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .map(DeriveExpr::Glyph)
+                    .context(InvalidCodepointSnafu { codepoint })
+            }
+            Some(ch) if ch.is_ascii_alphabetic() => {
+                let name = self
+                    .take_while(|ch| ch.is_ascii_alphanumeric() || ch == '-')
+                    .to_owned();
+                self.expect('(', "opening parenthesis")?;
+                let source = self.parse_expr()?;
+                let mut args = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() != Some(',') {
+                        break;
+                    }
+                    self.offset += 1;
+                    self.skip_whitespace();
+                    let offset = self.offset;
+                    let arg = self.take_while(|ch| ch == '-' || ch == '+' || ch.is_ascii_digit());
+                    args.push(arg.parse::<isize>().map_err(|_| {
+                        DeriveExprParseError::Unexpected {
+                            offset,
+                            found: self.peek().unwrap_or(' '),
+                            expected: "integer",
+                        }
+                    })?);
+                }
+                self.expect(')', "closing parenthesis")?;
+                let transform = GlyphTransform::from_name(&name, &args)?;
+                Ok(DeriveExpr::Transform {
+                    transform,
+                    source: Box::new(source),
+                })
+            }
+            Some(found) => Err(DeriveExprParseError::Unexpected {
+                offset,
+                found,
+                expected: "quoted character, U+XXXX or transform",
+            }),
+            None => Err(DeriveExprParseError::UnexpectedEnd {
+                expected: "quoted character, U+XXXX or transform",
+            }),
+        }
+    }
+}
+
+impl GlyphTransform {
+    fn from_name(name: &str, args: &[isize]) -> Result<GlyphTransform, DeriveExprParseError> {
+        let expected = match name {
+            "mirror-h" | "mirror-v" | "rotate-cw" | "rotate-ccw" | "rotate-180" => 0,
+            "shift" => 2,
+            _ => {
+                return Err(DeriveExprParseError::UnknownTransform {
+                    name: name.to_owned(),
+                })
+            }
+        };
+        ensure!(
+            args.len() == expected,
+            ArgumentCountSnafu {
+                name,
+                expected,
+                got: args.len(),
+            }
+        );
+        Ok(match (name, args) {
+            ("mirror-h", _) => GlyphTransform::MirrorHorizontal,
+            ("mirror-v", _) => GlyphTransform::MirrorVertical,
+            ("rotate-cw", _) => GlyphTransform::RotateCw,
+            ("rotate-ccw", _) => GlyphTransform::RotateCcw,
+            ("rotate-180", _) => GlyphTransform::Rotate180,
+            (_, &[dx, dy]) => GlyphTransform::Shift { dx, dy },
+            _ => unreachable!("argument count is checked above"),
+        })
+    }
+
+    pub fn apply(&self, matrix: &BitmapMatrix) -> BitmapMatrix {
+        match self {
+            GlyphTransform::MirrorHorizontal => matrix.mirror_horizontal(),
+            GlyphTransform::MirrorVertical => matrix.mirror_vertical(),
+            GlyphTransform::RotateCw => matrix.rotate_cw(),
+            GlyphTransform::RotateCcw => matrix.rotate_ccw(),
+            GlyphTransform::Rotate180 => matrix.rotate_180(),
+            GlyphTransform::Shift { dx, dy } => matrix.shifted(*dx, *dy),
+        }
+    }
+}
+
+/// Evaluates every derive rule against the loaded source files.
+///
+/// Rules are evaluated on every load, so a derived glyph always reflects the current state of its source.
+/// A glyph drawn explicitly in any source file wins over the rule for the same character.
+/// A rule that fails to evaluate is skipped and reported, other rules are still evaluated.
+pub fn derive_glyphs(
+    rules: &BTreeMap<char, DeriveExpr>,
+    files: &[SourceFile],
+) -> (Vec<GlyphDefinition>, Vec<DeriveError>) {
+    struct Evaluator<'a> {
+        rules: &'a BTreeMap<char, DeriveExpr>,
+        /// Drawn glyphs by every character their labels resolve to, the first file winning.
+        drawn: HashMap<char, &'a GlyphDefinition>,
+        resolved: HashMap<char, Option<BitmapMatrix>>,
+        visiting: HashSet<char>,
+    }
+
+    impl Evaluator<'_> {
+        /// Resolves `ch` on the way to derive `target`, which errors are reported for.
+        fn resolve_char(
+            &mut self,
+            target: char,
+            ch: char,
+        ) -> Result<Option<BitmapMatrix>, DeriveError> {
+            if let Some(&glyph) = self.drawn.get(&ch) {
+                return Ok(glyph.value.as_ref().map(|_| BitmapMatrix::from(glyph)));
+            }
+            if let Some(resolved) = self.resolved.get(&ch) {
+                return Ok(resolved.clone());
+            }
+            let Some(rule) = self.rules.get(&ch) else {
+                return Err(DeriveError::MissingSource {
+                    target,
+                    source_char: ch,
+                });
+            };
+            if !self.visiting.insert(ch) {
+                return Err(DeriveError::Cycle { target });
+            }
+            let matrix = self.evaluate(target, rule)?;
+            self.visiting.remove(&ch);
+            self.resolved.insert(ch, matrix.clone());
+            Ok(matrix)
+        }
+
+        fn evaluate(
+            &mut self,
+            target: char,
+            expr: &DeriveExpr,
+        ) -> Result<Option<BitmapMatrix>, DeriveError> {
+            match expr {
+                DeriveExpr::Glyph(ch) => self.resolve_char(target, *ch),
+                DeriveExpr::Transform { transform, source } => Ok(self
+                    .evaluate(target, source)?
+                    .map(|matrix| transform.apply(&matrix))),
+            }
+        }
+    }
+
+    let mut drawn = HashMap::new();
+    for file in files {
+        for glyph in file.document.list_glyph() {
+            for label in glyph.labels.iter().flat_map(|label| label.to_semantic()) {
+                if let Some(ch) = resolve_char(&label) {
+                    drawn.entry(ch).or_insert(glyph);
+                }
+            }
+        }
+    }
+    let mut evaluator = Evaluator {
+        rules,
+        drawn,
+        resolved: HashMap::new(),
+        visiting: HashSet::new(),
+    };
+
+    let mut result = Vec::new();
+    let mut errors = Vec::new();
+    for &target in rules.keys() {
+        if evaluator.drawn.contains_key(&target) {
+            continue;
+        }
+        let matrix = match evaluator.resolve_char(target, target) {
+            Ok(matrix) => matrix,
+            Err(e) => {
+                evaluator.visiting.clear();
+                errors.push(e);
+                continue;
+            }
+        };
+        result.push(GlyphDefinition {
+            labels: vec![GlyphLabel::CharacterSingle(target)],
+            indent: "  ".to_owned(),
+            value: matrix.and_then(|matrix| matrix.to_glyph_value()),
+        });
+    }
+
+    (result, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::project::{temp_project, Project};
+
+    use super::*;
+
+    /// Rows of a glyph with `@` for inked pixels.
+    fn rows(glyph: &GlyphDefinition) -> Vec<String> {
+        let value = glyph.value.as_ref().unwrap();
+        value
+            .data
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|px| if px.is_some() { '@' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_and_displays_rules() {
+        for rule in [
+            "'('",
+            "mirror-h('(')",
+            "mirror-v(mirror-h('b'))",
+            "rotate-cw('∪')",
+            "shift('-', 0, -3)",
+        ] {
+            assert_eq!(rule.parse::<DeriveExpr>().unwrap().to_string(), rule);
+        }
+        assert_eq!(
+            " U+41 ".parse::<DeriveExpr>().unwrap(),
+            DeriveExpr::Glyph('A')
+        );
+        assert!(matches!(
+            "spin('a')".parse::<DeriveExpr>(),
+            Err(DeriveExprParseError::UnknownTransform { .. })
+        ));
+        assert!(matches!(
+            "shift('a', 1)".parse::<DeriveExpr>(),
+            Err(DeriveExprParseError::ArgumentCount { got: 1, .. })
+        ));
+        assert!(matches!(
+            "mirror-h('a'".parse::<DeriveExpr>(),
+            Err(DeriveExprParseError::UnexpectedEnd { .. })
+        ));
+        assert!(matches!(
+            "'a' 'b'".parse::<DeriveExpr>(),
+            Err(DeriveExprParseError::Unexpected { offset: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn derives_glyphs_from_drawn_and_derived_ones() {
+        let manifest = "[derive]\n\
+                        'a' = \"mirror-h('m')\"\n\
+                        'm' = \"'n'\"\n\
+                        'b' = \"'q'\"\n\
+                        'd' = \"mirror-h('b')\"\n\
+                        'p' = \"mirror-v('b')\"\n\
+                        'q' = \"rotate-180('d')\"\n\
+                        'x' = \"'y'\"\n\
+                        'y' = \"'x'\"\n\
+                        '(' = \"mirror-h(')')\"\n\
+                        ')' = \"mirror-h('(')\"\n";
+        let dir = temp_project(&[
+            ("project.toml", manifest),
+            ("src/b.yaff", "'b':\n  @..\n  @@@\n  @.@\n  @@@\n"),
+            (
+                "src/paren.yaff",
+                "\"RIGHT PARENTHESIS\":\n  @.\n  .@\n  @.\n",
+            ),
+        ]);
+        let (project, diagnostics) = Project::load(dir.path());
+        let messages: Vec<_> = diagnostics.iter().map(|d| d.message.clone()).collect();
+        assert_eq!(
+            messages,
+            [
+                "cannot derive 'a': source glyph 'n' is not defined",
+                "cannot derive 'm': source glyph 'n' is not defined",
+                "cannot derive 'x': rules refer to each other in a cycle",
+                "cannot derive 'y': rules refer to each other in a cycle",
+            ]
+        );
+
+        let derived = |ch| rows(project.find_glyph(ch).unwrap().glyph);
+        assert_eq!(derived('b'), ["@..", "@@@", "@.@", "@@@"], "drawn wins");
+        assert_eq!(derived('d'), ["..@", "@@@", "@.@", "@@@"]);
+        assert_eq!(derived('p'), ["@@@", "@.@", "@@@", "@.."]);
+        assert_eq!(derived('q'), ["@@@", "@.@", "@@@", "@.."]);
+        assert_eq!(derived(')'), ["@.", ".@", "@."], "drawn under a tag wins");
+        assert_eq!(derived('('), [".@", "@.", ".@"]);
+    }
+}
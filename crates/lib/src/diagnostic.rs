@@ -0,0 +1,189 @@
+use std::{fmt, ops::Range, path::PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// Stable identifier of a diagnostic, safe to match on from tools and to search for in docs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticCode {
+    /// A file or directory could not be read.
+    Io,
+    /// `workspace.toml` is not a valid workspace manifest.
+    InvalidWorkspaceManifest,
+    /// `project.toml` is not a valid project manifest.
+    InvalidProjectManifest,
+    /// A `.yaff` source file failed to parse.
+    InvalidYaff,
+    /// A `[derive]` rule could not be evaluated.
+    InvalidDeriveRule,
+    /// A workspace member has no `project.toml`.
+    MissingProjectManifest,
+    /// A pattern in `workspace.toml` is not a valid glob.
+    InvalidMemberPattern,
+    /// The same character is defined more than once in a project.
+    DuplicateGlyph,
+    /// A backend cannot express something of the font, which is left out of its output.
+    UnsupportedFeature,
+    /// The glyph cache could not be read or written, so glyphs are compiled from scratch.
+    GlyphCache,
+    /// Something of an imported font cannot be kept in a project, like pixels outside of a cell.
+    LossyImport,
+    /// A glyph does not fit the fixed cell of a backend, so its pixels outside of it are dropped.
+    GlyphClipped,
+}
+
+impl DiagnosticCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiagnosticCode::Io => "E0001",
+            DiagnosticCode::InvalidWorkspaceManifest => "E0002",
+            DiagnosticCode::InvalidProjectManifest => "E0003",
+            DiagnosticCode::InvalidYaff => "E0004",
+            DiagnosticCode::InvalidDeriveRule => "E0005",
+            DiagnosticCode::MissingProjectManifest => "E0006",
+            DiagnosticCode::InvalidMemberPattern => "E0007",
+            DiagnosticCode::DuplicateGlyph => "W0001",
+            DiagnosticCode::UnsupportedFeature => "W0002",
+            DiagnosticCode::GlyphCache => "W0003",
+            DiagnosticCode::LossyImport => "W0004",
+            DiagnosticCode::GlyphClipped => "W0005",
+        }
+    }
+}
+
+/// Location inside a file.
+///
+/// `start` and `end` are byte offsets, `line` and `column` are 1-based and point to `start`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(content: &str, range: Range<usize>) -> Span {
+        LineIndex::new(content).span(range)
+    }
+}
+
+/// Starts of the lines of a text, to make many [`Span`]s of it
+/// without counting lines from the start of the text for each.
+pub(crate) struct LineIndex<'a> {
+    content: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub(crate) fn new(content: &'a str) -> LineIndex<'a> {
+        let line_starts = std::iter::once(0)
+            .chain(content.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+        LineIndex {
+            content,
+            line_starts,
+        }
+    }
+
+    pub(crate) fn span(&self, range: Range<usize>) -> Span {
+        let content = self.content;
+        let mut start = range.start.min(content.len());
+        while !content.is_char_boundary(start) {
+            start -= 1;
+        }
+        let line = self
+            .line_starts
+            .partition_point(|&line_start| line_start <= start);
+        let column = content[self.line_starts[line - 1]..start].chars().count() + 1;
+        Span {
+            start,
+            end: range.end.max(start),
+            line,
+            column,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: DiagnosticCode,
+    pub path: PathBuf,
+    pub span: Option<Span>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(
+        code: DiagnosticCode,
+        path: impl Into<PathBuf>,
+        span: Option<Span>,
+        message: impl fmt::Display,
+    ) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            path: path.into(),
+            span,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn warning(
+        code: DiagnosticCode,
+        path: impl Into<PathBuf>,
+        span: Option<Span>,
+        message: impl fmt::Display,
+    ) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(code, path, span, message)
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(
+            f,
+            "{severity}[{}]: {}",
+            self.code.as_str(),
+            self.path.to_string_lossy()
+        )?;
+        if let Some(span) = &self.span {
+            write!(f, ":{}:{}", span.line, span.column)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_columns_in_characters() {
+        let content = "ab\n가나다 x\n";
+        let index = LineIndex::new(content);
+        let x = content.find('x').unwrap();
+        let span = index.span(x..x + 1);
+        assert_eq!((span.line, span.column), (2, 5));
+        assert_eq!(span, Span::new(content, x..x + 1));
+        // inside of a character, and past the end.
+        assert_eq!(index.span(4..4).start, 3);
+        let end = index.span(100..100);
+        assert_eq!((end.start, end.line, end.column), (content.len(), 3, 1));
+    }
+}
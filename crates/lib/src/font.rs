@@ -0,0 +1,487 @@
+use std::collections::{BTreeMap, HashSet};
+
+use snafu::prelude::*;
+use yaff::{GlyphDefinition, SemanticGlyphLabel};
+
+use crate::{
+    backend::{FontOptions, FontStyle, FontVerseion},
+    glyph::BitmapMatrix,
+    project::{resolve_char, Project, SourceDateEpochError},
+};
+
+/// Index of a glyph in [`Font::glyphs`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GlyphId(pub u32);
+
+/// Font resolved from a project, which every backend builds from.
+///
+/// Labels, synthesized styles and metrics are resolved here once,
+/// so backends only deal with glyph IDs, codepoints and bitmaps.
+pub struct Font {
+    /// Names, version and font-wide metrics.
+    pub options: FontOptions,
+    /// Glyphs ordered by their ID.
+    pub glyphs: Vec<Glyph>,
+    /// Codepoints mapped to glyphs.
+    pub cmap: BTreeMap<char, GlyphId>,
+    pub features: FontFeatures,
+}
+
+pub struct Glyph {
+    pub id: GlyphId,
+    /// Labels the glyph is defined with, for names and messages.
+    pub labels: Vec<SemanticGlyphLabel>,
+    /// Bitmap with the style of the font applied, whose top row is at the top of the cell.
+    pub bitmap: BitmapMatrix,
+    pub metrics: GlyphMetrics,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlyphMetrics {
+    /// Advance width in pixels, which is the drawn width plus emboldening.
+    pub advance: usize,
+    /// Columns of the bitmap to hang out on the left of the origin.
+    pub overhang: usize,
+}
+
+/// Box around the inked pixels of a glyph, relative to its origin on the baseline
+/// with y growing upwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InkBox {
+    /// Left edge, negative when the glyph hangs out to the left of the origin.
+    pub x: i32,
+    /// Bottom edge, negative below the baseline.
+    pub y: i32,
+    pub width: usize,
+    pub height: usize,
+    /// Top row of the box in the bitmap.
+    pub row: usize,
+    /// Left column of the box in the bitmap.
+    pub col: usize,
+}
+
+/// Data for layout features, which not every backend can express.
+#[derive(Debug, Clone, Default)]
+pub struct FontFeatures {
+    /// Glyphs labeled with a sequence of more than one character, keyed by the sequence.
+    pub ligatures: BTreeMap<Vec<char>, GlyphId>,
+}
+
+#[derive(Debug, Snafu)]
+pub enum FontError {
+    #[snafu(display("failed to resolve the time to write into fonts"))]
+    Timestamp { source: SourceDateEpochError },
+    #[snafu(display("expect a version like `1.0` or `1.25` in [project] but got `{version}`"))]
+    InvalidVersion { version: String },
+    #[snafu(display("expect a weight between 1 and 1000 in [project] but got {weight}"))]
+    InvalidWeight { weight: u16 },
+    #[snafu(display(
+        "{name} of {value} pixels in [project] does not fit in cells {height} pixels high"
+    ))]
+    MetricOutOfCell {
+        name: &'static str,
+        value: u16,
+        height: u16,
+    },
+}
+
+impl Font {
+    /// Resolves the sub-family `sub_family_name` of `project`, synthesized with `style`.
+    ///
+    /// Names, version and font-wide metrics come from the `[project]` section of the manifest.
+    /// A character defined in several source files is taken from the first one in load order,
+    /// and drawn glyphs win over derived ones.
+    /// Glyphs mapped to a character come first in codepoint order, then the rest by their tags,
+    /// so glyph IDs do not depend on the order glyphs are defined in.
+    pub fn from_project(
+        project: &Project,
+        sub_family_name: &str,
+        style: FontStyle,
+    ) -> Result<Font, FontError> {
+        let settings = &project.manifest.project;
+        let family_name = settings.name.clone().unwrap_or_else(|| {
+            project
+                .path
+                .file_name()
+                .unwrap_or(project.path.as_os_str())
+                .to_string_lossy()
+                .into_owned()
+        });
+        let version = settings.version.as_deref().unwrap_or("1.0");
+        let version = parse_version(version).context(InvalidVersionSnafu { version })?;
+        let weight = settings.weight.unwrap_or(400);
+        ensure!((1..=1000).contains(&weight), InvalidWeightSnafu { weight });
+        let height = settings.height.unwrap_or_else(|| {
+            project
+                .list_glyph()
+                .filter_map(|glyph| glyph.value.as_ref())
+                .map(|value| value.height)
+                .max()
+                .unwrap_or(0)
+        });
+        let descender = settings.descender;
+        ensure!(
+            descender <= height,
+            MetricOutOfCellSnafu {
+                name: "descender",
+                value: descender,
+                height,
+            }
+        );
+        let ascender = settings.ascender.unwrap_or(height - descender);
+        ensure!(
+            ascender <= height,
+            MetricOutOfCellSnafu {
+                name: "ascender",
+                value: ascender,
+                height,
+            }
+        );
+
+        let full_name = format!("{family_name} {sub_family_name}");
+        let options = FontOptions {
+            copyright_notice: settings.copyright.clone(),
+            unique_id: format!("{version};{full_name}"),
+            family_name,
+            sub_family_name: sub_family_name.to_owned(),
+            full_font_name: None,
+            postscript_name: None,
+            version,
+            weight,
+            timestamp: project.manifest.build.timestamp().context(TimestampSnafu)?,
+            height,
+            ascender,
+            descender,
+            style,
+        };
+        Ok(Font::from_glyphs(project.list_glyph(), options))
+    }
+
+    pub fn from_glyphs<'a>(
+        glyphs: impl IntoIterator<Item = &'a GlyphDefinition>,
+        options: FontOptions,
+    ) -> Font {
+        let baseline = options.height.saturating_sub(options.descender) as usize;
+        let style = options.style;
+        // a label defined more than once stays with the first glyph in load order,
+        // and a glyph left without labels is dropped.
+        let mut claimed = HashSet::new();
+        let mut glyphs: Vec<_> = glyphs
+            .into_iter()
+            .filter_map(|glyph| {
+                let value = glyph.value.as_ref()?;
+                let semantic: Vec<_> = glyph
+                    .labels
+                    .iter()
+                    .filter_map(|label| label.to_semantic())
+                    .collect();
+                let has_labels = !semantic.is_empty();
+                let labels: Vec<_> = semantic
+                    .into_iter()
+                    .filter(|label| {
+                        let key = match resolve_char(label) {
+                            Some(ch) => SemanticGlyphLabel::CharSequence(vec![ch]),
+                            None => label.clone(),
+                        };
+                        claimed.insert(key)
+                    })
+                    .collect();
+                if has_labels && labels.is_empty() {
+                    return None;
+                }
+                let (bitmap, overhang) = style.apply(BitmapMatrix::from(glyph), baseline);
+                let metrics = GlyphMetrics {
+                    advance: value.width as usize + style.embolden,
+                    overhang,
+                };
+                Some((labels, bitmap, metrics))
+            })
+            .collect();
+        glyphs.sort_by_cached_key(|(labels, ..)| order_key(labels));
+
+        let mut cmap = BTreeMap::new();
+        let mut features = FontFeatures::default();
+        let glyphs = glyphs
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (labels, bitmap, metrics))| {
+                let id = GlyphId(idx as u32);
+                for label in &labels {
+                    match (label, resolve_char(label)) {
+                        (_, Some(ch)) => {
+                            cmap.insert(ch, id);
+                        }
+                        (SemanticGlyphLabel::CharSequence(vec), None) if vec.len() > 1 => {
+                            features.ligatures.insert(vec.clone(), id);
+                        }
+                        _ => {}
+                    }
+                }
+                Glyph {
+                    id,
+                    labels,
+                    bitmap,
+                    metrics,
+                }
+            })
+            .collect();
+
+        Font {
+            options,
+            glyphs,
+            cmap,
+            features,
+        }
+    }
+
+    pub fn glyph(&self, id: GlyphId) -> Option<&Glyph> {
+        self.glyphs.get(id.0 as usize)
+    }
+
+    /// Rows of the cell above the baseline, which bitmaps count from.
+    pub fn baseline(&self) -> usize {
+        self.options.height.saturating_sub(self.options.descender) as usize
+    }
+
+    /// Codepoints mapped to the glyph `id`, in order.
+    pub fn codepoints(&self, id: GlyphId) -> impl Iterator<Item = char> + '_ {
+        self.cmap
+            .iter()
+            .filter(move |(_, glyph)| **glyph == id)
+            .map(|(&ch, _)| ch)
+    }
+
+    /// Box around the inked pixels of `glyph`, `None` if it has none.
+    pub fn ink_box(&self, glyph: &Glyph) -> Option<InkBox> {
+        let rows = &glyph.bitmap.0;
+        let is_inked = |row: &Vec<_>| row.iter().any(Option::is_some);
+        let top = rows.iter().position(is_inked)?;
+        let bottom = rows.iter().rposition(is_inked)?;
+        let (left, right) = rows[top..=bottom]
+            .iter()
+            .filter_map(|row| {
+                let left = row.iter().position(Option::is_some)?;
+                let right = row.iter().rposition(Option::is_some)?;
+                Some((left, right))
+            })
+            .fold((usize::MAX, 0), |(l, r), (left, right)| {
+                (l.min(left), r.max(right))
+            });
+        Some(InkBox {
+            x: left as i32 - glyph.metrics.overhang as i32,
+            y: self.baseline() as i32 - bottom as i32 - 1,
+            width: right - left + 1,
+            height: bottom - top + 1,
+            row: top,
+            col: left,
+        })
+    }
+
+    /// The widest advance among glyphs in pixels.
+    pub fn max_advance(&self) -> usize {
+        self.glyphs
+            .iter()
+            .map(|glyph| glyph.metrics.advance)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl Glyph {
+    /// Whether the pixel at `row` and `col` of the bitmap is inked, in any color.
+    pub fn is_inked(&self, row: usize, col: usize) -> bool {
+        self.bitmap
+            .0
+            .get(row)
+            .and_then(|row| row.get(col))
+            .is_some_and(Option::is_some)
+    }
+
+    /// Labels joined for messages, like `'A', LATIN CAPITAL LETTER A`.
+    pub fn display_labels(&self) -> String {
+        self.labels
+            .iter()
+            .map(|label| label.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn order_key(labels: &[SemanticGlyphLabel]) -> (bool, Option<char>, Vec<String>) {
+    let ch = labels.iter().filter_map(resolve_char).min();
+    let labels = labels.iter().map(|label| label.to_string()).collect();
+    (ch.is_none(), ch, labels)
+}
+
+/// Version like `2.5` or `2.05`, whose decimals count hundredths as `head.fontRevision` does.
+fn parse_version(version: &str) -> Option<FontVerseion> {
+    let (major, minor) = version.split_once('.')?;
+    let is_number = |digits: &str| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit());
+    if !is_number(major) || !is_number(minor) || minor.len() > 2 {
+        return None;
+    }
+    FontVerseion::new(major.parse().ok()?, format!("{minor:0<2}").parse().ok()?)
+}
+
+/// Font named `Test` from the glyphs of the YAFF `source`, in cells `height` pixels high
+/// with `descender` of them below the baseline.
+#[cfg(test)]
+pub(crate) fn test_font(source: &str, height: u16, descender: u16, style: FontStyle) -> Font {
+    let document = yaff::parse_document(&mut &*source).unwrap();
+    let sub_family_name = match (style.is_bold(), style.is_italic()) {
+        (false, false) => "Regular",
+        (true, false) => "Bold",
+        (false, true) => "Oblique",
+        (true, true) => "Bold Oblique",
+    };
+    let options = FontOptions {
+        copyright_notice: None,
+        family_name: "Test".to_owned(),
+        sub_family_name: sub_family_name.to_owned(),
+        unique_id: format!("1.0;Test {sub_family_name}"),
+        full_font_name: None,
+        postscript_name: None,
+        version: FontVerseion::new(1, 0).unwrap(),
+        weight: 400,
+        timestamp: Some(jiff::Timestamp::UNIX_EPOCH),
+        height,
+        ascender: height - descender,
+        descender,
+        style,
+    };
+    Font::from_glyphs(document.list_glyph(), options)
+}
+
+#[cfg(test)]
+mod tests {
+    use write_fonts::{
+        read::{FontRef, TableProvider},
+        types::{Fixed, NameId},
+    };
+
+    use crate::{
+        backend::{FontBackend, OpentypeTtfBackend, OpentypeTtfOptions, SilentReporter},
+        project::temp_project,
+    };
+
+    use super::*;
+
+    fn labels(font: &Font) -> Vec<String> {
+        font.glyphs.iter().map(Glyph::display_labels).collect()
+    }
+
+    #[test]
+    fn orders_glyphs_by_codepoint_then_tags() {
+        let source = "\"zz\":\n  @\n\n'B':\n  @\n\n'fi':\n  @\n\n\"aa\":\n  @\n\n\
+                      u+0041:\n  @\n";
+        let font = test_font(source, 1, 0, FontStyle::default());
+        assert_eq!(labels(&font), ["A", "B", "`aa`", "`zz`", "fi"]);
+        assert_eq!(Vec::from_iter(font.cmap.keys()), [&'A', &'B']);
+        assert_eq!(font.features.ligatures[&vec!['f', 'i']], GlyphId(4));
+    }
+
+    #[test]
+    fn gives_labels_to_the_first_glyph() {
+        let source = "'A':\n'B':\n  @\n\n\
+                      \"LATIN CAPITAL LETTER A\":\n  @@\n\n\
+                      'B':\n'C':\n  @@@\n";
+        let font = test_font(source, 1, 0, FontStyle::default());
+        let advances: Vec<_> = font
+            .glyphs
+            .iter()
+            .map(|glyph| glyph.metrics.advance)
+            .collect();
+        assert_eq!(labels(&font), ["A, B", "C"]);
+        assert_eq!(advances, [1, 3], "the glyph left without labels is dropped");
+        assert_eq!(Vec::from_iter(font.codepoints(GlyphId(0))), ['A', 'B']);
+    }
+
+    #[test]
+    fn boxes_inked_pixels() {
+        let source = "'A':\n  ....\n  .@..\n  .@@.\n  ....\n\n' ':\n  ..\n  ..\n  ..\n  ..\n";
+        let font = test_font(source, 4, 1, FontStyle::default());
+        assert_eq!(font.baseline(), 3);
+        assert_eq!(
+            font.ink_box(&font.glyphs[1]),
+            Some(InkBox {
+                x: 1,
+                y: 0,
+                width: 2,
+                height: 2,
+                row: 1,
+                col: 1,
+            })
+        );
+        assert_eq!(font.ink_box(&font.glyphs[0]), None);
+
+        let bold = FontStyle {
+            embolden: 1,
+            oblique: None,
+        };
+        let font = test_font(source, 4, 1, bold);
+        assert_eq!(font.max_advance(), 5);
+        assert_eq!(font.ink_box(&font.glyphs[1]).unwrap().width, 3);
+    }
+
+    #[test]
+    fn reads_settings_of_the_project() {
+        let load = |manifest: &str| {
+            let dir = temp_project(&[
+                ("family/project.toml", manifest),
+                ("family/src/a.yaff", "'A':\n  @\n  @\n  @\n"),
+            ]);
+            let (project, diagnostics) = Project::load(dir.path().join("family"));
+            assert!(diagnostics.is_empty(), "{diagnostics:?}");
+            Font::from_project(&project, "Regular", FontStyle::default())
+        };
+        let font = load("[project]\ndescender = 1\nversion = \"2.5\"\n").unwrap();
+        let options = &font.options;
+        assert_eq!(options.family_name, "family");
+        assert_eq!(
+            (options.height, options.ascender, options.descender),
+            (3, 2, 1)
+        );
+        assert_eq!(options.unique_id, "2.5;family Regular");
+
+        let backend = OpentypeTtfBackend::new(OpentypeTtfOptions::default());
+        let ttf = backend
+            .build(&font, &SilentReporter)
+            .unwrap()
+            .remove(0)
+            .bytes;
+        let ttf = FontRef::new(&ttf).unwrap();
+        assert_eq!(ttf.head().unwrap().font_revision(), Fixed::from_f64(2.5));
+        let name = ttf.name().unwrap();
+        let version = name
+            .name_record()
+            .iter()
+            .find(|record| record.name_id() == NameId::VERSION_STRING)
+            .unwrap();
+        assert_eq!(
+            version.string(name.string_data()).unwrap().to_string(),
+            "Version 2.050"
+        );
+
+        let font = load("[project]\nversion = \"2.05\"\n").unwrap();
+        assert_eq!(font.options.version.minor, 5);
+        assert_eq!(font.options.unique_id, "2.05;family Regular");
+
+        for version in ["2", "2.123", "2.+5", "+2.5"] {
+            assert!(matches!(
+                load(&format!("[project]\nversion = \"{version}\"\n")),
+                Err(FontError::InvalidVersion { .. })
+            ));
+        }
+        assert!(matches!(
+            load("[project]\nweight = 0\n"),
+            Err(FontError::InvalidWeight { weight: 0 })
+        ));
+        assert!(matches!(
+            load("[project]\nheight = 2\nascender = 3\n"),
+            Err(FontError::MetricOutOfCell {
+                name: "ascender",
+                ..
+            })
+        ));
+    }
+}
@@ -1,12 +1,13 @@
-use std::collections::HashSet;
-
 use kurbo::{BezPath, Rect};
-use yaff::{GlyphDefinition, GlyphPaletteColor};
+use yaff::{GlyphDefinition, GlyphPaletteColor, GlyphValue};
 
-use crate::glyph::pathfinder::{find_path, MonochromeField};
+use crate::glyph::pathfinder::{
+    find_path, DiagonalRule, MonochromeField, PathfinderError, PathfinderMode,
+};
 
 use super::math::Pos;
 
+#[derive(Clone)]
 pub struct BitmapMatrix(pub Vec<Vec<Option<GlyphPaletteColor>>>);
 
 impl From<GlyphDefinition> for BitmapMatrix {
@@ -49,46 +50,349 @@ impl BitmapMatrix {
         BitmapMatrix(this)
     }
 
-    pub fn as_bezier_paths(&self, scale: usize) -> (Vec<BezPath>, Rect) {
+    pub fn width(&self) -> usize {
+        self.0.first().map(Vec::len).unwrap_or(0)
+    }
+
+    pub fn height(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Mirror itself along the vertical axis, so `(` becomes `)`.
+    pub fn mirror_horizontal(&self) -> BitmapMatrix {
+        BitmapMatrix(
+            self.0
+                .iter()
+                .map(|row| row.iter().rev().cloned().collect())
+                .collect(),
+        )
+    }
+
+    /// Mirror itself along the horizontal axis, so `b` becomes `p`.
+    pub fn mirror_vertical(&self) -> BitmapMatrix {
+        BitmapMatrix(self.0.iter().rev().cloned().collect())
+    }
+
+    /// Rotate itself clockwise. Note that width and height are swapped.
+    pub fn rotate_cw(&self) -> BitmapMatrix {
+        let (width, height) = (self.width(), self.height());
+        BitmapMatrix(
+            (0..width)
+                .map(|c| (0..height).rev().map(|r| self.0[r][c].clone()).collect())
+                .collect(),
+        )
+    }
+
+    /// Rotate itself counterclockwise. Note that width and height are swapped.
+    pub fn rotate_ccw(&self) -> BitmapMatrix {
+        let (width, height) = (self.width(), self.height());
+        BitmapMatrix(
+            (0..width)
+                .rev()
+                .map(|c| (0..height).map(|r| self.0[r][c].clone()).collect())
+                .collect(),
+        )
+    }
+
+    pub fn rotate_180(&self) -> BitmapMatrix {
+        self.mirror_horizontal().mirror_vertical()
+    }
+
+    /// Move every pixel by `dx` columns (right is positive) and `dy` rows (down is positive).
+    /// The size is kept, so pixels pushed out of the matrix are dropped.
+    pub fn shifted(&self, dx: isize, dy: isize) -> BitmapMatrix {
+        let (width, height) = (self.width(), self.height());
+        BitmapMatrix(
+            (0..height)
+                .map(|r| {
+                    (0..width)
+                        .map(|c| {
+                            let r = r.checked_add_signed(-dy)?;
+                            let c = c.checked_add_signed(-dx)?;
+                            self.0.get(r)?.get(c)?.clone()
+                        })
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    /// Thicken vertical strokes by `amount` pixels to the right, one pixel at a time.
+    /// The matrix gets wider by `amount`.
+    ///
+    /// A pixel is only filled when the pixel after it is empty, so a one pixel wide gap
+    /// between strokes is never closed and counters stay open.
+    pub fn emboldened(&self, amount: usize) -> BitmapMatrix {
+        let mut rows = self.0.clone();
+        for row in &mut rows {
+            row.resize(row.len() + amount, None);
+        }
+        for _ in 0..amount {
+            for row in &mut rows {
+                let source = row.clone();
+                for c in 1..row.len() {
+                    let next_is_empty = !matches!(source.get(c + 1), Some(Some(_)));
+                    if source[c].is_none() && next_is_empty {
+                        row[c] = source[c - 1].clone();
+                    }
+                }
+            }
+        }
+        BitmapMatrix(rows)
+    }
+
+    /// Lean itself to the right by shifting rows one more pixel every `step` rows,
+    /// counted from `baseline`, the index of the first row below the baseline.
+    ///
+    /// Rows below the baseline move to the left, so the matrix gets wider on both sides.
+    /// Returns the number of columns added on the left as well.
+    pub fn sheared(&self, step: usize, baseline: usize) -> (BitmapMatrix, usize) {
+        let step = step.max(1) as isize;
+        let offset = |r: usize| (baseline as isize - 1 - r as isize).div_euclid(step);
+        let (width, height) = (self.width(), self.height());
+        let min_offset = (0..height).map(offset).min().unwrap_or(0).min(0);
+        let max_offset = (0..height).map(offset).max().unwrap_or(0).max(0);
+        let new_width = width + (max_offset - min_offset) as usize;
+        let matrix = BitmapMatrix(
+            self.0
+                .iter()
+                .enumerate()
+                .map(|(r, row)| {
+                    let shift = (offset(r) - min_offset) as usize;
+                    let mut shifted = vec![None; shift];
+                    shifted.extend(row.iter().cloned());
+                    shifted.resize(new_width, None);
+                    shifted
+                })
+                .collect(),
+        );
+        (matrix, -min_offset as usize)
+    }
+
+    /// Convert itself back to [`GlyphValue`], returns `None` for an empty matrix.
+    pub fn to_glyph_value(&self) -> Option<GlyphValue> {
+        GlyphValue::new(self.0.clone()).ok()
+    }
+
+    /// Traces every colored area into a path of its own, with its holes as reversed subpaths.
+    ///
+    /// Areas of different colors are traced separately.
+    /// Whether pixels of the same color touching only by a corner belong to the same area
+    /// is decided by `rule`.
+    pub fn as_bezier_paths(
+        &self,
+        scale: usize,
+        rule: DiagonalRule,
+    ) -> Result<(Vec<BezPath>, Rect), PathfinderError> {
         struct Field<'a> {
-            mat: &'a BitmapMatrix,
-            color: &'a GlyphPaletteColor,
+            area: &'a [Vec<bool>],
         }
         impl MonochromeField for Field<'_> {
             fn is_colored_of_truthy_pos(&self, r: usize, c: usize) -> bool {
-                self.mat
-                    .0
+                self.area
                     .get(r)
                     .and_then(|row| row.get(c))
-                    .map_or(false, |v| matches!(v, Some(v) if v == self.color))
+                    .copied()
+                    .unwrap_or(false)
             }
         }
 
-        let height = self.0.len();
-        let width = self.0.get(0).map(Vec::len).unwrap_or(0);
-        let dots = Vec::from_iter((0..height).flat_map(|r| (0..width).map(move |c| Pos { r, c })));
+        let (width, height) = (self.width(), self.height());
+        let color_at = |pos: &Pos| self.0.get(pos.r)?.get(pos.c)?.as_ref();
+        // uncolored pixels are connected in the opposite way to colored ones,
+        // otherwise a hole could leak out through a corner or two areas could overlap.
+        let joins_area = rule == DiagonalRule::Join;
 
         let mut result = Vec::new();
         let mut whole_bb = Rect::ZERO;
 
-        let mut consumed_dots = HashSet::new();
-        for pos in dots {
-            if consumed_dots.contains(&pos) {
+        let mut traced = vec![vec![false; width]; height];
+        for pos in (0..height).flat_map(|r| (0..width).map(move |c| Pos { r, c })) {
+            if traced[pos.r][pos.c] {
                 continue;
             }
-            let Some(color) = &self.0[pos.r][pos.c] else {
-                consumed_dots.insert(pos);
+            let Some(color) = color_at(&pos) else {
                 continue;
             };
 
-            let path = find_path(pos, scale, Field { mat: &self, color }, |pos| {
-                consumed_dots.insert(pos);
+            let area = flood_fill(width, height, [pos.clone()], joins_area, |pos| {
+                color_at(pos) == Some(color)
             });
+            let mut path = find_path(
+                pos,
+                scale,
+                Field { area: &area },
+                PathfinderMode::Contour,
+                rule,
+            )?;
+
+            let border = (0..height)
+                .flat_map(|r| [Pos { r, c: 0 }, Pos { r, c: width - 1 }])
+                .chain((0..width).flat_map(|c| [Pos { r: 0, c }, Pos { r: height - 1, c }]));
+            let mut outside = flood_fill(width, height, border, !joins_area, |pos| {
+                !area[pos.r][pos.c]
+            });
+            for r in 0..height {
+                for c in 0..width {
+                    if area[r][c] || outside[r][c] {
+                        continue;
+                    }
+                    let hole = flood_fill(width, height, [Pos { r, c }], !joins_area, |pos| {
+                        !area[pos.r][pos.c]
+                    });
+                    path.extend(find_path(
+                        Pos { r, c },
+                        scale,
+                        Field { area: &area },
+                        PathfinderMode::Hole,
+                        rule,
+                    )?);
+                    for (outside_row, hole_row) in outside.iter_mut().zip(hole) {
+                        for (outside, hole) in outside_row.iter_mut().zip(hole_row) {
+                            *outside |= hole;
+                        }
+                    }
+                }
+            }
 
+            for (traced_row, area_row) in traced.iter_mut().zip(area) {
+                for (traced, area) in traced_row.iter_mut().zip(area_row) {
+                    *traced |= area;
+                }
+            }
             whole_bb = whole_bb.union(path.control_box());
             result.push(path);
         }
 
-        (result, whole_bb)
+        Ok((result, whole_bb))
+    }
+}
+
+/// Marks every pixel reachable from `seeds` through pixels satisfying `is_member`.
+/// Seeds not satisfying `is_member` are ignored.
+fn flood_fill(
+    width: usize,
+    height: usize,
+    seeds: impl IntoIterator<Item = Pos>,
+    diagonal: bool,
+    is_member: impl Fn(&Pos) -> bool,
+) -> Vec<Vec<bool>> {
+    let mut filled = vec![vec![false; width]; height];
+    let mut stack = Vec::from_iter(seeds);
+    while let Some(pos) = stack.pop() {
+        if filled[pos.r][pos.c] || !is_member(&pos) {
+            continue;
+        }
+        filled[pos.r][pos.c] = true;
+        for dr in -1isize..=1 {
+            for dc in -1isize..=1 {
+                if (dr == 0 && dc == 0) || (!diagonal && dr != 0 && dc != 0) {
+                    continue;
+                }
+                let (Some(r), Some(c)) =
+                    (pos.r.checked_add_signed(dr), pos.c.checked_add_signed(dc))
+                else {
+                    continue;
+                };
+                if r < height && c < width {
+                    stack.push(Pos { r, c });
+                }
+            }
+        }
+    }
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::PathEl;
+
+    use super::*;
+
+    fn matrix(rows: &[&str]) -> BitmapMatrix {
+        BitmapMatrix(
+            rows.iter()
+                .map(|row| {
+                    row.chars()
+                        .map(|ch| GlyphPaletteColor::try_from(ch).unwrap())
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    /// Number of subpaths in each traced path.
+    fn subpaths(matrix: &BitmapMatrix, rule: DiagonalRule) -> Vec<usize> {
+        let (paths, _) = matrix.as_bezier_paths(1, rule).unwrap();
+        paths
+            .iter()
+            .map(|path| {
+                path.elements()
+                    .iter()
+                    .filter(|el| matches!(el, PathEl::MoveTo(_)))
+                    .count()
+            })
+            .collect()
+    }
+
+    fn rows(matrix: &BitmapMatrix) -> Vec<String> {
+        matrix
+            .0
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| if cell.is_some() { '@' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn emboldens_without_closing_counters() {
+        let glyph = matrix(&["@.@", "@@@", "@.@"]);
+        assert_eq!(rows(&glyph.emboldened(1)), ["@.@@", "@@@@", "@.@@"]);
+        let stroke = matrix(&["@..."]);
+        assert_eq!(rows(&stroke.emboldened(2)), ["@@@..."]);
+    }
+
+    #[test]
+    fn shears_around_the_baseline() {
+        let glyph = matrix(&["@", "@", "@", "@"]);
+        let (sheared, overhang) = glyph.sheared(1, 3);
+        assert_eq!(rows(&sheared), ["...@", "..@.", ".@..", "@..."]);
+        assert_eq!(overhang, 1, "the row below the baseline moves left");
+        let (sheared, overhang) = glyph.sheared(2, 4);
+        assert_eq!(rows(&sheared), [".@", ".@", "@.", "@."]);
+        assert_eq!(overhang, 0);
+    }
+
+    #[test]
+    fn traces_diagonal_strokes_by_rule() {
+        let slash = matrix(&["..@", ".@.", "@.."]);
+        assert_eq!(subpaths(&slash, DiagonalRule::Join), [1]);
+        assert_eq!(subpaths(&slash, DiagonalRule::Split), [1, 1, 1]);
+
+        let cross = matrix(&["@.@", ".@.", "@.@"]);
+        assert_eq!(subpaths(&cross, DiagonalRule::Join), [1]);
+        assert_eq!(subpaths(&cross, DiagonalRule::Split), [1; 5]);
+    }
+
+    #[test]
+    fn encloses_holes_only_when_joining() {
+        let diamond = matrix(&[".@.", "@.@", ".@."]);
+        assert_eq!(subpaths(&diamond, DiagonalRule::Join), [2]);
+        assert_eq!(subpaths(&diamond, DiagonalRule::Split), [1; 4]);
+    }
+
+    #[test]
+    fn traces_checkerboards_at_the_edges() {
+        // joined, the two blank pixels away from the edges are enclosed as holes.
+        let checkerboard = matrix(&["@.@.", ".@.@", "@.@.", ".@.@"]);
+        assert_eq!(subpaths(&checkerboard, DiagonalRule::Join), [3]);
+        assert_eq!(subpaths(&checkerboard, DiagonalRule::Split), [1; 8]);
+
+        let inverted = matrix(&[".@.@", "@.@.", ".@.@", "@.@."]);
+        assert_eq!(subpaths(&inverted, DiagonalRule::Join), [3]);
+        assert_eq!(subpaths(&inverted, DiagonalRule::Split), [1; 8]);
     }
 }
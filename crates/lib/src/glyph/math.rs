@@ -1,5 +1,3 @@
-use std::usize;
-
 use kurbo::Point;
 use strum::FromRepr;
 
@@ -10,31 +8,21 @@ pub struct Pos {
 }
 
 impl Pos {
-    pub fn shifted(&self, direction: &Direction) -> Pos {
-        match direction {
+    /// Moves one step towards `direction`, `None` if it goes past the top or left edge.
+    pub fn shifted(&self, direction: &Direction) -> Option<Pos> {
+        let (r, c) = (self.r, self.c);
+        Some(match direction {
             Direction::Up => Pos {
-                r: self
-                    .r
-                    .checked_sub(1)
-                    .expect("Pos.shifted(Up) should success"),
-                c: self.c,
+                r: r.checked_sub(1)?,
+                c,
             },
             Direction::Left => Pos {
-                r: self.r,
-                c: self
-                    .c
-                    .checked_sub(1)
-                    .expect("Pos().shifted(Left) should success"),
-            },
-            Direction::Down => Pos {
-                r: self.r + 1,
-                c: self.c,
-            },
-            Direction::Right => Pos {
-                r: self.r,
-                c: self.c + 1,
+                r,
+                c: c.checked_sub(1)?,
             },
-        }
+            Direction::Down => Pos { r: r + 1, c },
+            Direction::Right => Pos { r, c: c + 1 },
+        })
     }
 
     pub fn as_kurbo_point(&self, scale: usize) -> Point {
@@ -42,7 +30,7 @@ impl Pos {
     }
 }
 
-#[derive(FromRepr, Debug, PartialEq, Eq)]
+#[derive(FromRepr, Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Direction {
     Up = 0,
@@ -102,11 +90,7 @@ impl<T> Matrix2x2<T> {
         Matrix2x2([[d, c], [b, a]])
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = &T> {
-        self.0.iter().flatten()
-    }
-
     pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> Matrix2x2<U> {
-        Matrix2x2(self.0.map(move |arr| arr.map(|v| f(v))))
+        Matrix2x2(self.0.map(move |arr| arr.map(&mut f)))
     }
 }
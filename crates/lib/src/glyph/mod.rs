@@ -1,6 +1,10 @@
 mod bitmap_matrix;
 pub(super) mod math;
+pub(crate) mod optimize;
 pub(crate) mod path;
 mod pathfinder;
+mod style;
 
 pub use bitmap_matrix::*;
+pub use pathfinder::{DiagonalRule, PathfinderError};
+pub use style::{OutlineStyle, PixelShape};
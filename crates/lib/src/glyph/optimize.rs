@@ -0,0 +1,313 @@
+use kurbo::{BezPath, PathEl, Point, Shape, Vec2};
+use yaff::GlyphPaletteColor;
+
+use super::{
+    pathfinder::{DiagonalRule, PathfinderError},
+    BitmapMatrix,
+};
+
+const EPSILON: f64 = 1e-9;
+
+enum Contour {
+    Polygon(Vec<Point>),
+    /// A contour with curves, which is kept as-is.
+    Verbatim(Vec<PathEl>),
+}
+
+/// Cleans up contours without changing the filled area:
+///
+/// - collinear segments are merged, and zero-area spikes are removed along with them
+/// - contours without area are removed
+/// - every polygon starts from its top-left most point
+/// - outer contours have positive area and holes have negative area in bitmap coordinates,
+///   that is, outer contours are clockwise on screen as [`BitmapMatrix::as_bezier_paths`] traces
+pub fn optimize_bezpath(path: &BezPath) -> BezPath {
+    let mut contours = split_contours(path);
+    for contour in &mut contours {
+        if let Contour::Polygon(points) = contour {
+            simplify_polygon(points);
+        }
+    }
+    contours.retain(|contour| match contour {
+        Contour::Polygon(points) => points.len() >= 3 && signed_area(points).abs() > EPSILON,
+        Contour::Verbatim(_) => true,
+    });
+
+    let polygons: Vec<BezPath> = contours
+        .iter()
+        .map(|contour| match contour {
+            Contour::Polygon(points) => polygon_to_bezpath(points),
+            Contour::Verbatim(elements) => BezPath::from_vec(elements.clone()),
+        })
+        .collect();
+    for (idx, contour) in contours.iter_mut().enumerate() {
+        let Contour::Polygon(points) = contour else {
+            continue;
+        };
+        let sample = inner_sample(points);
+        let depth = polygons
+            .iter()
+            .enumerate()
+            .filter(|&(other, polygon)| other != idx && polygon.winding(sample) != 0)
+            .count();
+        if (depth % 2 == 0) != (signed_area(points) > 0.) {
+            points.reverse();
+        }
+        let start = (0..points.len())
+            .min_by(|&a, &b| {
+                let (a, b) = (points[a], points[b]);
+                (a.y, a.x).partial_cmp(&(b.y, b.x)).unwrap()
+            })
+            .unwrap_or(0);
+        points.rotate_left(start);
+    }
+
+    BezPath::from_iter(contours.into_iter().flat_map(|contour| match contour {
+        Contour::Polygon(points) => polygon_to_bezpath(&points).elements().to_vec(),
+        Contour::Verbatim(elements) => elements,
+    }))
+}
+
+/// Merges overlapping and touching contours by filling the pixel grid with the path
+/// and tracing it again.
+///
+/// Only paths made of axis-aligned lines on the grid of `scale` can be re-traced,
+/// any other path is returned as-is.
+pub fn remove_overlaps(
+    path: &BezPath,
+    scale: usize,
+    rule: DiagonalRule,
+) -> Result<BezPath, PathfinderError> {
+    let grid = scale as f64;
+    let on_grid = |p: &Point| p.x >= 0. && p.y >= 0. && p.x % grid == 0. && p.y % grid == 0.;
+    let mut last = None::<Point>;
+    let is_rectilinear = path.elements().iter().all(|el| match el {
+        PathEl::MoveTo(p) => {
+            last = Some(*p);
+            on_grid(p)
+        }
+        PathEl::LineTo(p) => {
+            let axis_aligned = last.is_some_and(|last| last.x == p.x || last.y == p.y);
+            last = Some(*p);
+            on_grid(p) && axis_aligned
+        }
+        PathEl::QuadTo(..) | PathEl::CurveTo(..) => false,
+        PathEl::ClosePath => true,
+    });
+    if path.is_empty() || !is_rectilinear {
+        return Ok(path.clone());
+    }
+
+    let bb = path.bounding_box();
+    let (width, height) = ((bb.x1 / grid) as usize, (bb.y1 / grid) as usize);
+    let matrix = BitmapMatrix(
+        (0..height)
+            .map(|r| {
+                (0..width)
+                    .map(|c| {
+                        let center = Point::new((c as f64 + 0.5) * grid, (r as f64 + 0.5) * grid);
+                        (path.winding(center) != 0).then_some(GlyphPaletteColor::One)
+                    })
+                    .collect()
+            })
+            .collect(),
+    );
+    let (paths, _) = matrix.as_bezier_paths(scale, rule)?;
+    Ok(BezPath::from_iter(
+        paths.iter().flat_map(|path| path.elements().to_vec()),
+    ))
+}
+
+/// Polygons of the path without their closing points, with collinear points merged.
+/// Contours with curves are left out.
+pub(super) fn polygons(path: &BezPath) -> Vec<Vec<Point>> {
+    split_contours(path)
+        .into_iter()
+        .filter_map(|contour| match contour {
+            Contour::Polygon(mut points) => {
+                simplify_polygon(&mut points);
+                (points.len() >= 3).then_some(points)
+            }
+            Contour::Verbatim(_) => None,
+        })
+        .collect()
+}
+
+fn split_contours(path: &BezPath) -> Vec<Contour> {
+    let mut contours = Vec::new();
+    let mut elements = Vec::new();
+    let mut flush = |elements: &mut Vec<PathEl>| {
+        if elements.is_empty() {
+            return;
+        }
+        let elements = std::mem::take(elements);
+        let points: Option<Vec<Point>> = elements
+            .iter()
+            .filter_map(|el| match el {
+                PathEl::MoveTo(p) | PathEl::LineTo(p) => Some(Some(*p)),
+                PathEl::QuadTo(..) | PathEl::CurveTo(..) => Some(None),
+                PathEl::ClosePath => None,
+            })
+            .collect();
+        contours.push(match points {
+            Some(points) => Contour::Polygon(points),
+            None => Contour::Verbatim(elements),
+        });
+    };
+    for el in path.elements() {
+        if let PathEl::MoveTo(_) = el {
+            flush(&mut elements);
+        }
+        elements.push(*el);
+        if let PathEl::ClosePath = el {
+            flush(&mut elements);
+        }
+    }
+    flush(&mut elements);
+    contours
+}
+
+/// Removes duplicated points and points lying on the line between their neighbours.
+/// A spike going forth and back is such a point as well.
+fn simplify_polygon(points: &mut Vec<Point>) {
+    points.dedup();
+    if points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    let mut idx = 0;
+    let mut stable = 0;
+    while points.len() >= 3 && stable < points.len() {
+        let len = points.len();
+        idx %= len;
+        let prev = points[(idx + len - 1) % len];
+        let next = points[(idx + 1) % len];
+        let point = points[idx];
+        if point == prev || (point - prev).cross(next - point).abs() <= EPSILON {
+            points.remove(idx);
+            idx = idx.saturating_sub(1);
+            stable = 0;
+        } else {
+            idx += 1;
+            stable += 1;
+        }
+    }
+}
+
+fn signed_area(points: &[Point]) -> f64 {
+    let len = points.len();
+    (0..len)
+        .map(|idx| {
+            points[idx]
+                .to_vec2()
+                .cross(points[(idx + 1) % len].to_vec2())
+        })
+        .sum::<f64>()
+        / 2.
+}
+
+/// A point slightly inside of the polygon, next to the middle of its first edge.
+fn inner_sample(points: &[Point]) -> Point {
+    let (a, b) = (points[0], points[1 % points.len()]);
+    let edge = b - a;
+    let normal = Vec2::new(-edge.y, edge.x).normalize();
+    let inward = if signed_area(points) > 0. {
+        normal
+    } else {
+        -normal
+    };
+    a.midpoint(b) + inward * (edge.length() * 1e-3)
+}
+
+pub(super) fn polygon_to_bezpath(points: &[Point]) -> BezPath {
+    let mut path = BezPath::new();
+    let Some((first, rest)) = points.split_first() else {
+        return path;
+    };
+    path.move_to(*first);
+    for point in rest {
+        path.line_to(*point);
+    }
+    path.line_to(*first);
+    path.close_path();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f64, y: f64, size: f64) -> Vec<Point> {
+        vec![
+            Point::new(x, y),
+            Point::new(x + size, y),
+            Point::new(x + size, y + size),
+            Point::new(x, y + size),
+        ]
+    }
+
+    fn path(contours: &[Vec<Point>]) -> BezPath {
+        BezPath::from_iter(
+            contours
+                .iter()
+                .flat_map(|points| polygon_to_bezpath(points).elements().to_vec()),
+        )
+    }
+
+    #[test]
+    fn merges_collinear_points_and_spikes() {
+        let mut points = vec![
+            Point::new(0., 0.),
+            Point::new(5., 0.),
+            Point::new(10., 0.),
+            Point::new(10., 10.),
+            Point::new(15., 10.),
+            Point::new(10., 10.),
+            Point::new(0., 10.),
+            Point::new(0., 10.),
+        ];
+        simplify_polygon(&mut points);
+        assert_eq!(points, square(0., 0., 10.));
+    }
+
+    #[test]
+    fn orients_and_starts_contours() {
+        let mut outer = square(0., 0., 30.);
+        outer.reverse();
+        outer.rotate_left(2);
+        let hole = square(10., 10., 10.);
+        let flat = vec![
+            Point::new(0., 40.),
+            Point::new(10., 40.),
+            Point::new(20., 40.),
+        ];
+        let mut curve = BezPath::new();
+        curve.move_to((40., 0.));
+        curve.quad_to((50., 0.), (50., 10.));
+        curve.close_path();
+
+        let mut input = path(&[outer, hole, flat]);
+        input.extend(curve.elements().iter().copied());
+        let optimized = optimize_bezpath(&input);
+        let polygons = polygons(&optimized);
+        assert_eq!(polygons.len(), 2, "the flat contour is removed");
+        assert_eq!(polygons[0][0], Point::new(0., 0.));
+        assert!(signed_area(&polygons[0]) > 0.);
+        assert_eq!(polygons[1][0], Point::new(10., 10.));
+        assert!(signed_area(&polygons[1]) < 0.);
+        assert!(optimized.elements().ends_with(curve.elements()));
+    }
+
+    #[test]
+    fn merges_overlapping_contours() {
+        let input = path(&[square(0., 0., 20.), square(10., 10., 20.)]);
+        let merged = remove_overlaps(&input, 10, DiagonalRule::Join).unwrap();
+        let polygons = polygons(&merged);
+        assert_eq!(polygons.len(), 1);
+        assert_eq!(polygons[0].len(), 8);
+        assert_eq!(signed_area(&polygons[0]).abs(), 700.);
+
+        let off_grid = path(&[square(0., 0., 15.)]);
+        let kept = remove_overlaps(&off_grid, 10, DiagonalRule::Join).unwrap();
+        assert_eq!(kept, off_grid);
+    }
+}
@@ -1,35 +1,67 @@
-use std::{
-    collections::HashSet,
-    hash::{DefaultHasher, Hasher},
-};
+use std::ops::AddAssign;
 
-use kurbo::BezPath;
+use kurbo::{BezPath, PathEl, Point};
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct PointAndContours {
     pub points: usize,
     pub contours: usize,
 }
 
-pub fn analyze_bezpath(path: &BezPath) -> PointAndContours {
-    let points = HashSet::<u64>::from_iter(
-        path.elements()
-            .iter()
-            .flat_map(|el| match el {
-                kurbo::PathEl::MoveTo(p1) => vec![p1],
-                kurbo::PathEl::LineTo(p1) => vec![p1],
-                kurbo::PathEl::QuadTo(p1, p2) => vec![p1, p2],
-                kurbo::PathEl::CurveTo(p1, p2, p3) => vec![p1, p2, p3],
-                kurbo::PathEl::ClosePath => vec![],
-            })
-            .map(|p| {
-                let mut hasher = DefaultHasher::new();
-                hasher.write(&p.x.to_be_bytes());
-                hasher.write(&p.y.to_be_bytes());
-                hasher.finish()
-            }),
-    );
-    let points = points.len();
+impl PointAndContours {
+    /// How many points and contours are saved by going from `self` to `optimized`.
+    pub fn savings(&self, optimized: &PointAndContours) -> PointAndContours {
+        PointAndContours {
+            points: self.points.saturating_sub(optimized.points),
+            contours: self.contours.saturating_sub(optimized.contours),
+        }
+    }
+}
 
-    let contours = path.elements().len();
+impl AddAssign for PointAndContours {
+    fn add_assign(&mut self, rhs: PointAndContours) {
+        self.points += rhs.points;
+        self.contours += rhs.contours;
+    }
+}
+
+/// Counts points and contours the same way `glyf` stores them,
+/// where the closing point of a contour is dropped if it is the same as the starting one.
+pub fn analyze_bezpath(path: &BezPath) -> PointAndContours {
+    let mut points = 0;
+    let mut contours = 0;
+    let mut contour_points = 0;
+    let mut start = None::<Point>;
+    let mut last = None::<Point>;
+    for el in path.elements() {
+        match el {
+            PathEl::MoveTo(p1) => {
+                points += contour_points;
+                contours += 1;
+                contour_points = 1;
+                start = Some(*p1);
+                last = Some(*p1);
+            }
+            PathEl::LineTo(p1) => {
+                contour_points += 1;
+                last = Some(*p1);
+            }
+            PathEl::QuadTo(_, p2) => {
+                contour_points += 2;
+                last = Some(*p2);
+            }
+            PathEl::CurveTo(_, _, p3) => {
+                contour_points += 3;
+                last = Some(*p3);
+            }
+            PathEl::ClosePath => {
+                if contour_points > 1 && start.is_some() && start == last {
+                    contour_points -= 1;
+                }
+                last = start;
+            }
+        }
+    }
+    points += contour_points;
     PointAndContours { points, contours }
 }
@@ -1,43 +1,75 @@
 use kurbo::BezPath;
+use serde::Deserialize;
+use snafu::prelude::*;
 
 use super::math::{Direction, Matrix2x2, Pos};
 
 const IS_DEBUG: bool = false;
 
+/// How pixels touching only by their corners are traced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiagonalRule {
+    /// Trace them as one area, so a 1-pixel diagonal stroke becomes a single contour.
+    #[default]
+    Join,
+    /// Trace them as separate areas meeting at a point.
+    Split,
+}
+
+#[derive(Debug, Snafu)]
+pub enum PathfinderError {
+    #[snafu(display("cannot decide where to go at ({r}, {c}) heading {direction:?}"))]
+    UndecidableDirection {
+        r: usize,
+        c: usize,
+        direction: Direction,
+    },
+    #[snafu(display("path went out of the field at ({r}, {c}) heading {direction:?}"))]
+    OutOfField {
+        r: usize,
+        c: usize,
+        direction: Direction,
+    },
+}
+
 #[derive(PartialEq, Eq)]
 pub enum PathfinderMode {
+    /// Outer boundary of a colored area, travelled clockwise on screen.
     Contour,
+    /// Boundary of an uncolored area enclosed by a colored one, travelled counter-clockwise.
     Hole,
 }
 
+/// Traces the boundary starting from the top line of the pixel at `begin`.
+///
+/// For [`PathfinderMode::Contour`], `begin` must be the top-left most colored pixel of the area.
+/// For [`PathfinderMode::Hole`], it must be the top-left most uncolored pixel of the hole.
 pub fn find_path(
     begin: Pos,
     scale: usize,
     field: impl MonochromeField,
-    consumption_reporter: impl FnMut(Pos) -> (),
-) -> BezPath {
+    mode: PathfinderMode,
+    rule: DiagonalRule,
+) -> Result<BezPath, PathfinderError> {
     let mut path = BezPath::new();
-    _find_path(
-        begin,
-        scale,
-        field,
-        consumption_reporter,
-        PathfinderMode::Contour,
-        &mut path,
-    );
+    _find_path(begin, scale, field, mode, rule, &mut path)?;
 
-    path
+    Ok(path)
 }
 
 fn _find_path(
     begin_l: Pos,
     scale: usize,
     field: impl MonochromeField,
-    mut consumption_reporter: impl FnMut(Pos) -> (),
     mode: PathfinderMode,
+    rule: DiagonalRule,
     path: &mut BezPath,
-) {
-    let begin_r = begin_l.shifted(&Direction::Right);
+) -> Result<(), PathfinderError> {
+    let begin_r = Pos {
+        r: begin_l.r,
+        c: begin_l.c + 1,
+    };
     // we use top line of the pixel for the start of contour travelling
     //   @====>            <====@
     //   |    |     OR     |    |
@@ -59,8 +91,6 @@ fn _find_path(
     }
 
     while actual_begin != pos {
-        consumption_reporter(pos.clone());
-
         let mat = Matrix2x2([
             [
                 field.is_colored(pos.r.checked_sub(1), pos.c.checked_sub(1)),
@@ -71,44 +101,50 @@ fn _find_path(
                 field.is_colored(pos.r, pos.c),
             ],
         ]);
-        if is_contour {
-            for pos in mat.iter().flatten() {
-                consumption_reporter(pos.clone());
-            }
-        }
         let mat = mat.map(|x| x.is_some() == is_contour);
 
         let next_direction = match direction {
-            Direction::Up => next_direction(&mat.clone().rotate_cw()).map(Direction::rotate_ccw),
-            Direction::Left => next_direction(&mat.clone().flip()).map(Direction::flip),
-            Direction::Down => next_direction(&mat.clone().rotate_ccw()).map(Direction::rotate_cw),
-            Direction::Right => next_direction(&mat),
+            Direction::Up => {
+                next_direction(&mat.clone().rotate_cw(), rule).map(Direction::rotate_ccw)
+            }
+            Direction::Left => next_direction(&mat.clone().flip(), rule).map(Direction::flip),
+            Direction::Down => {
+                next_direction(&mat.clone().rotate_ccw(), rule).map(Direction::rotate_cw)
+            }
+            Direction::Right => next_direction(&mat, rule),
         };
 
         if IS_DEBUG {
             _debug_flow(&mat, is_contour, &pos, &direction, &next_direction);
         }
 
-        let Some(next_direction) = next_direction else {
-            panic!("next direction must be decided");
-        };
+        let next_direction = next_direction.context(UndecidableDirectionSnafu {
+            r: pos.r,
+            c: pos.c,
+            direction,
+        })?;
         if direction != next_direction {
             path.line_to(pos.as_kurbo_point(scale));
             size = 0;
         }
         size += 1;
-        pos = pos.shifted(&next_direction);
+        pos = pos.shifted(&next_direction).context(OutOfFieldSnafu {
+            r: pos.r,
+            c: pos.c,
+            direction: next_direction,
+        })?;
         direction = next_direction;
     }
     if size != 0 {
         path.line_to(pos.as_kurbo_point(scale));
     }
     path.close_path();
+    Ok(())
 }
 
 fn _debug_flow(
     Matrix2x2([[lt, rt], [lb, rb]]): &Matrix2x2<bool>,
-    is_contour: bool,
+    _is_contour: bool,
     pos: &Pos,
     from_dir: &Direction,
     to_dir: &Option<Direction>,
@@ -196,19 +232,33 @@ fn _debug_flow(
 /// | lb | rb |
 /// +----+----+
 /// ``````
-fn next_direction(Matrix2x2([[lt, rt], [lb, rb]]): &Matrix2x2<bool>) -> Option<Direction> {
+/// For diagonally touching pixels, [`DiagonalRule::Join`] turns left to keep the colored
+/// pixels together and [`DiagonalRule::Split`] turns right to keep them apart.
+/// It is the same turn whether a contour or a hole is travelled,
+/// since the colored side is always on the right.
+fn next_direction(
+    Matrix2x2([[lt, rt], [lb, rb]]): &Matrix2x2<bool>,
+    rule: DiagonalRule,
+) -> Option<Direction> {
     match (lt, rt, lb, rb) {
-        (false, _, false, _) | (true, _, true, _) => {
-            None
-            // panic!("lt xor lb must be true but (lt={lt}, lb={lb})")
-        }
+        (false, _, false, _) | (true, _, true, _) => None,
 
         //
-        //                   @ ?
+        //    @              @
+        // --+       OR     --+
+        //  @                  @
+        //
+        (false, true, true, false) | (true, false, false, true) => match rule {
+            DiagonalRule::Join => Some(Direction::Up),
+            DiagonalRule::Split => Some(Direction::Down),
+        },
+
+        //
+        //                   @ @
         // --+       OR     --+
         //  @|                |@
         //   v                v
-        (false, false, true, false) | (true, _, false, true) => Some(Direction::Down),
+        (false, false, true, false) | (true, true, false, true) => Some(Direction::Down),
 
         //
         //  @ @
@@ -220,9 +270,9 @@ fn next_direction(Matrix2x2([[lt, rt], [lb, rb]]): &Matrix2x2<bool>) -> Option<D
         //   ^                ^
         //  @|                |@
         // --+       OR     --+
-        //                   @ ?
+        //                   @ @
         //
-        (true, false, false, false) | (false, true, true, _) => Some(Direction::Up),
+        (true, false, false, false) | (false, true, true, true) => Some(Direction::Up),
     }
 }
 
@@ -242,7 +292,7 @@ pub trait MonochromeField {
     ) -> Option<Pos> {
         let r = r.into_option_usize()?;
         let c = c.into_option_usize()?;
-        self.is_colored_of_truthy_pos(r, c).then(|| Pos { r, c })
+        self.is_colored_of_truthy_pos(r, c).then_some(Pos { r, c })
     }
 }
 
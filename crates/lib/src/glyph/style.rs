@@ -0,0 +1,344 @@
+use std::f64::consts::PI;
+
+use kurbo::{BezPath, Point, Rect, Vec2};
+use serde::Deserialize;
+
+use super::{
+    optimize::{polygon_to_bezpath, polygons},
+    pathfinder::{DiagonalRule, PathfinderError},
+    BitmapMatrix,
+};
+
+/// Look of a pixel in outlines, from `shape` of the `[outline]` section.
+///
+/// ```toml
+/// [outline]
+/// shape = { kind = "dot", size = 0.8 }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum PixelShape {
+    /// Hard squares, exactly as drawn.
+    #[default]
+    Square,
+    /// A circle per pixel like LED dot-matrix displays.
+    Dot {
+        /// Diameter relative to a pixel.
+        #[serde(default = "default_dot_size")]
+        size: f64,
+    },
+    /// Squares joined as usual, but with every corner rounded.
+    Rounded {
+        /// Corner radius relative to a pixel, at most `0.5`.
+        #[serde(default = "default_corner_radius")]
+        radius: f64,
+    },
+    /// Horizontal runs of pixels with a blank line below each row like CRT scanlines.
+    Scanline {
+        /// Height of the blank line relative to a pixel.
+        #[serde(default = "default_scanline_gap")]
+        gap: f64,
+    },
+    /// Squares joined as usual, with stair steps of diagonal strokes beveled at 45 degrees.
+    Smoothed,
+}
+
+fn default_dot_size() -> f64 {
+    1.
+}
+
+fn default_corner_radius() -> f64 {
+    0.25
+}
+
+fn default_scanline_gap() -> f64 {
+    0.25
+}
+
+/// Strategy turning a bitmap into outlines.
+pub trait OutlineStyle {
+    /// Returns outlines in bitmap coordinates, where a pixel is `scale` units wide,
+    /// together with their bounding box.
+    ///
+    /// Outer contours must be clockwise on screen and holes counter-clockwise,
+    /// the same as [`BitmapMatrix::as_bezier_paths`].
+    fn outline(
+        &self,
+        matrix: &BitmapMatrix,
+        scale: usize,
+        rule: DiagonalRule,
+    ) -> Result<(Vec<BezPath>, Rect), PathfinderError>;
+}
+
+impl OutlineStyle for PixelShape {
+    fn outline(
+        &self,
+        matrix: &BitmapMatrix,
+        scale: usize,
+        rule: DiagonalRule,
+    ) -> Result<(Vec<BezPath>, Rect), PathfinderError> {
+        let paths = match *self {
+            PixelShape::Square => return matrix.as_bezier_paths(scale, rule),
+            PixelShape::Dot { size } => dots(matrix, scale as f64, size),
+            PixelShape::Rounded { radius } => {
+                let cut = scale as f64 * radius.clamp(0., 0.5);
+                map_polygons(matrix, scale, rule, |points| round_corners(points, cut))?
+            }
+            PixelShape::Scanline { gap } => scanlines(matrix, scale as f64, gap),
+            PixelShape::Smoothed => map_polygons(matrix, scale, rule, |points| {
+                polygon_to_bezpath(&bevel_steps(points, scale as f64))
+            })?,
+        };
+        let bb = paths
+            .iter()
+            .map(|path| path.control_box())
+            .reduce(|acc, bb| acc.union(bb))
+            .unwrap_or(Rect::ZERO);
+        Ok((paths, bb))
+    }
+}
+
+fn dots(matrix: &BitmapMatrix, scale: f64, size: f64) -> Vec<BezPath> {
+    let radius = scale * size.clamp(0., 1.) / 2.;
+    if radius <= 0. {
+        return Vec::new();
+    }
+    let mut paths = Vec::new();
+    for (r, row) in matrix.0.iter().enumerate() {
+        for (c, cell) in row.iter().enumerate() {
+            if cell.is_some() {
+                let center = Point::new((c as f64 + 0.5) * scale, (r as f64 + 0.5) * scale);
+                paths.push(circle(center, radius));
+            }
+        }
+    }
+    paths
+}
+
+/// A circle made of 8 quadratic curves, so it can be stored in `glyf` as-is.
+fn circle(center: Point, radius: f64) -> BezPath {
+    let step = PI / 4.;
+    let control_radius = radius / (step / 2.).cos();
+    let at = |angle: f64, radius: f64| center + Vec2::from_angle(angle) * radius;
+
+    let start = at(0., radius);
+    let mut path = BezPath::new();
+    path.move_to(start);
+    for idx in 0..8 {
+        let angle = idx as f64 * step;
+        let end = if idx == 7 {
+            start
+        } else {
+            at(angle + step, radius)
+        };
+        path.quad_to(at(angle + step / 2., control_radius), end);
+    }
+    path.close_path();
+    path
+}
+
+fn scanlines(matrix: &BitmapMatrix, scale: f64, gap: f64) -> Vec<BezPath> {
+    let height = scale * (1. - gap.clamp(0., 1.));
+    if height <= 0. {
+        return Vec::new();
+    }
+    let mut paths = Vec::new();
+    for (r, row) in matrix.0.iter().enumerate() {
+        let mut c = 0;
+        while c < row.len() {
+            if row[c].is_none() {
+                c += 1;
+                continue;
+            }
+            let start = c;
+            while c < row.len() && row[c].is_some() {
+                c += 1;
+            }
+            let (x0, x1) = (start as f64 * scale, c as f64 * scale);
+            let (y0, y1) = (r as f64 * scale, r as f64 * scale + height);
+            paths.push(polygon_to_bezpath(&[
+                Point::new(x0, y0),
+                Point::new(x1, y0),
+                Point::new(x1, y1),
+                Point::new(x0, y1),
+            ]));
+        }
+    }
+    paths
+}
+
+fn map_polygons(
+    matrix: &BitmapMatrix,
+    scale: usize,
+    rule: DiagonalRule,
+    f: impl Fn(&[Point]) -> BezPath,
+) -> Result<Vec<BezPath>, PathfinderError> {
+    let (paths, _) = matrix.as_bezier_paths(scale, rule)?;
+    Ok(paths
+        .iter()
+        .map(|path| {
+            BezPath::from_iter(
+                polygons(path)
+                    .iter()
+                    .flat_map(|points| f(points).elements().to_vec()),
+            )
+        })
+        .collect())
+}
+
+/// Replaces every corner with a quadratic curve starting `cut` away from it,
+/// or half of the edge if the edge is shorter.
+fn round_corners(points: &[Point], cut: f64) -> BezPath {
+    let len = points.len();
+    let mut path = BezPath::new();
+    for idx in 0..len {
+        let (prev, point, next) = (
+            points[(idx + len - 1) % len],
+            points[idx],
+            points[(idx + 1) % len],
+        );
+        let (incoming, outgoing) = (point - prev, next - point);
+        let enter = point - incoming.normalize() * cut.min(incoming.hypot() / 2.);
+        let leave = point + outgoing.normalize() * cut.min(outgoing.hypot() / 2.);
+        if idx == 0 {
+            path.move_to(enter);
+        } else {
+            path.line_to(enter);
+        }
+        path.quad_to(point, leave);
+    }
+    if let Some(&first) = points.first() {
+        let incoming = first - points[len - 1];
+        path.line_to(first - incoming.normalize() * cut.min(incoming.hypot() / 2.));
+    }
+    path.close_path();
+    path
+}
+
+/// Bevels stair steps, which are corners between one pixel long edges
+/// turning the other way from a neighbouring corner, by cutting them at the middle of the edges.
+fn bevel_steps(points: &[Point], scale: f64) -> Vec<Point> {
+    let len = points.len();
+    let at = |idx: usize| points[idx % len];
+    let turn = |idx: usize| {
+        let (prev, point, next) = (at(idx + len - 1), at(idx), at(idx + 1));
+        (point - prev).cross(next - point).signum()
+    };
+    let is_step = |a: Point, b: Point| ((b - a).hypot() - scale).abs() < 1e-9;
+
+    let mut beveled = Vec::new();
+    for idx in 0..len {
+        let (prev, point, next) = (at(idx + len - 1), at(idx), at(idx + 1));
+        let is_zigzag = turn(idx) != turn(idx + len - 1) || turn(idx) != turn(idx + 1);
+        if is_zigzag && is_step(prev, point) && is_step(point, next) {
+            beveled.push(prev.midpoint(point));
+            beveled.push(point.midpoint(next));
+        } else {
+            beveled.push(point);
+        }
+    }
+    beveled.dedup();
+    if beveled.len() > 1 && beveled.first() == beveled.last() {
+        beveled.pop();
+    }
+    beveled
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::Shape;
+    use yaff::GlyphPaletteColor;
+
+    use super::*;
+
+    fn matrix(rows: &[&str]) -> BitmapMatrix {
+        BitmapMatrix(
+            rows.iter()
+                .map(|row| {
+                    row.chars()
+                        .map(|ch| GlyphPaletteColor::try_from(ch).unwrap())
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    fn outline(shape: PixelShape, rows: &[&str]) -> (Vec<BezPath>, Rect) {
+        shape
+            .outline(&matrix(rows), 10, DiagonalRule::Join)
+            .unwrap()
+    }
+
+    fn area(paths: &[BezPath]) -> f64 {
+        paths.iter().map(|path| path.area().abs()).sum()
+    }
+
+    #[test]
+    fn reads_shapes_with_defaults() {
+        let shape = |source: &str| toml::from_str::<PixelShape>(source).unwrap();
+        assert_eq!(shape("kind = \"square\""), PixelShape::Square);
+        assert_eq!(shape("kind = \"dot\""), PixelShape::Dot { size: 1. });
+        assert_eq!(
+            shape("kind = \"rounded\"\nradius = 0.5"),
+            PixelShape::Rounded { radius: 0.5 }
+        );
+        assert_eq!(
+            shape("kind = \"scanline\""),
+            PixelShape::Scanline { gap: 0.25 }
+        );
+        assert!(toml::from_str::<PixelShape>("kind = \"star\"").is_err());
+    }
+
+    #[test]
+    fn draws_a_dot_per_pixel() {
+        let (paths, bb) = outline(PixelShape::Dot { size: 0.8 }, &["@.", ".@"]);
+        assert_eq!(paths.len(), 2);
+        let expected = Rect::new(1., 1., 19., 19.);
+        assert!(
+            (bb.origin() - expected.origin()).hypot() < 1e-9
+                && (bb.size() - expected.size()).to_vec2().hypot() < 1e-9,
+            "{bb:?}"
+        );
+        let radius: f64 = 4.;
+        assert!((area(&paths) - 2. * PI * radius * radius).abs() < 1.);
+        assert!(outline(PixelShape::Dot { size: 0. }, &["@"]).0.is_empty());
+    }
+
+    #[test]
+    fn draws_a_line_per_run_of_pixels() {
+        let (paths, bb) = outline(PixelShape::Scanline { gap: 0.25 }, &["@@.@", "...."]);
+        assert_eq!(paths.len(), 2);
+        assert_eq!(bb, Rect::new(0., 0., 40., 7.5));
+        assert_eq!(area(&paths), 3. * 10. * 7.5);
+        assert!(outline(PixelShape::Scanline { gap: 1. }, &["@"])
+            .0
+            .is_empty());
+    }
+
+    #[test]
+    fn rounds_corners() {
+        let (square, _) = outline(PixelShape::Square, &["@@", "@@"]);
+        let (rounded, bb) = outline(PixelShape::Rounded { radius: 0.25 }, &["@@", "@@"]);
+        assert_eq!(bb, Rect::new(0., 0., 20., 20.));
+        // each corner loses a bit of the 2.5 by 2.5 square it is cut from.
+        let lost = area(&square) - area(&rounded);
+        assert!(lost > 0. && lost < 4. * 2.5 * 2.5, "{lost}");
+        // radii above a half pixel are clamped.
+        let (clamped, _) = outline(PixelShape::Rounded { radius: 2. }, &["@"]);
+        let (half, _) = outline(PixelShape::Rounded { radius: 0.5 }, &["@"]);
+        assert_eq!(area(&clamped), area(&half));
+    }
+
+    #[test]
+    fn bevels_stair_steps() {
+        let rows = ["@..", "@@.", "@@@"];
+        let (square, _) = outline(PixelShape::Square, &rows);
+        let (smoothed, bb) = outline(PixelShape::Smoothed, &rows);
+        assert_eq!(bb, Rect::new(0., 0., 30., 30.));
+        assert!(area(&smoothed) < area(&square));
+
+        let (square, _) = outline(PixelShape::Square, &["@@", "@@"]);
+        let (smoothed, _) = outline(PixelShape::Smoothed, &["@@", "@@"]);
+        assert_eq!(area(&smoothed), area(&square), "a block has no step");
+    }
+}
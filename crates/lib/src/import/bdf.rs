@@ -0,0 +1,590 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use encoding_rs::Encoding;
+use snafu::prelude::*;
+use yaff::{GlyphDefinition, GlyphLabel, GlyphPaletteColor, GlyphValue};
+
+use crate::diagnostic::{Diagnostic, DiagnosticCode, Span};
+
+use super::{weight_of, ImportedFont};
+
+#[derive(Debug, Snafu)]
+pub enum BdfImportError {
+    #[snafu(display("failed to read {path}", path = path.to_string_lossy()))]
+    Io { path: PathBuf, source: io::Error },
+    #[snafu(display("{path}:{line}: {message}", path = path.to_string_lossy()))]
+    Syntax {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+/// Reads the BDF font at `path`, see [`parse_bdf`].
+pub fn read_bdf(path: impl AsRef<Path>) -> Result<(ImportedFont, Vec<Diagnostic>), BdfImportError> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).context(IoSnafu { path })?;
+    // property values of old fonts are often in Latin-1, which is never invalid.
+    let content = match String::from_utf8(bytes) {
+        Ok(content) => content,
+        Err(e) => e.into_bytes().iter().map(|&byte| byte as char).collect(),
+    };
+    parse_bdf(&content, path)
+}
+
+/// Parses a BDF font into glyphs with full cells.
+///
+/// Each glyph is placed into a cell as wide as its `DWIDTH` and as high as
+/// `FONT_ASCENT` plus `FONT_DESCENT`, from its `BBX` offsets.
+/// `ENCODING` becomes a character label, or a codepoint label if the charset of the font
+/// is unknown, and the `STARTCHAR` name becomes a tag.
+/// Anything which cannot be kept, like pixels outside of the cell, is reported as a warning.
+/// `path` is only used for messages.
+pub fn parse_bdf(
+    content: &str,
+    path: impl AsRef<Path>,
+) -> Result<(ImportedFont, Vec<Diagnostic>), BdfImportError> {
+    BdfParser {
+        content,
+        path: path.as_ref(),
+        lines: Vec::new(),
+        position: 0,
+        diagnostics: Vec::new(),
+    }
+    .parse()
+}
+
+struct BdfParser<'a> {
+    content: &'a str,
+    path: &'a Path,
+    /// Non-empty lines with their byte offset, consumed from `position`.
+    lines: Vec<(usize, &'a str)>,
+    position: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Font-wide values from the header.
+struct BdfHeader {
+    properties: HashMap<String, String>,
+    xlfd: Vec<String>,
+    bounding_box: [i32; 4],
+    dwidth: Option<i32>,
+}
+
+/// How `ENCODING` values are turned into labels.
+enum Charset {
+    Unicode,
+    SingleByte(&'static Encoding),
+    Unknown,
+}
+
+impl<'a> BdfParser<'a> {
+    fn parse(mut self) -> Result<(ImportedFont, Vec<Diagnostic>), BdfImportError> {
+        let mut offset = 0;
+        for line in self.content.split_inclusive('\n') {
+            let trimmed = line.trim();
+            if !trimmed.is_empty() {
+                self.lines.push((offset, trimmed));
+            }
+            offset += line.len();
+        }
+
+        let header = self.parse_header()?;
+        let property = |name: &str| header.properties.get(name).map(String::as_str);
+        let int_property = |name: &str| property(name).and_then(|value| value.parse::<i32>().ok());
+        let [_, bb_height, _, bb_y] = header.bounding_box;
+        let ascent = int_property("FONT_ASCENT").unwrap_or(bb_height + bb_y);
+        let descent = int_property("FONT_DESCENT").unwrap_or(-bb_y);
+        if ascent < 0 || descent < 0 || ascent + descent <= 0 {
+            return self.fail(format!(
+                "expect a positive cell height but got {ascent} above and {descent} below the baseline"
+            ));
+        }
+        let xlfd = |idx: usize| header.xlfd.get(idx).map(String::as_str);
+        let charset = self.charset(
+            property("CHARSET_REGISTRY").or(xlfd(13)),
+            property("CHARSET_ENCODING").or(xlfd(14)),
+        );
+
+        let mut glyphs = Vec::new();
+        let mut used_chars = HashSet::new();
+        let mut used_tags = HashSet::new();
+        while let Some((keyword, rest)) = self.peek() {
+            match keyword {
+                "STARTCHAR" => {
+                    let name = rest.to_owned();
+                    self.next();
+                    let glyph = self.parse_char(&name, &header, ascent, descent, &charset)?;
+                    glyphs.push(self.label_glyph(glyph, &name, &mut used_chars, &mut used_tags));
+                }
+                "ENDFONT" => break,
+                _ => {
+                    self.next();
+                }
+            }
+        }
+
+        let name = property("FAMILY_NAME")
+            .or(xlfd(2))
+            .filter(|name| !name.is_empty())
+            .map_or_else(
+                || {
+                    self.path
+                        .file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned()
+                },
+                str::to_owned,
+            );
+        let weight = property("WEIGHT_NAME").or(xlfd(3)).map_or(400, weight_of);
+        let font = ImportedFont {
+            name,
+            weight,
+            height: (ascent + descent) as usize,
+            descent: descent as usize,
+            glyphs,
+        };
+        Ok((font, self.diagnostics))
+    }
+
+    fn parse_header(&mut self) -> Result<BdfHeader, BdfImportError> {
+        match self.next() {
+            Some(("STARTFONT", _)) => {}
+            _ => return self.fail("expect `STARTFONT` at the start of a BDF font"),
+        }
+        let mut header = BdfHeader {
+            properties: HashMap::new(),
+            xlfd: Vec::new(),
+            bounding_box: [0; 4],
+            dwidth: None,
+        };
+        while let Some((keyword, rest)) = self.next() {
+            match keyword {
+                "FONT" => header.xlfd = rest.split('-').map(str::to_owned).collect(),
+                "FONTBOUNDINGBOX" => header.bounding_box = self.integers(rest)?,
+                "DWIDTH" => header.dwidth = Some(self.integers::<2>(rest)?[0]),
+                "STARTPROPERTIES" => {
+                    while let Some((name, value)) = self.next() {
+                        if name == "ENDPROPERTIES" {
+                            break;
+                        }
+                        header.properties.insert(name.to_owned(), unquote(value));
+                    }
+                }
+                "CHARS" => return Ok(header),
+                _ => {}
+            }
+        }
+        self.fail("expect `CHARS` before the glyphs")
+    }
+
+    fn parse_char(
+        &mut self,
+        name: &str,
+        header: &BdfHeader,
+        ascent: i32,
+        descent: i32,
+        charset: &Charset,
+    ) -> Result<(Option<GlyphLabel>, Option<GlyphValue>), BdfImportError> {
+        let mut encoding = None;
+        let mut dwidth = header.dwidth;
+        let mut bbx = None;
+        let mut bitmap = Vec::new();
+        while let Some((keyword, rest)) = self.next() {
+            match keyword {
+                // a second number is a code in a non-standard encoding, which cannot be used.
+                "ENCODING" => encoding = Some(self.integers::<1>(rest)?[0]),
+                "DWIDTH" => dwidth = Some(self.integers::<2>(rest)?[0]),
+                "BBX" => bbx = Some(self.integers::<4>(rest)?),
+                "BITMAP" => {
+                    let [width, height, ..] = bbx.unwrap_or([0; 4]);
+                    for _ in 0..height.max(0) {
+                        match self.next() {
+                            Some((row, "")) => bitmap.push(self.hex_row(row, width)?),
+                            _ => return self.fail(format!("expect {height} rows of `{name}`")),
+                        }
+                    }
+                }
+                "ENDCHAR" => {
+                    let Some(bbx) = bbx else {
+                        return self.fail(format!("expect `BBX` in `{name}`"));
+                    };
+                    let advance = dwidth.unwrap_or(header.bounding_box[0]).max(0) as usize;
+                    let cell_height = (ascent + descent) as usize;
+                    let (data, clipped) = fill_cell(&bitmap, bbx, (advance, cell_height), ascent);
+                    if clipped > 0 {
+                        self.warn(format!(
+                            "{clipped} pixels of `{name}` outside of its {advance}x{cell_height} cell are cut off"
+                        ));
+                    }
+                    // a row without columns cannot be written in YAFF.
+                    let value =
+                        (advance > 0).then(|| GlyphValue::new(data).expect("cells are rectangles"));
+                    let label = match encoding {
+                        Some(code) if code >= 0 => self.label_of(code as u32, charset),
+                        _ => None,
+                    };
+                    return Ok((label, value));
+                }
+                _ => {}
+            }
+        }
+        self.fail(format!("expect `ENDCHAR` of `{name}`"))
+    }
+
+    fn charset(&mut self, registry: Option<&str>, encoding: Option<&str>) -> Charset {
+        let registry = registry.unwrap_or("").to_ascii_lowercase();
+        let encoding = encoding.unwrap_or("").to_ascii_lowercase();
+        // WHATWG maps ISO 8859-1 to windows-1252, which differs from it in C1 controls.
+        if registry.is_empty() || registry == "iso10646" || registry == "iso8859" && encoding == "1"
+        {
+            return Charset::Unicode;
+        }
+        let found = [format!("{registry}-{encoding}"), encoding.clone()]
+            .iter()
+            .find_map(|label| Encoding::for_label(label.as_bytes()))
+            .filter(|encoding| encoding.is_single_byte());
+        match found {
+            Some(encoding) => Charset::SingleByte(encoding),
+            None => {
+                self.warn(format!(
+                    "charset `{registry}-{encoding}` is unknown, so encodings are kept as codepoints"
+                ));
+                Charset::Unknown
+            }
+        }
+    }
+
+    fn label_of(&mut self, code: u32, charset: &Charset) -> Option<GlyphLabel> {
+        let ch = match charset {
+            Charset::Unicode => char::from_u32(code),
+            Charset::SingleByte(encoding) => u8::try_from(code).ok().and_then(|byte| {
+                let bytes = [byte];
+                let decoded =
+                    encoding.decode_without_bom_handling_and_without_replacement(&bytes)?;
+                decoded.chars().next()
+            }),
+            Charset::Unknown => return Some(GlyphLabel::CodepointSingle(code)),
+        };
+        if ch.is_none() {
+            self.warn(format!(
+                "encoding {code} is not a character, so it is left out"
+            ));
+        }
+        ch.map(GlyphLabel::CharacterSingle)
+    }
+
+    /// Gives the glyph its labels, leaving out ones taken by an earlier glyph.
+    fn label_glyph(
+        &mut self,
+        (label, value): (Option<GlyphLabel>, Option<GlyphValue>),
+        name: &str,
+        used_chars: &mut HashSet<String>,
+        used_tags: &mut HashSet<String>,
+    ) -> GlyphDefinition {
+        let mut labels = Vec::new();
+        if let Some(label) = label {
+            if used_chars.insert(label.to_string()) {
+                labels.push(label);
+            } else {
+                self.diagnostics.push(Diagnostic::warning(
+                    DiagnosticCode::DuplicateGlyph,
+                    self.path,
+                    self.span(),
+                    format!("{label} of `{name}` is already taken by an earlier glyph"),
+                ));
+            }
+        }
+        // names like `uni0041` only restate the encoding.
+        let is_generated = labels.iter().any(|label| match label {
+            GlyphLabel::CharacterSingle(ch) => is_generated_name(name, *ch as u32),
+            GlyphLabel::CodepointSingle(code) => is_generated_name(name, *code),
+            _ => false,
+        });
+        if !name.is_empty() && !is_generated && used_tags.insert(name.to_owned()) {
+            labels.push(GlyphLabel::Tag(name.to_owned()));
+        }
+        GlyphDefinition {
+            labels,
+            indent: "  ".to_owned(),
+            value,
+        }
+    }
+
+    fn hex_row(&mut self, row: &str, width: i32) -> Result<Vec<u8>, BdfImportError> {
+        let len = (width.max(0) as usize).div_ceil(8);
+        let bytes: Option<Vec<u8>> = (0..len)
+            .map(|idx| {
+                row.get(idx * 2..idx * 2 + 2)
+                    .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+            })
+            .collect();
+        match bytes {
+            Some(bytes) => Ok(bytes),
+            None => self.fail(format!("expect {len} bytes in hex but got `{row}`")),
+        }
+    }
+
+    fn integers<const N: usize>(&mut self, value: &str) -> Result<[i32; N], BdfImportError> {
+        let numbers: Vec<_> = value
+            .split_ascii_whitespace()
+            .map_while(|number| number.parse().ok())
+            .collect();
+        match numbers.get(..N) {
+            Some(numbers) => Ok(numbers.try_into().expect("sliced to N")),
+            None => self.fail(format!("expect {N} integers but got `{value}`")),
+        }
+    }
+
+    fn peek(&self) -> Option<(&'a str, &'a str)> {
+        let (_, line) = self.lines.get(self.position)?;
+        Some(
+            line.split_once(' ')
+                .map_or((line, ""), |(keyword, rest)| (keyword, rest.trim())),
+        )
+    }
+
+    fn next(&mut self) -> Option<(&'a str, &'a str)> {
+        let (_, line) = *self.lines.get(self.position)?;
+        self.position += 1;
+        Some(
+            line.split_once(' ')
+                .map_or((line, ""), |(keyword, rest)| (keyword, rest.trim())),
+        )
+    }
+
+    /// Span of the line consumed last.
+    fn span(&self) -> Option<Span> {
+        let (offset, line) = self.lines.get(self.position.checked_sub(1)?)?;
+        Some(Span::new(self.content, *offset..offset + line.len()))
+    }
+
+    fn warn(&mut self, message: String) {
+        self.diagnostics.push(Diagnostic::warning(
+            DiagnosticCode::LossyImport,
+            self.path,
+            self.span(),
+            message,
+        ));
+    }
+
+    fn fail<T>(&self, message: impl Into<String>) -> Result<T, BdfImportError> {
+        SyntaxSnafu {
+            path: self.path,
+            line: self.span().map_or(1, |span| span.line),
+            message,
+        }
+        .fail()
+    }
+}
+
+/// Whether `name` is made from `code` after a common convention, like `uni0041` or `U+0041`.
+fn is_generated_name(name: &str, code: u32) -> bool {
+    let hex = |digits: &str| u32::from_str_radix(digits, 16).ok() == Some(code);
+    if let Some(digits) = name.strip_prefix("uni").or(name.strip_prefix("U+")) {
+        hex(digits)
+    } else if let Some(digits) = name.strip_prefix('u') {
+        hex(digits)
+    } else if let Some(digits) = name.strip_prefix("char") {
+        digits.parse() == Ok(code)
+    } else {
+        false
+    }
+}
+
+/// Places the pixels of a `BBX` box into a cell of `(width, height)`,
+/// returning the cell and the number of pixels outside of it.
+fn fill_cell(
+    bitmap: &[Vec<u8>],
+    [width, height, x, y]: [i32; 4],
+    (cell_width, cell_height): (usize, usize),
+    ascent: i32,
+) -> (Vec<Vec<Option<GlyphPaletteColor>>>, usize) {
+    let mut data = vec![vec![None; cell_width]; cell_height];
+    let mut clipped = 0;
+    for (r, row) in bitmap.iter().enumerate() {
+        for c in 0..width.max(0) as usize {
+            let inked = row
+                .get(c / 8)
+                .is_some_and(|byte| byte & (0x80 >> (c % 8)) != 0);
+            if !inked {
+                continue;
+            }
+            // rows of the box count down from its top, which is `y + height` above the baseline.
+            let col = x + c as i32;
+            let cell_row = ascent - (y + height) + r as i32;
+            let pixel = usize::try_from(cell_row)
+                .ok()
+                .zip(usize::try_from(col).ok())
+                .and_then(|(cell_row, col)| data.get_mut(cell_row)?.get_mut(col));
+            match pixel {
+                Some(pixel) => *pixel = Some(GlyphPaletteColor::Zero),
+                None => clipped += 1,
+            }
+        }
+    }
+    (data, clipped)
+}
+
+/// Value of a property, whose quotes are doubled inside a string.
+fn unquote(value: &str) -> String {
+    match value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    {
+        Some(value) => value.replace("\"\"", "\""),
+        None => value.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backend::{BdfBackend, BdfOptions, FontBackend, FontStyle, SilentReporter},
+        font::{test_font, Font},
+        project::Project,
+    };
+
+    use super::*;
+
+    fn to_bdf(font: &Font) -> String {
+        let backend = BdfBackend::new(BdfOptions::default());
+        let artifacts = backend.build(font, &SilentReporter).unwrap();
+        String::from_utf8(artifacts[0].bytes.clone()).unwrap()
+    }
+
+    /// Rows of the glyph as YAFF draws them.
+    fn rows(glyph: &GlyphDefinition) -> Vec<String> {
+        let value = glyph.value.as_ref().unwrap();
+        value
+            .data
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|pixel| if pixel.is_some() { '@' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_fonts_through_projects() {
+        let source = "'A':\n  .@.\n  @.@\n  @@@\n  ...\n\n\
+                      'j':\n  ..@\n  ...\n  ..@\n  @@.\n\n\
+                      \"dot\":\n  ...\n  .@.\n  ...\n  ...\n";
+        let bdf = to_bdf(&test_font(source, 4, 1, FontStyle::default()));
+
+        let (imported, diagnostics) = parse_bdf(&bdf, "Test.bdf").unwrap();
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        assert_eq!(
+            (
+                imported.name.as_str(),
+                imported.weight,
+                imported.height,
+                imported.descent
+            ),
+            ("Test", 400, 4, 1)
+        );
+        let labels: Vec<_> = imported
+            .glyphs
+            .iter()
+            .map(|glyph| {
+                glyph
+                    .labels
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(labels, [["'A'"], ["'j'"], ["\"dot\""]]);
+        assert_eq!(rows(&imported.glyphs[1]), ["..@", "...", "..@", "@@."]);
+
+        let dir = tempfile::tempdir().unwrap();
+        imported.write_project(dir.path(), "test").unwrap();
+        let (project, diagnostics) = Project::load(dir.path());
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        let font = Font::from_project(&project, "Regular", FontStyle::default()).unwrap();
+        assert_eq!(to_bdf(&font), bdf);
+    }
+
+    #[test]
+    fn places_boxes_into_cells() {
+        let bdf = "STARTFONT 2.1\n\
+                   FONTBOUNDINGBOX 4 4 0 -1\n\
+                   STARTPROPERTIES 2\nFONT_ASCENT 3\nFONT_DESCENT 1\nENDPROPERTIES\n\
+                   CHARS 2\n\
+                   STARTCHAR bar\nENCODING 124\nDWIDTH 3 0\nBBX 1 4 1 -1\n\
+                   BITMAP\n80\n80\n80\n80\nENDCHAR\n\
+                   STARTCHAR wide\nENCODING -1\nDWIDTH 2 0\nBBX 3 1 0 0\n\
+                   BITMAP\nE0\nENDCHAR\n\
+                   ENDFONT\n";
+        let (imported, diagnostics) = parse_bdf(bdf, "unit.bdf").unwrap();
+        assert_eq!(rows(&imported.glyphs[0]), [".@.", ".@.", ".@.", ".@."]);
+        assert_eq!(rows(&imported.glyphs[1]), ["..", "..", "@@", ".."]);
+        let [clipped] = &diagnostics[..] else {
+            panic!("expect a single warning but got {diagnostics:?}");
+        };
+        assert_eq!(clipped.code, DiagnosticCode::LossyImport);
+        assert_eq!(
+            clipped.span.as_ref().unwrap().line,
+            24,
+            "at `ENDCHAR` of `wide`"
+        );
+    }
+
+    #[test]
+    fn labels_glyphs_by_charset() {
+        let bdf = |registry: &str, encoding: &str| {
+            format!(
+                "STARTFONT 2.1\nFONTBOUNDINGBOX 1 1 0 0\n\
+                 STARTPROPERTIES 2\nCHARSET_REGISTRY \"{registry}\"\n\
+                 CHARSET_ENCODING \"{encoding}\"\nENDPROPERTIES\n\
+                 CHARS 2\n\
+                 STARTCHAR Aogonek\nENCODING 161\nBBX 1 1 0 0\nBITMAP\n80\nENDCHAR\n\
+                 STARTCHAR uni00A1\nENCODING 161\nBBX 1 1 0 0\nBITMAP\n80\nENDCHAR\n\
+                 ENDFONT\n"
+            )
+        };
+        let import = |bdf: &str| {
+            let (imported, diagnostics) = parse_bdf(bdf, "unit.bdf").unwrap();
+            let labels: Vec<_> = imported
+                .glyphs
+                .iter()
+                .flat_map(|glyph| &glyph.labels)
+                .map(ToString::to_string)
+                .collect();
+            let codes: Vec<_> = diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.code)
+                .collect();
+            (labels, codes)
+        };
+
+        let (labels, codes) = import(&bdf("ISO8859", "2"));
+        assert_eq!(labels, ["'Ą'", "\"Aogonek\"", "\"uni00A1\""]);
+        assert_eq!(codes, [DiagnosticCode::DuplicateGlyph]);
+
+        let (labels, _) = import(&bdf("ISO10646", "1"));
+        assert_eq!(labels[0], "'¡'");
+
+        let (labels, codes) = import(&bdf("Unknown", "0"));
+        assert_eq!(labels[0], "0xa1");
+        assert_eq!(codes[0], DiagnosticCode::LossyImport);
+    }
+
+    #[test]
+    fn reports_syntax_errors_with_lines() {
+        let bdf = "STARTFONT 2.1\nFONTBOUNDINGBOX 1 1 0 0\nCHARS 1\n\
+                   STARTCHAR a\nBBX 8 1 0 0\nBITMAP\nZZ\nENDCHAR\nENDFONT\n";
+        let Err(BdfImportError::Syntax { line, .. }) = parse_bdf(bdf, "unit.bdf") else {
+            panic!("expect a syntax error");
+        };
+        assert_eq!(line, 7);
+        assert!(parse_bdf("STARTCHAR a\n", "unit.bdf").is_err());
+    }
+}
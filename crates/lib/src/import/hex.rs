@@ -0,0 +1,223 @@
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use snafu::prelude::*;
+use yaff::{GlyphDefinition, GlyphLabel, GlyphPaletteColor, GlyphValue};
+
+use crate::diagnostic::{Diagnostic, DiagnosticCode, Span};
+
+use super::ImportedFont;
+
+/// Rows of every glyph of Unifont.
+pub const HEX_HEIGHT: usize = 16;
+/// Rows of Unifont glyphs below the baseline.
+const HEX_DESCENT: usize = 2;
+
+#[derive(Debug, Snafu)]
+pub enum HexImportError {
+    #[snafu(display("failed to read {path}", path = path.to_string_lossy()))]
+    Io { path: PathBuf, source: io::Error },
+    #[snafu(display("{path}:{line}: {message}", path = path.to_string_lossy()))]
+    Syntax {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+/// Reads the Unifont `.hex` file at `path`, see [`parse_hex`].
+pub fn read_hex(path: impl AsRef<Path>) -> Result<(ImportedFont, Vec<Diagnostic>), HexImportError> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path).context(IoSnafu { path })?;
+    parse_hex(&content, path)
+}
+
+/// Parses `CODEPOINT:HEXBITS` lines of GNU Unifont into glyphs with codepoint labels.
+///
+/// Every glyph is 16 pixels high and as wide as its bits allow, which is 8 or 16 pixels
+/// in Unifont itself. Empty lines and lines starting with `#` are skipped,
+/// and a codepoint taken by an earlier line is reported as a warning and left out.
+/// The font is named after the file stem of `path`, which is otherwise only used for messages.
+pub fn parse_hex(
+    content: &str,
+    path: impl AsRef<Path>,
+) -> Result<(ImportedFont, Vec<Diagnostic>), HexImportError> {
+    let path = path.as_ref();
+    let mut glyphs = Vec::new();
+    let mut diagnostics = Vec::new();
+    let mut used = HashSet::new();
+    let mut offset = 0;
+    for (idx, line) in content.split_inclusive('\n').enumerate() {
+        let start = offset;
+        offset += line.len();
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fail = |message: String| SyntaxSnafu {
+            path,
+            line: idx + 1,
+            message,
+        };
+        let (code, bits) = line
+            .split_once(':')
+            .with_context(|| fail("expect `CODEPOINT:HEXBITS`".to_owned()))?;
+        // `from_str_radix` takes a leading sign, so the digits are checked first.
+        let is_hex = !code.is_empty() && code.bytes().all(|b| b.is_ascii_hexdigit());
+        let code = is_hex
+            .then(|| u32::from_str_radix(code, 16).ok())
+            .flatten()
+            .with_context(|| fail(format!("`{code}` is not a hexadecimal codepoint")))?;
+        ensure!(
+            char::from_u32(code).is_some(),
+            fail(format!("codepoint {code:04X} is not a character"))
+        );
+        ensure!(
+            !bits.is_empty() && bits.len() % (HEX_HEIGHT * 2) == 0,
+            fail(format!(
+                "expect a multiple of {} hex digits for 16 rows but got {}",
+                HEX_HEIGHT * 2,
+                bits.len()
+            ))
+        );
+        let digits_per_row = bits.len() / HEX_HEIGHT;
+        let data = bits
+            .as_bytes()
+            .chunks(digits_per_row)
+            .map(hex_row)
+            .collect::<Option<Vec<_>>>()
+            .with_context(|| fail("expect hex digits after `:`".to_owned()))?;
+
+        if !used.insert(code) {
+            diagnostics.push(Diagnostic::warning(
+                DiagnosticCode::DuplicateGlyph,
+                path,
+                Some(Span::new(content, start..start + line.len())),
+                format!("codepoint {code:04X} is already taken by an earlier line"),
+            ));
+            continue;
+        }
+        glyphs.push(GlyphDefinition {
+            labels: vec![GlyphLabel::CodepointSingle(code)],
+            indent: String::new(),
+            value: Some(GlyphValue::new(data).expect("rows are as long")),
+        });
+    }
+
+    let font = ImportedFont {
+        name: path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+        weight: 400,
+        height: HEX_HEIGHT,
+        descent: HEX_DESCENT,
+        glyphs,
+    };
+    Ok((font, diagnostics))
+}
+
+/// Pixels of a row of hex digits, most significant bit first.
+fn hex_row(row: &[u8]) -> Option<Vec<Option<GlyphPaletteColor>>> {
+    let mut pixels = Vec::with_capacity(row.len() * 4);
+    for &digit in row {
+        let nibble = (digit as char).to_digit(16)?;
+        pixels.extend(
+            (0..4)
+                .rev()
+                .map(|bit| (nibble >> bit & 1 == 1).then_some(GlyphPaletteColor::Zero)),
+        );
+    }
+    Some(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::project::Project;
+
+    use super::*;
+
+    fn line(code: &str, row: &str) -> String {
+        format!("{code}:{}\n", row.repeat(HEX_HEIGHT))
+    }
+
+    #[test]
+    fn reads_8_and_16_pixel_glyphs() {
+        let content = format!(
+            "# comment\n\n{}{}",
+            line("0041", "81"),
+            line("4E00", "8001")
+        );
+        let (imported, diagnostics) = parse_hex(&content, "unifont.hex").unwrap();
+        assert!(diagnostics.is_empty());
+        assert_eq!(imported.name, "unifont");
+        let widths: Vec<_> = imported
+            .glyphs
+            .iter()
+            .map(|glyph| glyph.value.as_ref().unwrap().width)
+            .collect();
+        assert_eq!(widths, [8, 16]);
+        let row = &imported.glyphs[1].value.as_ref().unwrap().data[0];
+        assert!(row[0].is_some() && row[15].is_some());
+        assert!(row[1..15].iter().all(Option::is_none));
+        assert!(matches!(
+            imported.glyphs[1].labels[..],
+            [GlyphLabel::CodepointSingle(0x4E00)]
+        ));
+    }
+
+    #[test]
+    fn keeps_the_first_of_duplicate_codepoints() {
+        let content = [line("0041", "FF"), line("0041", "00")].concat();
+        let (imported, diagnostics) = parse_hex(&content, "unifont.hex").unwrap();
+        assert_eq!(imported.glyphs.len(), 1);
+        assert!(imported.glyphs[0].value.as_ref().unwrap().data[0][0].is_some());
+        let [duplicate] = &diagnostics[..] else {
+            panic!("expect a single warning but got {diagnostics:?}");
+        };
+        assert_eq!(duplicate.code, DiagnosticCode::DuplicateGlyph);
+        assert_eq!(duplicate.span.as_ref().unwrap().line, 2);
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        let cases = [
+            ("0041\n".to_owned(), "expect `CODEPOINT:HEXBITS`"),
+            (line("XYZ", "00"), "`XYZ` is not a hexadecimal codepoint"),
+            (
+                line("123456789", "00"),
+                "`123456789` is not a hexadecimal codepoint",
+            ),
+            (line("+41", "00"), "`+41` is not a hexadecimal codepoint"),
+            (line("D800", "00"), "codepoint D800 is not a character"),
+            (line("110000", "00"), "codepoint 110000 is not a character"),
+            ("0041:00\n".to_owned(), "expect a multiple of 32 hex digits"),
+            (line("0041", "0G"), "expect hex digits after `:`"),
+        ];
+        for (content, expected) in cases {
+            let content = format!("{}{content}", line("0020", "00"));
+            let Err(HexImportError::Syntax { line, message, .. }) =
+                parse_hex(&content, "unifont.hex")
+            else {
+                panic!("expect a syntax error for {content:?}");
+            };
+            assert_eq!(line, 2);
+            assert!(message.starts_with(expected), "{message}");
+        }
+    }
+
+    #[test]
+    fn writes_projects_which_load() {
+        let (imported, _) = parse_hex(&line("0041", "18"), "unifont.hex").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        imported.write_project(dir.path(), "unifont").unwrap();
+        let (project, diagnostics) = Project::load(dir.path());
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        let glyph = project.find_glyph('A').unwrap();
+        assert_eq!(glyph.glyph.value.as_ref().unwrap().height, 16);
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use snafu::prelude::*;
+use unicode_blocks::find_unicode_block;
+use yaff::{BlockElement, Comment, Document, GlyphDefinition, GlyphLabel};
+
+mod bdf;
+mod hex;
+mod outline;
+
+pub use bdf::{parse_bdf, read_bdf, BdfImportError};
+pub use hex::{parse_hex, read_hex, HexImportError, HEX_HEIGHT};
+pub use outline::{
+    devectorize_font, rasterize_font, read_outline_font, read_pixel_font, OutlineImportError,
+    RasterizeOptions,
+};
+
+/// Glyphs read from a font of another format, ready to be written as a project.
+pub struct ImportedFont {
+    /// Name of the font the glyphs are read from, used as the project name.
+    pub name: String,
+    /// Weight from 100 to 900, 400 being regular.
+    pub weight: u16,
+    /// Rows of every glyph cell.
+    pub height: usize,
+    /// Rows of the cell below the baseline.
+    pub descent: usize,
+    /// Glyphs with full cells, in the order of the source font.
+    pub glyphs: Vec<GlyphDefinition>,
+}
+
+#[derive(Debug, Snafu)]
+pub enum WriteProjectError {
+    #[snafu(display("failed to create {path}", path = path.to_string_lossy()))]
+    CreateDir { path: PathBuf, source: io::Error },
+    #[snafu(display("failed to write {path}", path = path.to_string_lossy()))]
+    WriteFile { path: PathBuf, source: io::Error },
+}
+
+impl ImportedFont {
+    /// Writes the glyphs as YAFF files of the project at `dir`, returning the written files.
+    ///
+    /// Glyphs are filed under `src/blocks/<Block>/<file_stem>.yaff` by the Unicode block
+    /// of their first character, and the rest go to `src/<file_stem>.yaff`.
+    /// `project.toml` is created unless the project has one already.
+    /// Existing source files are never overwritten.
+    pub fn write_project(
+        self,
+        dir: impl AsRef<Path>,
+        file_stem: &str,
+    ) -> Result<Vec<PathBuf>, WriteProjectError> {
+        let dir = dir.as_ref();
+        let manifest = self.manifest();
+        let header = format!("imported from {}", self.name);
+        let mut files: BTreeMap<PathBuf, Vec<GlyphDefinition>> = BTreeMap::new();
+        for glyph in self.glyphs {
+            let block = glyph.labels.iter().find_map(|label| match label {
+                GlyphLabel::CharacterSingle(ch) => find_unicode_block(*ch),
+                GlyphLabel::CodepointSingle(code) => find_unicode_block(char::from_u32(*code)?),
+                GlyphLabel::CharacterSequence(vec) => find_unicode_block(*vec.first()?),
+                _ => None,
+            });
+            let file_name = format!("{file_stem}.yaff");
+            let path = match block {
+                Some(block) => Path::new("src")
+                    .join("blocks")
+                    .join(block.name())
+                    .join(file_name),
+                None => Path::new("src").join(file_name),
+            };
+            files.entry(path).or_default().push(glyph);
+        }
+
+        let mut written = Vec::new();
+        for (relative_path, glyphs) in files {
+            let path = dir.join(relative_path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).context(CreateDirSnafu { path: parent })?;
+            }
+            let mut elements = vec![
+                BlockElement::Comment(Comment(header.clone())),
+                BlockElement::Whitespace("\n".to_owned()),
+            ];
+            for glyph in glyphs {
+                elements.push(BlockElement::GlyphDefinition(glyph));
+                elements.push(BlockElement::Whitespace("\n\n\n".to_owned()));
+            }
+            let content = Document::new(elements).to_string();
+            create_new(&path, &content).context(WriteFileSnafu { path: &path })?;
+            written.push(path);
+        }
+
+        let manifest_path = dir.join("project.toml");
+        match create_new(&manifest_path, &manifest) {
+            Ok(()) => written.push(manifest_path),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(source) => {
+                return Err(WriteProjectError::WriteFile {
+                    path: manifest_path,
+                    source,
+                })
+            }
+        }
+        Ok(written)
+    }
+
+    fn manifest(&self) -> String {
+        format!(
+            "[project]\n\
+             name = {}\n\
+             weight = {}\n\
+             height = {}\n\
+             descender = {}\n",
+            toml::Value::String(self.name.clone()),
+            self.weight,
+            self.height,
+            self.descent,
+        )
+    }
+}
+
+/// Writes a file which must not exist yet.
+fn create_new(path: &Path, content: &str) -> io::Result<()> {
+    use std::io::Write;
+
+    fs::File::create_new(path)?.write_all(content.as_bytes())
+}
+
+/// Weight of an XLFD or similar weight name, regular if unknown.
+fn weight_of(name: &str) -> u16 {
+    match name
+        .to_ascii_lowercase()
+        .replace([' ', '-', '_'], "")
+        .as_str()
+    {
+        "thin" | "hairline" => 100,
+        "extralight" | "ultralight" => 200,
+        "light" => 300,
+        "semibold" | "demibold" | "demi" => 600,
+        "bold" => 700,
+        "extrabold" | "ultrabold" => 800,
+        "black" | "heavy" => 900,
+        _ => 400,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use yaff::{GlyphPaletteColor, GlyphValue};
+
+    use crate::project::{temp_project, Project};
+
+    use super::*;
+
+    fn imported(labels: Vec<Vec<GlyphLabel>>) -> ImportedFont {
+        ImportedFont {
+            name: "Imported \"Font\"".to_owned(),
+            weight: 700,
+            height: 1,
+            descent: 0,
+            glyphs: labels
+                .into_iter()
+                .map(|labels| GlyphDefinition {
+                    labels,
+                    indent: String::new(),
+                    value: Some(
+                        GlyphValue::new(vec![vec![Some(GlyphPaletteColor::Zero)]]).unwrap(),
+                    ),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn files_glyphs_by_block() {
+        let font = imported(vec![
+            vec![GlyphLabel::CodepointSingle(0x41)],
+            vec![GlyphLabel::CharacterSingle('가')],
+            vec![GlyphLabel::Tag("notdef".to_owned())],
+            vec![GlyphLabel::CharacterSequence(vec!['f', 'i'])],
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let written = font.write_project(dir.path(), "font").unwrap();
+        let written: Vec<_> = written
+            .iter()
+            .map(|path| path.strip_prefix(dir.path()).unwrap().to_owned())
+            .collect();
+        assert_eq!(
+            written,
+            [
+                Path::new("src/blocks/Basic Latin/font.yaff"),
+                Path::new("src/blocks/Hangul Syllables/font.yaff"),
+                Path::new("src/font.yaff"),
+                Path::new("project.toml"),
+            ]
+        );
+        let (project, diagnostics) = Project::load(dir.path());
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        let settings = &project.manifest.project;
+        assert_eq!(settings.name.as_deref(), Some("Imported \"Font\""));
+        assert_eq!((settings.weight, settings.height), (Some(700), Some(1)));
+        assert_eq!(project.list_glyph().count(), 4);
+    }
+
+    #[test]
+    fn keeps_existing_files() {
+        let dir = temp_project(&[("project.toml", "# mine\n")]);
+        let font = || imported(vec![vec![GlyphLabel::CharacterSingle('A')]]);
+        let written = font().write_project(dir.path(), "font").unwrap();
+        assert_eq!(written.len(), 1, "the manifest is kept");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("project.toml")).unwrap(),
+            "# mine\n"
+        );
+        assert!(matches!(
+            font().write_project(dir.path(), "font"),
+            Err(WriteProjectError::WriteFile { .. })
+        ));
+    }
+
+    #[test]
+    fn reads_weight_names() {
+        let weights: Vec<_> = [
+            "Thin",
+            "Extra-Light",
+            "light",
+            "Medium",
+            "Demi Bold",
+            "BOLD",
+            "heavy",
+        ]
+        .into_iter()
+        .map(weight_of)
+        .collect();
+        assert_eq!(weights, [100, 200, 300, 400, 600, 700, 900]);
+    }
+}
@@ -0,0 +1,1163 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use kurbo::{Affine, BezPath, PathEl, Point, Rect, Shape};
+use snafu::prelude::*;
+use write_fonts::read::{
+    tables::{
+        cmap::{Cmap, Cmap12, Cmap4, CmapSubtable, PlatformId},
+        glyf::{Anchor, CurvePoint, Glyf, Glyph as GlyfGlyph},
+        loca::Loca,
+        postscript::{self, charstring::CommandSink, dict, FdSelect, Index},
+    },
+    types::{Fixed, GlyphId, GlyphId16, NameId, Version16Dot16},
+    FontData, FontRead, FontRef, ReadError, TableProvider,
+};
+use yaff::{GlyphDefinition, GlyphLabel, GlyphPaletteColor, GlyphValue};
+
+use crate::diagnostic::{Diagnostic, DiagnosticCode};
+
+use super::{weight_of, ImportedFont};
+
+/// Sub-scanlines sampled for every row of pixels.
+const SUBSAMPLES: usize = 16;
+/// Nesting of composite glyphs followed before giving up, as fonts may reference themselves.
+const MAX_COMPONENT_DEPTH: usize = 16;
+/// Tolerance of flattening curves into lines, in pixels.
+const FLATTEN_TOLERANCE: f64 = 0.01;
+/// Cells higher than this tell that outlines are not on a pixel grid.
+const MAX_PIXEL_CELL_HEIGHT: usize = 256;
+
+/// How an outline font is rendered into bitmaps.
+#[derive(Debug, Clone)]
+pub struct RasterizeOptions {
+    /// Pixels per em, which is the size of the font in pixels.
+    pub ppem: u16,
+    /// Characters to render, the ones missing from the font are skipped.
+    pub chars: BTreeSet<char>,
+    /// Coverage of a pixel from 0 to 1 from which it is inked, unless `grayscale` is set.
+    pub threshold: f32,
+    /// Map coverage to the opacities of the palette, `0` for full, then `8`, `7` and `15`
+    /// for three quarters, half and a quarter.
+    pub grayscale: bool,
+}
+
+impl Default for RasterizeOptions {
+    fn default() -> Self {
+        RasterizeOptions {
+            ppem: 12,
+            chars: BTreeSet::new(),
+            threshold: 0.5,
+            grayscale: false,
+        }
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum OutlineImportError {
+    #[snafu(display("failed to read {path}", path = path.to_string_lossy()))]
+    Io { path: PathBuf, source: io::Error },
+    #[snafu(display("{path} is not a valid TrueType or OpenType font", path = path.to_string_lossy()))]
+    ReadFont { path: PathBuf, source: ReadError },
+    #[snafu(display("{path} has a malformed `CFF ` table: {message}", path = path.to_string_lossy()))]
+    ReadCff { path: PathBuf, message: String },
+    #[snafu(display("{path} has no `glyf` or `CFF ` outlines", path = path.to_string_lossy()))]
+    NoOutlines { path: PathBuf },
+    #[snafu(display("{path} has no Unicode `cmap` subtable", path = path.to_string_lossy()))]
+    NoUnicodeCmap { path: PathBuf },
+    #[snafu(display("ppem must not be zero"))]
+    PpemZero,
+    #[snafu(display("expect a threshold between 0 and 1 but got {threshold}"))]
+    InvalidThreshold { threshold: f32 },
+    #[snafu(display("grid must not be zero"))]
+    GridZero,
+    #[snafu(display("{path} has no outlines to find the pixel grid from", path = path.to_string_lossy()))]
+    NoGrid { path: PathBuf },
+    #[snafu(display(
+        "{path} does not look like a pixel font, a grid of {grid} units makes cells {height} pixels high",
+        path = path.to_string_lossy()
+    ))]
+    NotPixelFont {
+        path: PathBuf,
+        grid: u64,
+        height: usize,
+    },
+}
+
+/// Reads the TrueType or OpenType font at `path`, see [`rasterize_font`].
+pub fn read_outline_font(
+    path: impl AsRef<Path>,
+    options: &RasterizeOptions,
+) -> Result<(ImportedFont, Vec<Diagnostic>), OutlineImportError> {
+    let path = path.as_ref();
+    let data = fs::read(path).context(IoSnafu { path })?;
+    rasterize_font(&data, path, options)
+}
+
+/// Renders characters of an outline font into glyphs with full cells.
+///
+/// The cell is as high as the ascender plus the descender of `hhea` at `options.ppem`,
+/// and every glyph is as wide as its rounded advance. Glyphs are sampled with
+/// [`SUBSAMPLES`] scanlines per row and exact spans along them, under the nonzero rule.
+/// Pixels outside of the cell and outlines which cannot be read are reported as warnings.
+/// `path` is only used for messages and as the fallback name of the font.
+pub fn rasterize_font(
+    data: &[u8],
+    path: impl AsRef<Path>,
+    options: &RasterizeOptions,
+) -> Result<(ImportedFont, Vec<Diagnostic>), OutlineImportError> {
+    let path = path.as_ref();
+    ensure!(options.ppem > 0, PpemZeroSnafu);
+    ensure!(
+        (0.0..=1.0).contains(&options.threshold),
+        InvalidThresholdSnafu {
+            threshold: options.threshold
+        }
+    );
+    let font = FontRef::new(data).context(ReadFontSnafu { path })?;
+    let outlines = Outlines::new(&font, path)?;
+    let head = font.head().context(ReadFontSnafu { path })?;
+    let hhea = font.hhea().context(ReadFontSnafu { path })?;
+    let hmtx = font.hmtx().context(ReadFontSnafu { path })?;
+    let cmap = font.cmap().context(ReadFontSnafu { path })?;
+    let cmap = UnicodeCmap::new(&cmap).context(NoUnicodeCmapSnafu { path })?;
+
+    let scale = options.ppem as f64 / head.units_per_em().max(1) as f64;
+    let ascent = (hhea.ascender().to_i16() as f64 * scale).round().max(0.) as usize;
+    let descent = (-hhea.descender().to_i16() as f64 * scale).round().max(0.) as usize;
+    let height = ascent + descent;
+    // pixels grow down from the top of the cell, while font units grow up from the baseline.
+    let to_pixels = Affine::new([scale, 0., 0., -scale, 0., ascent as f64]);
+
+    let mut diagnostics = Vec::new();
+    let mut warn = |message: String| {
+        diagnostics.push(Diagnostic::warning(
+            DiagnosticCode::LossyImport,
+            path,
+            None,
+            message,
+        ));
+    };
+    let mut glyphs = Vec::new();
+    for &ch in &options.chars {
+        // glyph 0 is what fonts show for characters they lack.
+        let Some(gid) = cmap.map(ch).filter(|&gid| gid != GlyphId::NOTDEF) else {
+            continue;
+        };
+        let advance = hmtx.advance(gid).unwrap_or(0) as f64 * scale;
+        let width = advance.round().max(0.) as usize;
+        let mut notes = Vec::new();
+        let outline = match outlines.path(gid, 0, &mut notes) {
+            Ok(outline) => outline,
+            Err(message) => {
+                warn(format!(
+                    "outline of U+{:04X} is left empty: {message}",
+                    ch as u32
+                ));
+                BezPath::new()
+            }
+        };
+        for note in notes {
+            warn(format!("U+{:04X}: {note}", ch as u32));
+        }
+        let outline = to_pixels * outline;
+
+        let cell = Rect::new(0., 0., width as f64, height as f64);
+        // rendered with a margin around the cell to tell whether anything is clipped.
+        let bounds = outline.bounding_box().union(cell).expand();
+        let coverage = rasterize(&outline, bounds);
+        let (left, top) = (-bounds.x0 as usize, -bounds.y0 as usize);
+        let pixel = |coverage: f64| quantize(coverage, options);
+        let is_clipped = coverage.iter().enumerate().any(|(y, row)| {
+            row.iter().enumerate().any(|(x, &coverage)| {
+                let is_outside =
+                    !(top..top + height).contains(&y) || !(left..left + width).contains(&x);
+                is_outside && pixel(coverage).is_some()
+            })
+        });
+        if is_clipped {
+            warn(format!(
+                "U+{:04X} does not fit its {width}x{height} cell and is clipped",
+                ch as u32
+            ));
+        }
+        let data: Vec<Vec<_>> = coverage[top..top + height]
+            .iter()
+            .map(|row| row[left..left + width].iter().map(|&c| pixel(c)).collect())
+            .collect();
+        glyphs.push(GlyphDefinition {
+            labels: vec![GlyphLabel::CharacterSingle(ch)],
+            indent: String::new(),
+            value: (width > 0).then(|| GlyphValue::new(data).expect("cells are rectangles")),
+        });
+    }
+
+    let name = font_name(&font).unwrap_or_else(|| {
+        path.file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    });
+    let weight = font
+        .os2()
+        .map(|os2| os2.us_weight_class())
+        .unwrap_or_else(|_| weight_of(&name));
+    let imported = ImportedFont {
+        name: format!("{name} {}px", options.ppem),
+        weight,
+        height,
+        descent,
+        glyphs,
+    };
+    Ok((imported, diagnostics))
+}
+
+/// Reads the TrueType or OpenType pixel font at `path`, see [`devectorize_font`].
+pub fn read_pixel_font(
+    path: impl AsRef<Path>,
+    grid: Option<u16>,
+) -> Result<(ImportedFont, Vec<Diagnostic>), OutlineImportError> {
+    let path = path.as_ref();
+    let data = fs::read(path).context(IoSnafu { path })?;
+    devectorize_font(&data, path, grid)
+}
+
+/// Recovers the bitmaps of a font whose outlines are made of pixels, like the ones built
+/// by the studio itself or by tools drawing on a grid.
+///
+/// Pixels are `grid` font units wide and high, found as the greatest common divisor
+/// of every advance and every point of straight outlines if omitted.
+/// A pixel is inked when its center is inside of the outline under the nonzero rule,
+/// which is exact for outlines on the grid however their squares overlap.
+/// The cell spans the bounds of `head`, the ascender and descender of `hhea` and every glyph,
+/// and every glyph is as wide as its advance. Every glyph mapped in the Unicode subtable
+/// of `cmap` is read with all of its characters as labels. Curves, pixels left of the origin
+/// and outlines which cannot be read are reported as warnings. `path` is only used for messages and as the fallback name.
+pub fn devectorize_font(
+    data: &[u8],
+    path: impl AsRef<Path>,
+    grid: Option<u16>,
+) -> Result<(ImportedFont, Vec<Diagnostic>), OutlineImportError> {
+    let path = path.as_ref();
+    ensure!(grid != Some(0), GridZeroSnafu);
+    let font = FontRef::new(data).context(ReadFontSnafu { path })?;
+    let outlines = Outlines::new(&font, path)?;
+    let head = font.head().context(ReadFontSnafu { path })?;
+    let hhea = font.hhea().context(ReadFontSnafu { path })?;
+    let hmtx = font.hmtx().context(ReadFontSnafu { path })?;
+    let cmap = font.cmap().context(ReadFontSnafu { path })?;
+    let cmap = UnicodeCmap::new(&cmap).context(NoUnicodeCmapSnafu { path })?;
+
+    let mut diagnostics = Vec::new();
+    let mut warn = |message: String| {
+        diagnostics.push(Diagnostic::warning(
+            DiagnosticCode::LossyImport,
+            path,
+            None,
+            message,
+        ));
+    };
+    // glyph 0 is a real glyph of fonts built without `.notdef`, like the ones of the studio,
+    // unless `post` names it otherwise. Version 1.0 names glyph 0 `.notdef` in any case.
+    let has_notdef = font.post().is_ok_and(|post| {
+        post.version() != Version16Dot16::VERSION_1_0
+            && post.glyph_name(GlyphId16::NOTDEF) == Some(".notdef")
+    });
+    let mut chars: BTreeMap<GlyphId, Vec<char>> = BTreeMap::new();
+    for (ch, gid) in cmap.mappings() {
+        if !(has_notdef && gid == GlyphId::NOTDEF) {
+            chars.entry(gid).or_default().push(ch);
+        }
+    }
+
+    let mut glyphs = Vec::new();
+    let mut divisor = 0;
+    // `head` only bounds the ink, while `hhea` keeps rows of the cell nothing is drawn in.
+    let mut bounds = Rect::new(
+        0.,
+        head.y_min().min(hhea.descender().to_i16()) as f64,
+        0.,
+        head.y_max().max(hhea.ascender().to_i16()) as f64,
+    );
+    for (&gid, chars) in &chars {
+        let labels = display_chars(chars);
+        let mut notes = Vec::new();
+        let outline = match outlines.path(gid, 0, &mut notes) {
+            Ok(outline) => outline,
+            Err(message) => {
+                warn(format!("outline of {labels} is left empty: {message}"));
+                BezPath::new()
+            }
+        };
+        for note in notes {
+            warn(format!("{labels}: {note}"));
+        }
+        let advance = hmtx.advance(gid).unwrap_or(0);
+        divisor = gcd(divisor, advance as u64);
+        // a curved outline is not drawn on the grid, so none of its points tell the grid.
+        let mut coords = Vec::new();
+        let mut is_straight = true;
+        for el in outline.elements() {
+            match el {
+                PathEl::MoveTo(p) | PathEl::LineTo(p) => {
+                    for coord in [p.x, p.y] {
+                        if coord.fract() == 0. {
+                            coords.push(coord.abs() as u64);
+                        } else {
+                            is_straight = false;
+                        }
+                    }
+                }
+                PathEl::QuadTo(..) | PathEl::CurveTo(..) => is_straight = false,
+                PathEl::ClosePath => {}
+            }
+        }
+        if is_straight {
+            divisor = coords.into_iter().fold(divisor, gcd);
+        } else {
+            warn(format!(
+                "{labels} is not made of squares on whole units, so it is sampled at pixel centers"
+            ));
+        }
+        if !outline.is_empty() {
+            bounds = bounds.union(outline.bounding_box());
+        }
+        glyphs.push((chars, labels, advance, outline));
+    }
+
+    let grid = match grid {
+        Some(grid) => grid as f64,
+        None => {
+            ensure!(divisor > 0, NoGridSnafu { path });
+            divisor as f64
+        }
+    };
+    let ascent = (bounds.y1 / grid).ceil().max(0.) as usize;
+    let descent = (-bounds.y0 / grid).ceil().max(0.) as usize;
+    let height = ascent + descent;
+    ensure!(
+        height <= MAX_PIXEL_CELL_HEIGHT,
+        NotPixelFontSnafu {
+            path,
+            grid: grid as u64,
+            height
+        }
+    );
+    // centers of pixels in font units, which never lie on an outline on the grid.
+    let center = |row: usize, col: isize| {
+        Point::new(
+            (col as f64 + 0.5) * grid,
+            (ascent as f64 - row as f64 - 0.5) * grid,
+        )
+    };
+
+    let mut definitions = Vec::new();
+    for (chars, labels, advance, outline) in glyphs {
+        let width = (advance as f64 / grid).round() as usize;
+        if advance as f64 % grid != 0. {
+            warn(format!(
+                "advance of {labels} is not on the grid and is rounded to {width} pixels"
+            ));
+        }
+        let data: Vec<Vec<_>> = (0..height)
+            .map(|row| {
+                (0..width)
+                    .map(|col| {
+                        (outline.winding(center(row, col as isize)) != 0)
+                            .then_some(GlyphPaletteColor::Zero)
+                    })
+                    .collect()
+            })
+            .collect();
+        if !outline.is_empty() {
+            let ink = outline.bounding_box();
+            let (first, last) = ((ink.x0 / grid).floor(), (ink.x1 / grid).ceil());
+            let is_clipped = (first as isize..last as isize)
+                .filter(|&col| col < 0 || col as usize >= width)
+                .any(|col| (0..height).any(|row| outline.winding(center(row, col)) != 0));
+            if is_clipped {
+                warn(format!(
+                    "{labels} does not fit its {width}x{height} cell and is clipped"
+                ));
+            }
+        }
+        definitions.push(GlyphDefinition {
+            labels: chars
+                .iter()
+                .map(|&ch| GlyphLabel::CharacterSingle(ch))
+                .collect(),
+            indent: String::new(),
+            value: (width > 0).then(|| GlyphValue::new(data).expect("cells are rectangles")),
+        });
+    }
+
+    let name = font_name(&font).unwrap_or_else(|| {
+        path.file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    });
+    let weight = font
+        .os2()
+        .map(|os2| os2.us_weight_class())
+        .unwrap_or_else(|_| weight_of(&name));
+    let imported = ImportedFont {
+        name,
+        weight,
+        height,
+        descent,
+        glyphs: definitions,
+    };
+    Ok((imported, diagnostics))
+}
+
+/// The Unicode subtable of `cmap`, preferring the full repertoire to the BMP.
+///
+/// Subtables of other platforms map bytes of legacy encodings like Mac Roman,
+/// which would give their glyphs to the wrong characters.
+enum UnicodeCmap<'a> {
+    Format4(Cmap4<'a>),
+    Format12(Cmap12<'a>),
+}
+
+impl<'a> UnicodeCmap<'a> {
+    fn new(cmap: &Cmap<'a>) -> Option<UnicodeCmap<'a>> {
+        cmap.encoding_records()
+            .iter()
+            .filter_map(|record| {
+                let subtable = record.subtable(cmap.offset_data()).ok()?;
+                match (record.platform_id(), record.encoding_id(), subtable) {
+                    (PlatformId::Windows, 10, CmapSubtable::Format12(subtable)) => {
+                        Some((0, UnicodeCmap::Format12(subtable)))
+                    }
+                    (PlatformId::Unicode, _, CmapSubtable::Format12(subtable)) => {
+                        Some((1, UnicodeCmap::Format12(subtable)))
+                    }
+                    (PlatformId::Windows, 1, CmapSubtable::Format4(subtable)) => {
+                        Some((2, UnicodeCmap::Format4(subtable)))
+                    }
+                    (PlatformId::Unicode, _, CmapSubtable::Format4(subtable)) => {
+                        Some((3, UnicodeCmap::Format4(subtable)))
+                    }
+                    _ => None,
+                }
+            })
+            .min_by_key(|(rank, _)| *rank)
+            .map(|(_, cmap)| cmap)
+    }
+
+    fn map(&self, ch: char) -> Option<GlyphId> {
+        match self {
+            UnicodeCmap::Format4(subtable) => subtable.map_codepoint(ch),
+            UnicodeCmap::Format12(subtable) => subtable.map_codepoint(ch),
+        }
+    }
+
+    /// Every character mapped by the subtable in codepoint order, glyph 0 included.
+    fn mappings(&self) -> BTreeMap<char, GlyphId> {
+        // the iterators leave out characters mapped to glyph 0, which is a real glyph of fonts
+        // without `.notdef`, so those are found where a segment or group starts from it.
+        let (mapped, notdef): (Vec<_>, Vec<_>) = match self {
+            UnicodeCmap::Format4(subtable) => (
+                subtable.iter().collect(),
+                (subtable.start_code().iter())
+                    .zip(subtable.end_code())
+                    .zip(subtable.id_delta())
+                    .filter_map(|((start, end), delta)| {
+                        let code = 0u16.wrapping_sub(delta.get() as u16);
+                        (start.get()..=end.get())
+                            .contains(&code)
+                            .then_some(code as u32)
+                    })
+                    .collect(),
+            ),
+            UnicodeCmap::Format12(subtable) => (
+                subtable.iter().collect(),
+                (subtable.groups().iter())
+                    .filter(|group| group.start_glyph_id() == 0)
+                    .map(|group| group.start_char_code())
+                    .collect(),
+            ),
+        };
+        let notdef = notdef
+            .into_iter()
+            .filter_map(char::from_u32)
+            // `U+FFFF` only ends the segments of a format 4 subtable.
+            .filter(|&ch| ch != '\u{FFFF}' && self.map(ch) == Some(GlyphId::NOTDEF))
+            .map(|ch| (ch, GlyphId::NOTDEF));
+        mapped
+            .into_iter()
+            .filter_map(|(code, gid)| Some((char::from_u32(code)?, gid)))
+            .chain(notdef)
+            .collect()
+    }
+}
+
+/// Characters of a glyph for messages, like `U+0041 U+0391`.
+fn display_chars(chars: &[char]) -> String {
+    chars
+        .iter()
+        .map(|&ch| format!("U+{:04X}", ch as u32))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Family name of the font from its `name` table.
+fn font_name(font: &FontRef) -> Option<String> {
+    let name = font.name().ok()?;
+    let records = name.name_record();
+    [NameId::TYPOGRAPHIC_FAMILY_NAME, NameId::FAMILY_NAME]
+        .into_iter()
+        .find_map(|id| {
+            let record = records.iter().find(|record| record.name_id() == id)?;
+            Some(record.string(name.string_data()).ok()?.to_string())
+        })
+}
+
+/// Palette color of a pixel with `coverage`, `None` if it is left empty.
+fn quantize(coverage: f64, options: &RasterizeOptions) -> Option<GlyphPaletteColor> {
+    if !options.grayscale {
+        return (coverage >= options.threshold as f64 && coverage > 0.)
+            .then_some(GlyphPaletteColor::Zero);
+    }
+    match (coverage * 4.).round() as u8 {
+        0 => None,
+        1 => Some(GlyphPaletteColor::Fifteen),
+        2 => Some(GlyphPaletteColor::Seven),
+        3 => Some(GlyphPaletteColor::Eight),
+        _ => Some(GlyphPaletteColor::Zero),
+    }
+}
+
+/// Coverage of every pixel of `bounds`, whose edges are on whole pixels,
+/// by `path` under the nonzero winding rule.
+fn rasterize(path: &BezPath, bounds: Rect) -> Vec<Vec<f64>> {
+    let width = bounds.width() as usize;
+    let height = bounds.height() as usize;
+    let mut coverage = vec![vec![0.; width]; height];
+
+    // edges as (x0, y0, x1, y1) relative to `bounds`, every subpath closed.
+    let mut edges = Vec::new();
+    let (mut start, mut last) = (Point::ZERO, Point::ZERO);
+    let origin = bounds.origin().to_vec2();
+    kurbo::flatten(path, FLATTEN_TOLERANCE, |el| match el {
+        PathEl::MoveTo(p) => {
+            edges.push((last, start));
+            start = p - origin;
+            last = start;
+        }
+        PathEl::LineTo(p) => {
+            edges.push((last, p - origin));
+            last = p - origin;
+        }
+        PathEl::ClosePath => {
+            edges.push((last, start));
+            last = start;
+        }
+        _ => unreachable!("flattened paths only have lines"),
+    });
+    edges.push((last, start));
+    edges.retain(|(p0, p1)| p0.y != p1.y);
+
+    let mut crossings = Vec::new();
+    for (row, coverage) in coverage.iter_mut().enumerate() {
+        for sample in 0..SUBSAMPLES {
+            let y = row as f64 + (sample as f64 + 0.5) / SUBSAMPLES as f64;
+            crossings.clear();
+            for (p0, p1) in &edges {
+                let (top, bottom) = if p0.y < p1.y { (p0, p1) } else { (p1, p0) };
+                if top.y <= y && y < bottom.y {
+                    let x = p0.x + (y - p0.y) * (p1.x - p0.x) / (p1.y - p0.y);
+                    crossings.push((x, if p0.y < p1.y { 1 } else { -1 }));
+                }
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut winding = 0;
+            let mut span_start = 0.;
+            for &(x, direction) in &crossings {
+                if winding == 0 {
+                    span_start = x;
+                }
+                winding += direction;
+                if winding == 0 {
+                    add_span(coverage, span_start, x);
+                }
+            }
+        }
+    }
+    coverage
+}
+
+/// Adds a span of one sub-scanline from `x0` to `x1` to the coverage of a row.
+fn add_span(row: &mut [f64], x0: f64, x1: f64) {
+    let weight = 1. / SUBSAMPLES as f64;
+    let (x0, x1) = (x0.max(0.), x1.min(row.len() as f64));
+    if x0 >= x1 {
+        return;
+    }
+    let (first, last) = (x0.floor() as usize, (x1.ceil() as usize).min(row.len()) - 1);
+    if first == last {
+        row[first] += (x1 - x0) * weight;
+        return;
+    }
+    row[first] += (first as f64 + 1. - x0) * weight;
+    for pixel in &mut row[first + 1..last] {
+        *pixel += weight;
+    }
+    row[last] += (x1 - last as f64) * weight;
+}
+
+/// Outlines of an OpenType font, in font units.
+enum Outlines<'a> {
+    Glyf {
+        loca: Loca<'a>,
+        glyf: Glyf<'a>,
+    },
+    Cff {
+        charstrings: Index<'a>,
+        global_subrs: Index<'a>,
+        /// Local subroutines of every font dict, only one unless the font is CID-keyed.
+        subrs: Vec<Option<Index<'a>>>,
+        fd_select: Option<FdSelect<'a>>,
+    },
+}
+
+impl<'a> Outlines<'a> {
+    fn new(font: &FontRef<'a>, path: &Path) -> Result<Outlines<'a>, OutlineImportError> {
+        if let (Ok(loca), Ok(glyf)) = (font.loca(None), font.glyf()) {
+            return Ok(Outlines::Glyf { loca, glyf });
+        }
+        let cff = font.cff().map_err(|_| NoOutlinesSnafu { path }.build())?;
+        Outlines::cff(
+            cff.offset_data().as_bytes(),
+            cff.top_dicts().get(0),
+            cff.global_subrs(),
+        )
+        .map_err(|e| {
+            ReadCffSnafu {
+                path,
+                message: e.to_string(),
+            }
+            .build()
+        })
+    }
+
+    fn cff(
+        data: &'a [u8],
+        top_dict: Result<&'a [u8], postscript::Error>,
+        global_subrs: impl Into<Index<'a>>,
+    ) -> Result<Outlines<'a>, postscript::Error> {
+        let mut charstrings = None;
+        let mut private = None;
+        let mut fd_array = None;
+        let mut fd_select = None;
+        for entry in dict::entries(top_dict?, None) {
+            match entry? {
+                dict::Entry::CharstringsOffset(offset) => charstrings = Some(offset),
+                dict::Entry::PrivateDictRange(range) => private = Some(range),
+                dict::Entry::FdArrayOffset(offset) => fd_array = Some(offset),
+                dict::Entry::FdSelectOffset(offset) => fd_select = Some(offset),
+                _ => {}
+            }
+        }
+        let tail = |offset: usize| data.get(offset..).ok_or(ReadError::OutOfBounds);
+        let charstrings = Index::new(
+            tail(charstrings.ok_or(ReadError::MalformedData(
+                "expect CharStrings in the top dict",
+            ))?)?,
+            false,
+        )?;
+        let subrs = match (fd_array, fd_select) {
+            (Some(fd_array), Some(_)) => {
+                let fonts = Index::new(tail(fd_array)?, false)?;
+                (0..fonts.count() as usize)
+                    .map(|idx| {
+                        let private =
+                            dict::entries(fonts.get(idx)?, None).find_map(|entry| match entry {
+                                Ok(dict::Entry::PrivateDictRange(range)) => Some(range),
+                                _ => None,
+                            });
+                        private.map_or(Ok(None), |range| local_subrs(data, range))
+                    })
+                    .collect::<Result<_, postscript::Error>>()?
+            }
+            _ => vec![private.map_or(Ok(None), |range| local_subrs(data, range))?],
+        };
+        let fd_select = match (fd_array, fd_select) {
+            (Some(_), Some(offset)) => Some(FdSelect::read(FontData::new(tail(offset)?))?),
+            _ => None,
+        };
+        Ok(Outlines::Cff {
+            charstrings,
+            global_subrs: global_subrs.into(),
+            subrs,
+            fd_select,
+        })
+    }
+
+    /// Outline of the glyph `gid`, noting anything which is left out into `notes`.
+    fn path(&self, gid: GlyphId, depth: usize, notes: &mut Vec<String>) -> Result<BezPath, String> {
+        match self {
+            Outlines::Glyf { loca, glyf } => {
+                let glyph = loca.get_glyf(gid, glyf).map_err(|e| e.to_string())?;
+                match glyph {
+                    None => Ok(BezPath::new()),
+                    Some(GlyfGlyph::Simple(glyph)) => {
+                        let points: Vec<_> = glyph.points().collect();
+                        let mut path = BezPath::new();
+                        let mut start = 0;
+                        for end in glyph.end_pts_of_contours() {
+                            let end = end.get() as usize + 1;
+                            let contour = points.get(start..end).ok_or("contour out of bounds")?;
+                            quadratic_contour(&mut path, contour);
+                            start = end;
+                        }
+                        Ok(path)
+                    }
+                    Some(GlyfGlyph::Composite(glyph)) => {
+                        if depth >= MAX_COMPONENT_DEPTH {
+                            return Err("components are nested too deep".to_owned());
+                        }
+                        let mut path = BezPath::new();
+                        for component in glyph.components() {
+                            let (dx, dy) = match component.anchor {
+                                Anchor::Offset { x, y } => (x as f64, y as f64),
+                                Anchor::Point { .. } => {
+                                    notes.push(format!(
+                                        "component {} is placed by points, which is not supported, so it is not moved",
+                                        component.glyph
+                                    ));
+                                    (0., 0.)
+                                }
+                            };
+                            let t = component.transform;
+                            let transform = Affine::new([
+                                t.xx.to_f32() as f64,
+                                t.yx.to_f32() as f64,
+                                t.xy.to_f32() as f64,
+                                t.yy.to_f32() as f64,
+                                dx,
+                                dy,
+                            ]);
+                            let child = self.path(component.glyph.into(), depth + 1, notes)?;
+                            path.extend(transform * child);
+                        }
+                        Ok(path)
+                    }
+                }
+            }
+            Outlines::Cff {
+                charstrings,
+                global_subrs,
+                subrs,
+                fd_select,
+            } => {
+                let font_index = match fd_select {
+                    Some(fd_select) => fd_select.font_index(gid).ok_or("glyph has no font dict")?,
+                    None => 0,
+                } as usize;
+                let charstring = charstrings
+                    .get(gid.to_u32() as usize)
+                    .map_err(|e| e.to_string())?;
+                let mut sink = PathSink(BezPath::new());
+                postscript::charstring::evaluate(
+                    charstring,
+                    global_subrs.clone(),
+                    subrs.get(font_index).cloned().flatten(),
+                    None,
+                    &mut sink,
+                )
+                .map_err(|e| e.to_string())?;
+                Ok(sink.0)
+            }
+        }
+    }
+}
+
+/// Local subroutines of the Private DICT at `range` of the `CFF ` table.
+fn local_subrs(data: &[u8], range: Range<usize>) -> Result<Option<Index<'_>>, postscript::Error> {
+    let private = data.get(range.clone()).ok_or(ReadError::OutOfBounds)?;
+    for entry in dict::entries(private, None) {
+        if let dict::Entry::SubrsOffset(offset) = entry? {
+            let subrs = data
+                .get(range.start + offset..)
+                .ok_or(ReadError::OutOfBounds)?;
+            return Ok(Some(Index::new(subrs, false)?));
+        }
+    }
+    Ok(None)
+}
+
+/// Appends a closed TrueType contour, where two off-curve points imply an on-curve one
+/// between them.
+fn quadratic_contour(path: &mut BezPath, points: &[CurvePoint]) {
+    let Some(first) = points.first() else {
+        return;
+    };
+    let point = |p: &CurvePoint| Point::new(p.x as f64, p.y as f64);
+    // start on an on-curve point, or between the first two off-curve points if there is none.
+    let start_idx = points.iter().position(|p| p.on_curve);
+    let start = match start_idx {
+        Some(idx) => point(&points[idx]),
+        None => point(first).midpoint(point(&points[1 % points.len()])),
+    };
+    let rotated = points
+        .iter()
+        .cycle()
+        .skip(start_idx.map_or(0, |idx| idx + 1))
+        .take(points.len() - start_idx.map_or(0, |_| 1));
+    path.move_to(start);
+    let mut control: Option<Point> = None;
+    for p in rotated {
+        match (p.on_curve, control) {
+            (true, None) => path.line_to(point(p)),
+            (true, Some(c)) => {
+                path.quad_to(c, point(p));
+                control = None;
+            }
+            (false, None) => control = Some(point(p)),
+            (false, Some(c)) => {
+                path.quad_to(c, c.midpoint(point(p)));
+                control = Some(point(p));
+            }
+        }
+    }
+    match control {
+        Some(c) => path.quad_to(c, start),
+        None => path.line_to(start),
+    }
+    path.close_path();
+}
+
+/// Collects the outline of a charstring.
+struct PathSink(BezPath);
+
+impl CommandSink for PathSink {
+    fn move_to(&mut self, x: Fixed, y: Fixed) {
+        self.0.move_to((x.to_f64(), y.to_f64()));
+    }
+
+    fn line_to(&mut self, x: Fixed, y: Fixed) {
+        self.0.line_to((x.to_f64(), y.to_f64()));
+    }
+
+    fn curve_to(&mut self, cx0: Fixed, cy0: Fixed, cx1: Fixed, cy1: Fixed, x: Fixed, y: Fixed) {
+        self.0.curve_to(
+            (cx0.to_f64(), cy0.to_f64()),
+            (cx1.to_f64(), cy1.to_f64()),
+            (x.to_f64(), y.to_f64()),
+        );
+    }
+
+    fn close(&mut self) {
+        self.0.close_path();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use write_fonts::{
+        from_obj::ToOwnedTable,
+        tables::{
+            cmap::{Cmap as CmapTable, EncodingRecord},
+            glyf::{GlyfLocaBuilder, SimpleGlyph},
+            head::Head,
+        },
+        FontBuilder,
+    };
+
+    use crate::{
+        backend::{FontBackend, FontStyle, OpentypeTtfBackend, OpentypeTtfOptions, SilentReporter},
+        font::test_font,
+    };
+
+    use super::*;
+
+    /// A CFF-flavored OpenType font shipped with the GUI.
+    const PRETENDARD: &[u8] =
+        include_bytes!("../../../gui/assets/fonts/pretendard/static/Pretendard-Medium.otf");
+
+    fn rows(glyph: &GlyphDefinition) -> Vec<String> {
+        let value = glyph.value.as_ref().expect("glyph has a bitmap");
+        value
+            .data
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|p| if p.is_some() { '@' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// TrueType font traced by the studio from the YAFF `source`, in cells 5 pixels high
+    /// with 1 of them below the baseline. A pixel is 13 units wide.
+    fn traced(source: &str) -> Vec<u8> {
+        let font = test_font(source, 5, 1, FontStyle::default());
+        let backend = OpentypeTtfBackend::new(OpentypeTtfOptions::default());
+        let artifacts = backend.build(&font, &SilentReporter).unwrap();
+        artifacts[0].bytes.clone()
+    }
+
+    /// `ttf` with the tables added by `add` in place of its own.
+    fn with_tables(ttf: &[u8], add: impl FnOnce(&FontRef, &mut FontBuilder)) -> Vec<u8> {
+        let font = FontRef::new(ttf).unwrap();
+        let mut builder = FontBuilder::new();
+        add(&font, &mut builder);
+        builder.copy_missing_tables(font);
+        builder.build()
+    }
+
+    /// `ttf` with glyphs drawn by `outlines` in font units, in glyph ID order.
+    fn with_outlines(ttf: &[u8], outlines: &[BezPath]) -> Vec<u8> {
+        with_tables(ttf, |font, builder| {
+            let mut glyf_loca = GlyfLocaBuilder::new();
+            for outline in outlines {
+                glyf_loca
+                    .add_glyph(&SimpleGlyph::from_bezpath(outline).unwrap())
+                    .unwrap();
+            }
+            let (glyf, loca, format) = glyf_loca.build();
+            let mut head: Head = font.head().unwrap().to_owned_table();
+            head.index_to_loc_format = format as i16;
+            builder.add_table(&glyf).unwrap();
+            builder.add_table(&loca).unwrap();
+            builder.add_table(&head).unwrap();
+        })
+    }
+
+    /// `ttf` mapping characters by `unicode`, and bytes of Mac Roman by `mac_roman`.
+    fn with_cmap(ttf: &[u8], unicode: &[(char, u32)], mac_roman: &[(char, u32)]) -> Vec<u8> {
+        let cmap = |mappings: &[(char, u32)]| {
+            CmapTable::from_mappings(mappings.iter().map(|&(ch, gid)| (ch, GlyphId::new(gid))))
+                .unwrap()
+        };
+        let mut records = Vec::new();
+        if !unicode.is_empty() {
+            records.extend(cmap(unicode).encoding_records);
+        }
+        if !mac_roman.is_empty() {
+            let bmp = cmap(mac_roman).encoding_records.remove(0).subtable;
+            records.push(EncodingRecord::new(
+                PlatformId::Macintosh,
+                0,
+                (*bmp).clone(),
+            ));
+        }
+        records.sort_by_key(|record| (record.platform_id, record.encoding_id));
+        with_tables(ttf, |_, builder| {
+            builder.add_table(&CmapTable::new(records)).unwrap();
+        })
+    }
+
+    #[test]
+    fn rasterizes_cff_outlines() {
+        let options = RasterizeOptions {
+            ppem: 14,
+            chars: BTreeSet::from(['R', '\u{10FFFD}']),
+            ..Default::default()
+        };
+        let (font, diagnostics) =
+            rasterize_font(PRETENDARD, "Pretendard-Medium.otf", &options).unwrap();
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        assert_eq!(font.name, "Pretendard 14px");
+        assert_eq!((font.weight, font.height, font.descent), (500, 16, 3));
+        // characters missing from the font are skipped.
+        assert_eq!(font.glyphs.len(), 1);
+        assert!(matches!(
+            font.glyphs[0].labels[..],
+            [GlyphLabel::CharacterSingle('R')]
+        ));
+        assert_eq!(
+            rows(&font.glyphs[0]),
+            [
+                ".........",
+                ".........",
+                ".........",
+                ".@@@@@...",
+                ".@@..@@@.",
+                ".@@...@@.",
+                ".@@...@@.",
+                ".@@...@@.",
+                ".@@@@@@..",
+                ".@@..@...",
+                ".@@..@@..",
+                ".@@...@@.",
+                ".@@...@@.",
+                ".........",
+                ".........",
+                ".........",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        let options = RasterizeOptions {
+            ppem: 0,
+            ..Default::default()
+        };
+        let result = rasterize_font(PRETENDARD, "Pretendard-Medium.otf", &options);
+        assert!(matches!(result, Err(OutlineImportError::PpemZero)));
+        let options = RasterizeOptions {
+            threshold: 1.5,
+            ..Default::default()
+        };
+        let result = rasterize_font(PRETENDARD, "Pretendard-Medium.otf", &options);
+        assert!(matches!(
+            result,
+            Err(OutlineImportError::InvalidThreshold { .. })
+        ));
+    }
+
+    #[test]
+    fn covers_pixels_by_area() {
+        // a pixel-sized square centered on the corner of four pixels.
+        let square = Rect::new(0.5, 0.5, 1.5, 1.5).to_path(0.);
+        let coverage = rasterize(&square, Rect::new(0., 0., 2., 2.));
+        assert_eq!(coverage, [[0.25, 0.25], [0.25, 0.25]]);
+    }
+
+    #[test]
+    fn quantizes_coverage() {
+        let bilevel = RasterizeOptions::default();
+        assert_eq!(quantize(0.49, &bilevel), None);
+        assert_eq!(quantize(0.5, &bilevel), Some(GlyphPaletteColor::Zero));
+
+        let grayscale = RasterizeOptions {
+            grayscale: true,
+            ..Default::default()
+        };
+        let levels = [0., 0.2, 0.5, 0.7, 1.].map(|coverage| quantize(coverage, &grayscale));
+        assert_eq!(
+            levels,
+            [
+                None,
+                Some(GlyphPaletteColor::Fifteen),
+                Some(GlyphPaletteColor::Seven),
+                Some(GlyphPaletteColor::Eight),
+                Some(GlyphPaletteColor::Zero),
+            ]
+        );
+    }
+
+    #[test]
+    fn devectorizes_traced_fonts() {
+        // no ink reaches the top row or the descender, which still belong to the cell.
+        let ttf = traced(
+            "'O':\n  ....\n  @@@@\n  @..@\n  @@@@\n  ....\n\n\
+             '/':\n  ...@\n  ..@.\n  .@..\n  @...\n  ....\n\n\
+             ' ':\n  ..\n  ..\n  ..\n  ..\n  ..\n",
+        );
+
+        let (imported, diagnostics) = devectorize_font(&ttf, "Test-Regular.ttf", None).unwrap();
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        assert_eq!((imported.name.as_str(), imported.weight), ("Test", 400));
+        assert_eq!((imported.height, imported.descent), (5, 1));
+        let labels: Vec<_> = imported
+            .glyphs
+            .iter()
+            .map(|glyph| glyph.labels[0].to_string())
+            .collect();
+        assert_eq!(labels, ["' '", "'/'", "'O'"]);
+        assert_eq!(rows(&imported.glyphs[0]), [".."; 5]);
+        assert_eq!(
+            rows(&imported.glyphs[1]),
+            ["...@", "..@.", ".@..", "@...", "...."]
+        );
+        assert_eq!(
+            rows(&imported.glyphs[2]),
+            ["....", "@@@@", "@..@", "@@@@", "...."]
+        );
+
+        let result = devectorize_font(&ttf, "Test-Regular.ttf", Some(0));
+        assert!(matches!(result, Err(OutlineImportError::GridZero)));
+    }
+
+    #[test]
+    fn finds_the_grid_past_curved_glyphs() {
+        let ttf = traced("'O':\n  ....\n  ....\n  ....\n  ....\n  ....\n\n'\u{a9}':\n  ....\n");
+        let square = Rect::new(0., 0., 39., 39.).to_path(0.);
+        // a round logo on whole units which are not on the grid of 13.
+        let mut round = BezPath::new();
+        round.move_to((11., 20.));
+        round.quad_to((11., 35.), (26., 35.));
+        round.quad_to((41., 35.), (41., 20.));
+        round.quad_to((41., 5.), (26., 5.));
+        round.quad_to((11., 5.), (11., 20.));
+        round.close_path();
+        let ttf = with_outlines(&ttf, &[square, round]);
+
+        let (imported, diagnostics) = devectorize_font(&ttf, "Test-Regular.ttf", None).unwrap();
+        assert_eq!((imported.height, imported.descent), (5, 1));
+        assert_eq!(
+            rows(&imported.glyphs[0]),
+            ["....", "@@@.", "@@@.", "@@@.", "...."]
+        );
+        let [curved] = &diagnostics[..] else {
+            panic!("expect a single warning but got {diagnostics:?}");
+        };
+        assert!(curved.message.starts_with("U+00A9 is not made of squares"));
+    }
+
+    #[test]
+    fn reads_only_the_unicode_cmap() {
+        let base = traced("'X':\n  @\n\n'A':\n  @@\n\n'B':\n  @@@\n");
+        // glyph 0 is `X`, which `C` is mapped to as the glyph shown for missing characters.
+        let unicode = [('X', 0), ('A', 1), ('B', 2), ('C', 0)];
+        let ttf = with_cmap(&base, &unicode, &[('A', 1), ('\u{80}', 2)]);
+
+        let (imported, _) = devectorize_font(&ttf, "Test-Regular.ttf", None).unwrap();
+        let labels: Vec<_> = imported
+            .glyphs
+            .iter()
+            .map(|glyph| {
+                glyph
+                    .labels
+                    .iter()
+                    .map(|label| label.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        assert_eq!(labels, ["'C' 'X'", "'A'", "'B'"]);
+
+        // unless the font is rendered, where glyph 0 stands for missing characters.
+
+        let options = RasterizeOptions {
+            ppem: 5,
+            chars: BTreeSet::from(['A', 'C', 'X', '\u{80}']),
+            ..Default::default()
+        };
+        let (imported, _) = rasterize_font(&ttf, "Test-Regular.ttf", &options).unwrap();
+        assert_eq!(imported.glyphs.len(), 1);
+        assert!(matches!(
+            imported.glyphs[0].labels[..],
+            [GlyphLabel::CharacterSingle('A')]
+        ));
+
+        let mac_roman_only = with_cmap(&base, &[], &[('A', 1)]);
+        let result = rasterize_font(&mac_roman_only, "Test-Regular.ttf", &options);
+        assert!(matches!(
+            result,
+            Err(OutlineImportError::NoUnicodeCmap { .. })
+        ));
+        let result = devectorize_font(&mac_roman_only, "Test-Regular.ttf", None);
+        assert!(matches!(
+            result,
+            Err(OutlineImportError::NoUnicodeCmap { .. })
+        ));
+    }
+}
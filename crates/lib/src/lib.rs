@@ -1,9 +1,21 @@
 mod backend;
+pub mod coverage;
+mod derived;
+mod diagnostic;
+pub mod font;
 mod glyph;
+pub mod import;
 mod project;
 mod source_file;
 mod workspace;
 
 pub use backend::*;
-pub use project::{Project, ProjectLoadError};
-pub use workspace::{Workspace, WorkspaceLoadError};
+pub use derived::{DeriveError, DeriveExpr, DeriveExprParseError, GlyphTransform};
+pub use diagnostic::{Diagnostic, DiagnosticCode, Severity, Span};
+pub use glyph::{BitmapMatrix, DiagonalRule, OutlineStyle, PathfinderError, PixelShape};
+pub use project::{
+    BuildSettings, BuildTarget, GlyphOrigin, GlyphProvenance, OutlineSettings, Project,
+    ProjectManifest, ProjectSettings, SourceDateEpochError, SynthesisSettings,
+};
+pub use source_file::{SourceFile, SourceFileLoadError};
+pub use workspace::{BuildOverrides, Workspace, WorkspaceManifest, WorkspaceSection};
@@ -1,48 +1,708 @@
-use std::{fs, io, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fmt, fs,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
 
-use serde::Deserialize;
+use jiff::{civil::Date, tz::TimeZone, Timestamp};
+use rayon::prelude::*;
+use serde::{Deserialize, Deserializer};
 use snafu::prelude::*;
+use yaff::{GlyphDefinition, SemanticGlyphLabel};
 
-use crate::source_file::{SourceFile, SourceFileLoadError};
+use crate::{
+    backend::{FileNameTemplate, FontStyle},
+    derived::{derive_glyphs, DeriveExpr},
+    diagnostic::{Diagnostic, DiagnosticCode, LineIndex, Span},
+    glyph::{DiagonalRule, PixelShape},
+    source_file::SourceFile,
+    workspace::BuildOverrides,
+};
 
 pub struct Project {
+    pub path: PathBuf,
     pub manifest: ProjectManifest,
+    /// Source files in load order, which is sorted by path.
     pub files: Vec<SourceFile>,
+    /// Glyphs made by `[derive]` rules, evaluated when the project is loaded.
+    pub derived: Vec<GlyphDefinition>,
+    /// Build settings imposed by the workspace, empty if the project is loaded alone.
+    pub overrides: BuildOverrides,
+    glyph_index: HashMap<char, GlyphLocation>,
 }
 
-#[derive(Debug, Snafu)]
-pub enum ProjectLoadError {
-    #[snafu(transparent)]
-    Io { source: io::Error },
-    #[snafu(transparent)]
-    Walkdir { source: walkdir::Error },
-    #[snafu(transparent)]
-    De { source: toml::de::Error },
-    #[snafu(transparent)]
-    SourceFile { source: SourceFileLoadError },
+enum GlyphLocation {
+    Source {
+        file: usize,
+        label: SemanticGlyphLabel,
+    },
+    Derived {
+        index: usize,
+    },
+}
+
+/// A glyph together with where it comes from.
+pub struct GlyphProvenance<'a> {
+    pub glyph: &'a GlyphDefinition,
+    pub origin: GlyphOrigin<'a>,
+}
+
+pub enum GlyphOrigin<'a> {
+    /// Drawn in a source file.
+    Source {
+        file: &'a SourceFile,
+        span: Option<Span>,
+    },
+    /// Made by a `[derive]` rule of the manifest.
+    Derived { rule: &'a DeriveExpr },
 }
 
 impl Project {
-    pub fn load(path: impl AsRef<Path>) -> Result<Project, ProjectLoadError> {
+    /// Loads the project at `path` as much as possible.
+    ///
+    /// Broken files are reported as diagnostics and skipped, so the returned project
+    /// may be partial if any diagnostic is an error.
+    /// A broken `project.toml` falls back to the default manifest,
+    /// while a broken `[derive]` rule is dropped alone.
+    pub fn load(path: impl AsRef<Path>) -> (Project, Vec<Diagnostic>) {
         let path = path.as_ref();
-        let manifest: ProjectManifest =
-            toml::from_str(&fs::read_to_string(path.join("project.toml"))?)?;
+        let mut diagnostics = Vec::new();
+
+        let manifest_path = path.join("project.toml");
+        let mut rule_spans = HashMap::new();
+        let manifest = match fs::read_to_string(&manifest_path) {
+            Ok(content) => {
+                let mut manifest = toml::from_str(&content).unwrap_or_else(|e: toml::de::Error| {
+                    diagnostics.push(Diagnostic::error(
+                        DiagnosticCode::InvalidProjectManifest,
+                        &manifest_path,
+                        e.span().map(|span| Span::new(&content, span)),
+                        e.message(),
+                    ));
+                    ProjectManifest::default()
+                });
+                // a syntax error is reported above already, and leaves no rules to read.
+                let section: DeriveSection = toml::from_str(&content).unwrap_or_default();
+                let index = LineIndex::new(&content);
+                for (key, value) in section.derive {
+                    let span = index.span(key.span());
+                    let rule = match key.get_ref().parse::<char>() {
+                        Ok(ch) => match value.into_inner() {
+                            toml::Value::String(expr) => expr
+                                .parse::<DeriveExpr>()
+                                .map(|expr| (ch, expr))
+                                .map_err(|e| format!("invalid rule for {ch:?}: {e}")),
+                            other => Err(format!(
+                                "expect a rule for {ch:?} as a string but got {}",
+                                other.type_str()
+                            )),
+                        },
+                        Err(_) => Err(format!(
+                            "expect a single character to derive but got `{}`",
+                            key.get_ref()
+                        )),
+                    };
+                    match rule {
+                        Ok((ch, expr)) => {
+                            manifest.derive.insert(ch, expr);
+                            rule_spans.insert(ch, span);
+                        }
+                        Err(message) => diagnostics.push(Diagnostic::error(
+                            DiagnosticCode::InvalidDeriveRule,
+                            &manifest_path,
+                            Some(span),
+                            message,
+                        )),
+                    }
+                }
+                manifest
+            }
+            Err(e) => {
+                diagnostics.push(Diagnostic::error(
+                    DiagnosticCode::Io,
+                    &manifest_path,
+                    None,
+                    e,
+                ));
+                ProjectManifest::default()
+            }
+        };
 
-        let files = walkdir::WalkDir::new(path.join("src"))
+        // Walking is cheap and gives the load order, while reading and parsing are done in
+        // parallel. Results are kept in walk order, so the outcome never depends on scheduling.
+        let mut entries = Vec::new();
+        for entry in walkdir::WalkDir::new(path.join("src"))
             .follow_links(true)
-            .into_iter()
-            .collect::<Result<Vec<_>, _>>()?
-            .into_iter()
-            .filter_map(|entry| {
-                (entry.file_type().is_file()
-                    && entry.file_name().as_encoded_bytes().ends_with(b".yaff"))
-                .then(|| SourceFile::load(entry.path()))
+            .sort_by_file_name()
+        {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    entries.push(Err(Diagnostic::error(
+                        DiagnosticCode::Io,
+                        e.path().unwrap_or(path),
+                        None,
+                        &e,
+                    )));
+                    continue;
+                }
+            };
+            if !(entry.file_type().is_file()
+                && entry.file_name().as_encoded_bytes().ends_with(b".yaff"))
+            {
+                continue;
+            }
+            entries.push(Ok(entry.into_path()));
+        }
+        let loaded: Vec<_> = entries
+            .into_par_iter()
+            .map(|entry| {
+                entry.and_then(|file_path| {
+                    SourceFile::load(path, file_path, 0).map_err(|e| e.to_diagnostic())
+                })
+            })
+            .collect();
+        let mut files = Vec::new();
+        for result in loaded {
+            match result {
+                Ok(mut file) => {
+                    file.order = files.len();
+                    files.push(file);
+                }
+                Err(diagnostic) => diagnostics.push(diagnostic),
+            }
+        }
+
+        let (derived, errors) = derive_glyphs(&manifest.derive, &files);
+        diagnostics.extend(errors.into_iter().map(|e| {
+            let span = rule_spans.get(&e.target()).cloned();
+            Diagnostic::error(DiagnosticCode::InvalidDeriveRule, &manifest_path, span, e)
+        }));
+
+        let mut glyph_index = HashMap::new();
+        for (idx, file) in files.iter().enumerate() {
+            for glyph in file.document.list_glyph() {
+                for label in glyph.labels.iter().flat_map(|label| label.to_semantic()) {
+                    let Some(ch) = resolve_char(&label) else {
+                        continue;
+                    };
+                    let Some(&GlyphLocation::Source { file: first, .. }) = glyph_index.get(&ch)
+                    else {
+                        glyph_index.insert(ch, GlyphLocation::Source { file: idx, label });
+                        continue;
+                    };
+                    if first == idx {
+                        continue;
+                    }
+                    diagnostics.push(Diagnostic::warning(
+                        DiagnosticCode::DuplicateGlyph,
+                        &file.path,
+                        file.glyph_span(&label),
+                        format!(
+                            "{label} is already defined in {}, this definition is ignored",
+                            files[first].relative_path.to_string_lossy()
+                        ),
+                    ));
+                }
+            }
+        }
+        for (index, glyph) in derived.iter().enumerate() {
+            for label in glyph.labels.iter().flat_map(|label| label.to_semantic()) {
+                if let Some(ch) = resolve_char(&label) {
+                    glyph_index
+                        .entry(ch)
+                        .or_insert(GlyphLocation::Derived { index });
+                }
+            }
+        }
+
+        (
+            Project {
+                path: path.to_owned(),
+                manifest,
+                files,
+                derived,
+                overrides: BuildOverrides::default(),
+                glyph_index,
+            },
+            diagnostics,
+        )
+    }
+
+    /// Finds the glyph for `ch` and where it is defined.
+    ///
+    /// If several source files define the same character, the first one in load order wins.
+    pub fn find_glyph(&self, ch: char) -> Option<GlyphProvenance<'_>> {
+        match self.glyph_index.get(&ch)? {
+            GlyphLocation::Source { file, label } => {
+                let file = &self.files[*file];
+                Some(GlyphProvenance {
+                    glyph: file.document.get_glyph(label)?,
+                    origin: GlyphOrigin::Source {
+                        file,
+                        span: file.glyph_span(label),
+                    },
+                })
+            }
+            GlyphLocation::Derived { index } => Some(GlyphProvenance {
+                glyph: &self.derived[*index],
+                origin: GlyphOrigin::Derived {
+                    rule: self.manifest.derive.get(&ch)?,
+                },
+            }),
+        }
+    }
+
+    /// Lists every glyph of the project, both drawn and derived.
+    pub fn list_glyph(&self) -> impl Iterator<Item = &GlyphDefinition> {
+        self.files
+            .iter()
+            .flat_map(|file| file.document.list_glyph())
+            .chain(&self.derived)
+    }
+
+    /// Targets to build, with `formats` of the workspace overrides applied.
+    ///
+    /// An overriding format keeps the targets of the project in that format,
+    /// or gets a target with default options if the project has none.
+    pub fn build_targets(&self) -> Vec<BuildTarget> {
+        let targets = &self.manifest.build.targets;
+        let default_targets = [BuildTarget::new("ttf")];
+        let targets = if targets.is_empty() {
+            &default_targets[..]
+        } else {
+            &targets[..]
+        };
+        let Some(formats) = &self.overrides.formats else {
+            return targets.to_vec();
+        };
+        formats
+            .iter()
+            .flat_map(|format| {
+                let matched: Vec<_> = targets
+                    .iter()
+                    .filter(|target| &target.format == format)
+                    .cloned()
+                    .collect();
+                if matched.is_empty() {
+                    vec![BuildTarget::new(format)]
+                } else {
+                    matched
+                }
             })
-            .collect::<Result<_, _>>()?;
+            .collect()
+    }
+}
+
+/// Resolves a label into a character the same way backends do:
+/// a single character as-is and a tag by its Unicode name.
+pub(crate) fn resolve_char(label: &SemanticGlyphLabel) -> Option<char> {
+    match label {
+        SemanticGlyphLabel::CharSequence(vec) => match &vec[..] {
+            &[ch] => Some(ch),
+            _ => None,
+        },
+        SemanticGlyphLabel::Tag(tag) => unicode_names2::character(tag),
+    }
+}
 
-        Ok(Project { manifest, files })
+impl fmt::Display for GlyphProvenance<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.origin {
+            GlyphOrigin::Source { file, span } => {
+                write!(f, "{}", file.relative_path.to_string_lossy())?;
+                if let Some(span) = span {
+                    write!(f, ":{}:{}", span.line, span.column)?;
+                }
+                Ok(())
+            }
+            GlyphOrigin::Derived { rule } => write!(f, "derived by `{rule}`"),
+        }
     }
 }
 
-#[derive(Deserialize)]
-pub struct ProjectManifest {}
+#[derive(Default, Deserialize)]
+pub struct ProjectManifest {
+    #[serde(default)]
+    pub project: ProjectSettings,
+    /// Rules of the `[derive]` section, which [`Project::load`] reads apart from the rest
+    /// so that a broken rule is dropped alone.
+    #[serde(skip)]
+    pub derive: BTreeMap<char, DeriveExpr>,
+    #[serde(default)]
+    pub outline: OutlineSettings,
+    #[serde(default)]
+    pub synthesis: SynthesisSettings,
+    #[serde(default)]
+    pub build: BuildSettings,
+}
+
+/// The `[derive]` section as written, keyed by the character to derive.
+#[derive(Default, Deserialize)]
+struct DeriveSection {
+    #[serde(default)]
+    derive: BTreeMap<toml::Spanned<String>, toml::Spanned<toml::Value>>,
+}
+
+/// Names and font-wide metrics, from the `[project]` section.
+///
+/// ```toml
+/// [project]
+/// name = "Bitkodi"
+/// version = "1.2"
+/// height = 8
+/// ascender = 5
+/// descender = 1
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ProjectSettings {
+    /// Family name, the name of the project directory if omitted.
+    pub name: Option<String>,
+    /// Weight class of the glyphs as drawn, 400 by default.
+    pub weight: Option<u16>,
+    /// Font version as `major.minor`, `1.0` by default.
+    pub version: Option<String>,
+    /// Copyright notice written into every font.
+    pub copyright: Option<String>,
+    /// Height of the cell in pixels, the tallest glyph if omitted.
+    pub height: Option<u16>,
+    /// Rows of the cell above the baseline reserved for ascenders, `height - descender` if omitted.
+    pub ascender: Option<u16>,
+    /// Rows of the cell below the baseline.
+    #[serde(default)]
+    pub descender: u16,
+}
+
+/// How bitmaps are turned into outlines, from the `[outline]` section.
+///
+/// ```toml
+/// [outline]
+/// diagonal = "split"
+/// remove-overlaps = true
+/// shape = { kind = "rounded", radius = 0.3 }
+///
+/// [outline.styles]
+/// Dot = { kind = "dot", size = 0.8 }
+/// Scanline = { kind = "scanline" }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct OutlineSettings {
+    /// How pixels touching only by their corners are traced, `join` by default.
+    #[serde(default)]
+    pub diagonal: DiagonalRule,
+    /// Merge contours of differently colored pixels into one, off by default.
+    #[serde(default)]
+    pub remove_overlaps: bool,
+    /// Look of pixels in the regular font, hard squares by default.
+    #[serde(default)]
+    pub shape: PixelShape,
+    /// Extra sub-families built from the same glyphs, keyed by sub-family name.
+    #[serde(default)]
+    pub styles: BTreeMap<String, PixelShape>,
+}
+
+/// Sub-families synthesized from the drawn bitmaps, from the `[synthesis]` section.
+///
+/// ```toml
+/// [synthesis]
+/// bold = 1
+/// oblique = 4
+/// ```
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct SynthesisSettings {
+    /// Pixels added to the right of strokes for the Bold sub-family, which must not be zero.
+    pub bold: Option<NonZeroUsize>,
+    /// Rows per one pixel of shear for the Oblique sub-family, which must not be zero.
+    pub oblique: Option<NonZeroUsize>,
+}
+
+impl SynthesisSettings {
+    /// Synthesized styles with their sub-family names, Regular not included.
+    /// Bold Oblique is made only if both are enabled.
+    pub fn styles(&self) -> Vec<(&'static str, FontStyle)> {
+        let mut styles = Vec::new();
+        if let Some(embolden) = self.bold {
+            styles.push((
+                "Bold",
+                FontStyle {
+                    embolden: embolden.get(),
+                    oblique: None,
+                },
+            ));
+        }
+        if let Some(step) = self.oblique {
+            styles.push((
+                "Oblique",
+                FontStyle {
+                    embolden: 0,
+                    oblique: Some(step.get()),
+                },
+            ));
+        }
+        if let (Some(embolden), Some(step)) = (self.bold, self.oblique) {
+            styles.push((
+                "Bold Oblique",
+                FontStyle {
+                    embolden: embolden.get(),
+                    oblique: Some(step.get()),
+                },
+            ));
+        }
+        styles
+    }
+}
+
+/// Build settings, from the `[build]` section.
+///
+/// ```toml
+/// [build]
+/// source-date = 2024-05-01
+/// file-name = "{family}-{subfamily}-{version}"
+///
+/// [[build.target]]
+/// format = "ttf"
+///
+/// [[build.target]]
+/// format = "ttf"
+/// file-name = "{family}-{subfamily}-split"
+/// diagonal = "split"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BuildSettings {
+    /// Date written into fonts as their creation and modification time,
+    /// either a date in UTC or a date-time with offset.
+    #[serde(default, deserialize_with = "deserialize_source_date")]
+    pub source_date: Option<Timestamp>,
+    /// Output file names without extension, see [`FileNameTemplate`].
+    #[serde(default)]
+    pub file_name: FileNameTemplate,
+    /// Artifacts to build, a single `ttf` target if empty.
+    #[serde(default, rename = "target")]
+    pub targets: Vec<BuildTarget>,
+}
+
+/// An artifact to build, from a `[[build.target]]` entry.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BuildTarget {
+    /// Name of the backend in [`BackendRegistry`](crate::BackendRegistry), like `ttf`.
+    pub format: String,
+    /// Output file names, replacing `file-name` of `[build]`.
+    #[serde(default)]
+    pub file_name: Option<FileNameTemplate>,
+    /// The rest of the entry, which the backend interprets.
+    #[serde(flatten)]
+    pub options: toml::Table,
+}
+
+impl BuildTarget {
+    pub fn new(format: impl Into<String>) -> BuildTarget {
+        BuildTarget {
+            format: format.into(),
+            file_name: None,
+            options: toml::Table::new(),
+        }
+    }
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(display("SOURCE_DATE_EPOCH must be seconds since the Unix epoch but got `{value}`"))]
+pub struct SourceDateEpochError {
+    value: String,
+}
+
+impl BuildSettings {
+    /// Time to write into fonts, which is `SOURCE_DATE_EPOCH` if set, or `source-date` otherwise.
+    /// `None` means the time of the build, so the output is not reproducible.
+    pub fn timestamp(&self) -> Result<Option<Timestamp>, SourceDateEpochError> {
+        let Some(value) = env::var_os("SOURCE_DATE_EPOCH") else {
+            return Ok(self.source_date);
+        };
+        let value = value.to_string_lossy();
+        value
+            .trim()
+            .parse()
+            .ok()
+            .and_then(|seconds| Timestamp::from_second(seconds).ok())
+            .map(Some)
+            .context(SourceDateEpochSnafu { value })
+    }
+}
+
+fn deserialize_source_date<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Timestamp>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum DateValue {
+        Datetime(toml::value::Datetime),
+        String(String),
+    }
+    let value = match DateValue::deserialize(deserializer)? {
+        DateValue::Datetime(datetime) => datetime.to_string(),
+        DateValue::String(value) => value,
+    };
+    let timestamp = match value.parse::<Timestamp>() {
+        Ok(timestamp) => timestamp,
+        // a date-time without offset parses as a date as well, dropping its time.
+        Err(_) => value
+            .parse::<Date>()
+            .ok()
+            .filter(|date| date.to_string() == value)
+            .and_then(|date| date.to_zoned(TimeZone::UTC).ok())
+            .map(|zoned| zoned.timestamp())
+            .ok_or_else(|| {
+                serde::de::Error::custom(format!(
+                    "expect a date or a date-time with offset but got `{value}`"
+                ))
+            })?,
+    };
+    Ok(Some(timestamp))
+}
+
+/// Writes `files` of a project, keyed by their path from the project directory,
+/// into a directory removed once dropped.
+#[cfg(test)]
+pub(crate) fn temp_project(files: &[(&str, &str)]) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    for (name, content) in files {
+        let path = dir.path().join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_zero_synthesis() {
+        for synthesis in ["bold = 0", "oblique = 0"] {
+            let manifest = format!("[synthesis]\n{synthesis}\n");
+            assert!(toml::from_str::<ProjectManifest>(&manifest).is_err());
+        }
+        let manifest: ProjectManifest =
+            toml::from_str("[synthesis]\nbold = 1\noblique = 4\n").unwrap();
+        let names: Vec<_> = manifest
+            .synthesis
+            .styles()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["Bold", "Oblique", "Bold Oblique"]);
+    }
+
+    #[test]
+    fn first_definition_in_load_order_wins() {
+        let dir = temp_project(&[
+            ("project.toml", ""),
+            ("src/a.yaff", "'A':\n  @.\n  @.\n"),
+            (
+                "src/b.yaff",
+                "# the later 'A'\n'A':\n  .@\n  .@\n\n'B':\n  @@\n  @@\n",
+            ),
+        ]);
+        let (project, diagnostics) = Project::load(dir.path());
+        let [duplicate] = &diagnostics[..] else {
+            panic!("expect a single warning but got {diagnostics:?}");
+        };
+        assert_eq!(duplicate.code, DiagnosticCode::DuplicateGlyph);
+        assert!(duplicate.path.ends_with("b.yaff"));
+        let span = duplicate.span.as_ref().unwrap();
+        assert_eq!((span.line, span.column), (2, 1));
+
+        let font =
+            crate::font::Font::from_project(&project, "Regular", FontStyle::default()).unwrap();
+        assert_eq!(font.glyphs.len(), 2, "the later 'A' is dropped");
+        let a = font.glyph(font.cmap[&'A']).unwrap();
+        assert!(a.is_inked(0, 0) && !a.is_inked(0, 1));
+        let provenance = project.find_glyph('A').unwrap().to_string();
+        assert_eq!(provenance, "src/a.yaff:1:1");
+    }
+
+    #[test]
+    fn loads_files_in_walk_order() {
+        let sources: Vec<_> = (0..40)
+            .map(|idx| {
+                let name = format!("src/{idx:02}.yaff");
+                (name, format!("\"g{idx}\":\n  @\n\n'A':\n  @\n"))
+            })
+            .collect();
+        let mut files = vec![("project.toml", "")];
+        files.extend(sources.iter().map(|(name, source)| (&**name, &**source)));
+        let dir = temp_project(&files);
+
+        let (project, diagnostics) = Project::load(dir.path());
+        let tags: Vec<_> = project
+            .list_glyph()
+            .filter_map(|glyph| match &glyph.labels[0] {
+                yaff::GlyphLabel::Tag(tag) => Some(tag.clone()),
+                _ => None,
+            })
+            .collect();
+        let expected: Vec<_> = (0..40).map(|idx| format!("g{idx}")).collect();
+        assert_eq!(tags, expected);
+        let duplicates: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.path.file_name().unwrap().to_string_lossy())
+            .collect();
+        let expected: Vec<_> = (1..40).map(|idx| format!("{idx:02}.yaff")).collect();
+        assert_eq!(duplicates, expected, "'A' is kept from 00.yaff");
+        assert!(project
+            .find_glyph('A')
+            .unwrap()
+            .to_string()
+            .contains("00.yaff"));
+    }
+
+    #[test]
+    fn drops_broken_derive_rules_alone() {
+        let manifest = "[project]\nheight = 2\n\n[derive]\n\
+                        ')' = \"mirror-h('(')\"\n\
+                        'x' = \"spin('(')\"\n\
+                        'y' = \"'z'\"\n";
+        let dir = temp_project(&[
+            ("project.toml", manifest),
+            ("src/a.yaff", "'(':\n  .@\n  @.\n"),
+        ]);
+        let (project, diagnostics) = Project::load(dir.path());
+        assert_eq!(project.manifest.project.height, Some(2));
+        assert_eq!(project.manifest.derive.len(), 2, "only `x` is dropped");
+        assert!(project.find_glyph(')').is_some());
+
+        let lines: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                assert_eq!(diagnostic.code, DiagnosticCode::InvalidDeriveRule);
+                diagnostic.span.as_ref().unwrap().line
+            })
+            .collect();
+        assert_eq!(lines, [6, 7], "{diagnostics:?}");
+    }
+
+    #[test]
+    fn reads_source_dates() {
+        let source_date = |value: &str| {
+            toml::from_str::<BuildSettings>(&format!("source-date = {value}"))
+                .map(|settings| settings.source_date.unwrap().as_second())
+        };
+        assert_eq!(source_date("2024-05-01").unwrap(), 1_714_521_600);
+        assert_eq!(source_date("\"2024-05-01\"").unwrap(), 1_714_521_600);
+        assert_eq!(
+            source_date("2024-05-01T09:00:00+09:00").unwrap(),
+            1_714_521_600
+        );
+        assert!(source_date("2024-05-01T09:00:00").is_err(), "no offset");
+        assert!(source_date("\"tomorrow\"").is_err());
+        assert!(toml::from_str::<BuildSettings>("")
+            .unwrap()
+            .source_date
+            .is_none());
+    }
+}
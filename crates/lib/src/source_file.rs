@@ -1,36 +1,163 @@
 use std::{
+    collections::HashMap,
     fs, io,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 
 use snafu::prelude::*;
-use yaff::{parse_document, Document};
+use yaff::{parse_document, Document, SemanticGlyphLabel};
+
+use crate::diagnostic::{Diagnostic, DiagnosticCode, LineIndex, Span};
 
 pub struct SourceFile {
+    pub path: PathBuf,
+    /// Path relative to the project root, like `src/blocks/Basic Latin/letters.yaff`.
+    pub relative_path: PathBuf,
+    /// Name of the `src/blocks/<Block>` directory the file is filed under.
+    pub block: Option<String>,
+    /// Position of the file in the load order of its project.
+    pub order: usize,
     pub document: Document,
+    /// Where each label is defined, resolved once so the content need not be kept.
+    glyph_spans: HashMap<SemanticGlyphLabel, Span>,
 }
 
 #[derive(Debug, Snafu)]
 pub enum SourceFileLoadError {
-    #[snafu(transparent)]
-    Io { source: io::Error },
+    #[snafu(display("failed to read {path}", path = path.to_string_lossy()))]
+    Io { path: PathBuf, source: io::Error },
     #[snafu(display("failed to parse {path}", path = path.to_string_lossy()))]
     Yaff {
         path: PathBuf,
-        source: yaff::YaffParseError,
+        span: Span,
+        source: Box<yaff::YaffParseError>,
     },
 }
 
+impl SourceFileLoadError {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            SourceFileLoadError::Io { path, source } => {
+                Diagnostic::error(DiagnosticCode::Io, path, None, source)
+            }
+            SourceFileLoadError::Yaff { path, span, source } => Diagnostic::error(
+                DiagnosticCode::InvalidYaff,
+                path,
+                Some(span.clone()),
+                &source.msg,
+            ),
+        }
+    }
+}
+
 impl SourceFile {
-    pub fn load(path: impl AsRef<Path>) -> Result<SourceFile, SourceFileLoadError> {
+    pub fn load(
+        project_root: impl AsRef<Path>,
+        path: impl AsRef<Path>,
+        order: usize,
+    ) -> Result<SourceFile, SourceFileLoadError> {
         let path = path.as_ref();
-        let content = fs::read_to_string(path)?;
+        let content = fs::read_to_string(path).context(IoSnafu { path })?;
         let document =
             parse_document(&mut content.as_ref()).map_err(|source| SourceFileLoadError::Yaff {
                 path: path.to_owned(),
-                source,
+                span: Span::new(&content, source.offset..source.offset),
+                source: Box::new(source),
             })?;
 
-        Ok(SourceFile { document })
+        let relative_path = path.strip_prefix(project_root).unwrap_or(path).to_owned();
+        let block = relative_path
+            .strip_prefix(Path::new("src").join("blocks"))
+            .ok()
+            .and_then(|path| match path.components().collect::<Vec<_>>()[..] {
+                [Component::Normal(block), _, ..] => Some(block.to_string_lossy().into_owned()),
+                _ => None,
+            });
+        let index = LineIndex::new(&content);
+        let glyph_spans = document
+            .list_glyph()
+            .flat_map(|glyph| glyph.labels.iter().flat_map(|label| label.to_semantic()))
+            .filter_map(|label| {
+                let range = document.get_glyph_span(&label)?;
+                Some((label, index.span(range)))
+            })
+            .collect();
+
+        Ok(SourceFile {
+            path: path.to_owned(),
+            relative_path,
+            block,
+            order,
+            document,
+            glyph_spans,
+        })
+    }
+
+    /// Returns where the glyph labelled `label` is defined in this file.
+    pub fn glyph_span(&self, label: &SemanticGlyphLabel) -> Option<Span> {
+        self.glyph_spans.get(label).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::project::temp_project;
+
+    use super::*;
+
+    #[test]
+    fn files_sources_under_blocks() {
+        let dir = temp_project(&[
+            ("src/blocks/Basic Latin/letters.yaff", ""),
+            ("src/blocks/Basic Latin/extra/digits.yaff", ""),
+            ("src/blocks/loose.yaff", ""),
+            ("src/other.yaff", ""),
+        ]);
+        let block = |path: &str| {
+            SourceFile::load(dir.path(), dir.path().join(path), 0)
+                .unwrap()
+                .block
+        };
+        assert_eq!(
+            block("src/blocks/Basic Latin/letters.yaff").as_deref(),
+            Some("Basic Latin")
+        );
+        assert_eq!(
+            block("src/blocks/Basic Latin/extra/digits.yaff").as_deref(),
+            Some("Basic Latin")
+        );
+        assert_eq!(block("src/blocks/loose.yaff"), None);
+        assert_eq!(block("src/other.yaff"), None);
+    }
+
+    #[test]
+    fn locates_glyphs() {
+        let dir = temp_project(&[("src/a.yaff", "# comment\n'A':\n  @\n\n'B':\n  @\n")]);
+        let file = SourceFile::load(dir.path(), dir.path().join("src/a.yaff"), 3).unwrap();
+        assert_eq!(file.relative_path, Path::new("src/a.yaff"));
+        assert_eq!(file.order, 3);
+        let line = |ch| {
+            file.glyph_span(&SemanticGlyphLabel::CharSequence(vec![ch]))
+                .map(|span| span.line)
+        };
+        assert_eq!(line('A'), Some(2));
+        assert_eq!(line('B'), Some(5));
+        assert_eq!(line('C'), None);
+    }
+
+    #[test]
+    fn reports_broken_sources() {
+        let dir = temp_project(&[("src/a.yaff", "'A':\n  @\n\n'B'\n")]);
+        let error = SourceFile::load(dir.path(), dir.path().join("src/a.yaff"), 0)
+            .err()
+            .unwrap();
+        let diagnostic = error.to_diagnostic();
+        assert_eq!(diagnostic.code, DiagnosticCode::InvalidYaff);
+        assert_eq!(diagnostic.span.unwrap().line, 4);
+
+        let error = SourceFile::load(dir.path(), dir.path().join("src/b.yaff"), 0)
+            .err()
+            .unwrap();
+        assert_eq!(error.to_diagnostic().code, DiagnosticCode::Io);
     }
 }
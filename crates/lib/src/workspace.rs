@@ -1,37 +1,211 @@
+use glob::{MatchOptions, Pattern};
+use rayon::prelude::*;
 use serde::Deserialize;
-use snafu::prelude::*;
-use std::{fs, io, path::Path};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
 
-use crate::{Project, ProjectLoadError};
+use crate::{
+    diagnostic::{Diagnostic, DiagnosticCode, Span},
+    Project,
+};
 
 pub struct Workspace {
+    pub path: PathBuf,
     pub projects: Vec<Project>,
 }
 
-#[derive(Debug, Snafu)]
-pub enum WorkspaceLoadError {
-    #[snafu(transparent)]
-    Io { source: io::Error },
-    #[snafu(transparent)]
-    De { source: toml::de::Error },
-    #[snafu(transparent)]
-    Project { source: ProjectLoadError },
-}
-
 impl Workspace {
-    pub fn load(path: impl AsRef<Path>) -> Result<Workspace, WorkspaceLoadError> {
+    /// Loads the workspace at `path` together with every problem found on the way.
+    ///
+    /// Loading continues past broken files, so a half-broken workspace can still be opened.
+    /// If `workspace.toml` itself cannot be read, the workspace has no project.
+    pub fn load(path: impl AsRef<Path>) -> (Workspace, Vec<Diagnostic>) {
         let path = path.as_ref();
-        let config: WorkspaceManifest =
-            toml::from_str(&fs::read_to_string(path.join("workspace.toml"))?)?;
-        let projects = config
-            .workspace
-            .members
-            .iter()
-            .map(|subpath| Project::load(path.join(subpath)))
-            .collect::<Result<_, _>>()?;
+        let mut diagnostics = Vec::new();
+
+        let manifest_path = path.join("workspace.toml");
+        let config = match fs::read_to_string(&manifest_path) {
+            Ok(content) => match toml::from_str::<WorkspaceManifest>(&content) {
+                Ok(config) => Some(config),
+                Err(e) => {
+                    diagnostics.push(Diagnostic::error(
+                        DiagnosticCode::InvalidWorkspaceManifest,
+                        &manifest_path,
+                        e.span().map(|span| Span::new(&content, span)),
+                        e.message(),
+                    ));
+                    None
+                }
+            },
+            Err(e) => {
+                diagnostics.push(Diagnostic::error(
+                    DiagnosticCode::Io,
+                    &manifest_path,
+                    None,
+                    e,
+                ));
+                None
+            }
+        };
+
+        let mut projects = Vec::new();
+        if let Some(WorkspaceManifest { workspace: section }) = &config {
+            let overrides: Vec<_> = section
+                .overrides
+                .iter()
+                .filter_map(|(pattern, overrides)| {
+                    compile_pattern(pattern, &manifest_path, &mut diagnostics)
+                        .map(|pattern| (pattern, overrides))
+                })
+                .collect();
+            let members = expand_members(path, section, &manifest_path, &mut diagnostics);
+            let loaded: Vec<_> = members
+                .par_iter()
+                .map(|member| Project::load(path.join(member)))
+                .collect();
+            for (member, (mut project, project_diagnostics)) in members.iter().zip(loaded) {
+                diagnostics.extend(project_diagnostics);
+                for (pattern, overrides) in &overrides {
+                    if pattern.matches_path(member) {
+                        project.overrides.merge(overrides);
+                    }
+                }
+                projects.push(project);
+            }
+        }
+
+        (
+            Workspace {
+                path: path.to_owned(),
+                projects,
+            },
+            diagnostics,
+        )
+    }
+
+    /// Searches `start` and its ancestors for the nearest directory containing `workspace.toml`.
+    pub fn discover(start: impl AsRef<Path>) -> Option<PathBuf> {
+        start
+            .as_ref()
+            .ancestors()
+            .find(|dir| dir.join("workspace.toml").is_file())
+            .map(Path::to_owned)
+    }
+}
 
-        Ok(Workspace { projects })
+fn compile_pattern(
+    pattern: &str,
+    manifest_path: &Path,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<Pattern> {
+    Pattern::new(pattern.trim_start_matches("./"))
+        .map_err(|e| {
+            diagnostics.push(Diagnostic::error(
+                DiagnosticCode::InvalidMemberPattern,
+                manifest_path,
+                None,
+                format!("invalid pattern `{pattern}`: {e}"),
+            ))
+        })
+        .ok()
+}
+
+/// Expands `members` into member directories relative to `root`, leaving out `exclude`d ones.
+/// A member without `project.toml` is reported and skipped.
+fn expand_members(
+    root: &Path,
+    section: &WorkspaceSection,
+    manifest_path: &Path,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<PathBuf> {
+    let excludes: Vec<_> = section
+        .exclude
+        .iter()
+        .filter_map(|pattern| compile_pattern(pattern, manifest_path, diagnostics))
+        .collect();
+
+    let mut members = Vec::new();
+    for member in &section.members {
+        let Some(pattern) = compile_pattern(member, manifest_path, diagnostics) else {
+            continue;
+        };
+        let is_literal = Pattern::escape(pattern.as_str()) == pattern.as_str();
+        let full_pattern = format!(
+            "{}/{}",
+            Pattern::escape(&root.to_string_lossy()),
+            pattern.as_str()
+        );
+        let options = MatchOptions {
+            require_literal_leading_dot: true,
+            ..MatchOptions::new()
+        };
+        let paths = match glob::glob_with(&full_pattern, options) {
+            Ok(paths) => paths,
+            Err(e) => {
+                diagnostics.push(Diagnostic::error(
+                    DiagnosticCode::InvalidMemberPattern,
+                    manifest_path,
+                    None,
+                    format!("invalid pattern `{member}`: {e}"),
+                ));
+                continue;
+            }
+        };
+
+        let mut matched = false;
+        for entry in paths {
+            let dir = match entry {
+                Ok(dir) => dir,
+                Err(e) => {
+                    diagnostics.push(Diagnostic::error(
+                        DiagnosticCode::Io,
+                        e.path(),
+                        None,
+                        e.error(),
+                    ));
+                    continue;
+                }
+            };
+            if !dir.is_dir() {
+                continue;
+            }
+            matched = true;
+            let relative = dir.strip_prefix(root).unwrap_or(&dir).to_owned();
+            if excludes
+                .iter()
+                .any(|exclude| exclude.matches_path(&relative))
+                || members.contains(&relative)
+            {
+                continue;
+            }
+            if !dir.join("project.toml").is_file() {
+                diagnostics.push(Diagnostic::error(
+                    DiagnosticCode::MissingProjectManifest,
+                    manifest_path,
+                    None,
+                    format!(
+                        "workspace member `{}` has no project.toml",
+                        relative.to_string_lossy()
+                    ),
+                ));
+                continue;
+            }
+            members.push(relative);
+        }
+
+        if !matched && is_literal {
+            diagnostics.push(Diagnostic::error(
+                DiagnosticCode::MissingProjectManifest,
+                manifest_path,
+                None,
+                format!("workspace member `{member}` does not exist"),
+            ));
+        }
     }
+    members
 }
 
 #[derive(Deserialize)]
@@ -41,5 +215,178 @@ pub struct WorkspaceManifest {
 
 #[derive(Deserialize)]
 pub struct WorkspaceSection {
+    /// Member directories, glob patterns like `fonts/*` are allowed.
     pub members: Vec<String>,
+    /// Glob patterns of directories to leave out from `members`.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Build overrides for members matching the key pattern, applied in key order.
+    ///
+    /// ```toml
+    /// [workspace.overrides."fonts/bitkodi-*"]
+    /// formats = ["ttf"]
+    /// ```
+    #[serde(default)]
+    pub overrides: BTreeMap<String, BuildOverrides>,
+}
+
+/// Build settings a workspace imposes on its members.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BuildOverrides {
+    /// Output formats to produce, overriding the ones of the project.
+    pub formats: Option<Vec<String>>,
+}
+
+impl BuildOverrides {
+    pub fn merge(&mut self, other: &BuildOverrides) {
+        if let Some(formats) = &other.formats {
+            self.formats = Some(formats.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::project::temp_project;
+
+    use super::*;
+
+    const GLYPH: &str = "'A':\n  @\n";
+
+    fn member_paths(workspace: &Workspace) -> Vec<PathBuf> {
+        workspace
+            .projects
+            .iter()
+            .map(|project| {
+                project
+                    .path
+                    .strip_prefix(&workspace.path)
+                    .unwrap()
+                    .to_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn expands_globs_and_leaves_out_excluded_members() {
+        let manifest = r#"
+[workspace]
+members = ["./fonts/*", "extra", "fonts/a"]
+exclude = ["fonts/b*"]
+
+[workspace.overrides."fonts/*"]
+formats = ["bdf"]
+
+[workspace.overrides."fonts/c"]
+formats = ["ttf"]
+"#;
+        let dir = temp_project(&[
+            ("workspace.toml", manifest),
+            ("fonts/a/project.toml", ""),
+            ("fonts/a/src/a.yaff", GLYPH),
+            ("fonts/b1/project.toml", ""),
+            ("fonts/c/project.toml", ""),
+            ("fonts/c/src/a.yaff", GLYPH),
+            ("fonts/.hidden/project.toml", ""),
+            ("fonts/notes.txt", ""),
+            ("extra/project.toml", ""),
+            ("extra/src/a.yaff", GLYPH),
+        ]);
+        let (workspace, diagnostics) = Workspace::load(dir.path());
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        assert_eq!(
+            member_paths(&workspace),
+            [
+                Path::new("fonts/a"),
+                Path::new("fonts/c"),
+                Path::new("extra")
+            ]
+        );
+        let formats: Vec<_> = workspace
+            .projects
+            .iter()
+            .map(|project| project.overrides.formats.clone())
+            .collect();
+        assert_eq!(
+            formats,
+            [
+                Some(vec!["bdf".to_owned()]),
+                Some(vec!["ttf".to_owned()]),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_broken_members() {
+        let manifest = r#"
+[workspace]
+members = ["fonts/*", "missing", "[", "empty/*"]
+exclude = ["["]
+"#;
+        let dir = temp_project(&[
+            ("workspace.toml", manifest),
+            ("fonts/a/project.toml", ""),
+            ("fonts/a/src/a.yaff", GLYPH),
+            ("fonts/b/readme.txt", ""),
+        ]);
+        let (workspace, diagnostics) = Workspace::load(dir.path());
+        assert_eq!(member_paths(&workspace), [Path::new("fonts/a")]);
+        let messages: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.code, diagnostic.message.as_str()))
+            .collect();
+        assert_eq!(messages.len(), 4, "{messages:?}");
+        assert_eq!(messages[0].0, DiagnosticCode::InvalidMemberPattern);
+        assert_eq!(
+            messages[1],
+            (
+                DiagnosticCode::MissingProjectManifest,
+                "workspace member `fonts/b` has no project.toml"
+            )
+        );
+        assert_eq!(
+            messages[2],
+            (
+                DiagnosticCode::MissingProjectManifest,
+                "workspace member `missing` does not exist"
+            )
+        );
+        assert_eq!(messages[3].0, DiagnosticCode::InvalidMemberPattern);
+    }
+
+    #[test]
+    fn reports_broken_manifests() {
+        let dir = temp_project(&[("workspace.toml", "[workspace]\nmembers = 1\n")]);
+        let (workspace, diagnostics) = Workspace::load(dir.path());
+        assert!(workspace.projects.is_empty());
+        let [diagnostic] = &diagnostics[..] else {
+            panic!("expect a single error but got {diagnostics:?}");
+        };
+        assert_eq!(diagnostic.code, DiagnosticCode::InvalidWorkspaceManifest);
+        assert_eq!(diagnostic.span.as_ref().unwrap().line, 2);
+
+        let (_, diagnostics) = Workspace::load(dir.path().join("nowhere"));
+        assert_eq!(diagnostics[0].code, DiagnosticCode::Io);
+    }
+
+    #[test]
+    fn discovers_the_nearest_workspace() {
+        let dir = temp_project(&[
+            ("workspace.toml", ""),
+            ("fonts/a/workspace.toml", ""),
+            ("fonts/a/src/glyphs/.keep", ""),
+            ("fonts/b/src/.keep", ""),
+        ]);
+        let root = dir.path();
+        assert_eq!(
+            Workspace::discover(root.join("fonts/a/src/glyphs")),
+            Some(root.join("fonts/a"))
+        );
+        assert_eq!(
+            Workspace::discover(root.join("fonts/b/src")),
+            Some(root.to_owned())
+        );
+    }
 }
@@ -1,13 +1,27 @@
 use core::fmt;
 use snafu::prelude::*;
-use std::{collections::HashMap, num::TryFromIntError};
+use std::{
+    collections::{BTreeSet, HashMap},
+    num::TryFromIntError,
+    ops::Range,
+};
 
 pub struct Document {
     elements: Vec<BlockElement>,
+    /// Byte range of each element in the source, empty if the document is not parsed from a source.
+    spans: Vec<Range<usize>>,
     glyph_lut: HashMap<SemanticGlyphLabel, usize>,
 }
 
 impl Document {
+    pub fn with_spans(elements: Vec<(BlockElement, Range<usize>)>) -> Document {
+        let (elements, spans) = elements.into_iter().unzip();
+        Document {
+            spans,
+            ..Document::new(elements)
+        }
+    }
+
     pub fn new(elements: Vec<BlockElement>) -> Document {
         let mut glyph_lut = HashMap::new();
         for (idx, e) in elements.iter().enumerate() {
@@ -24,6 +38,7 @@ impl Document {
         }
         Document {
             elements,
+            spans: Vec::new(),
             glyph_lut,
         }
     }
@@ -36,9 +51,17 @@ impl Document {
         }
     }
 
+    /// Returns the byte range of the glyph definition in the source it is parsed from.
+    pub fn get_glyph_span(&self, label: &SemanticGlyphLabel) -> Option<Range<usize>> {
+        let idx = self.glyph_lut.get(label)?;
+        self.spans.get(*idx).cloned()
+    }
+
+    /// Lists glyphs in the order they are defined, each once even if it has several labels.
+    /// A glyph whose labels are all redefined later is left out.
     pub fn list_glyph(&self) -> impl Iterator<Item = &GlyphDefinition> {
-        self.glyph_lut
-            .values()
+        BTreeSet::from_iter(self.glyph_lut.values())
+            .into_iter()
             .flat_map(|idx| match &self.elements.get(*idx) {
                 Some(BlockElement::GlyphDefinition(def)) => Some(def),
                 _ => None,
@@ -46,6 +69,23 @@ impl Document {
     }
 }
 
+/// Writes the document back as YAFF, which parses into the same elements.
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for element in &self.elements {
+            match element {
+                BlockElement::Comment(comment) => writeln!(f, "# {}", comment.0)?,
+                BlockElement::Whitespace(whitespace) => f.write_str(whitespace)?,
+                BlockElement::Property(property) => {
+                    writeln!(f, "{}: {}", property.key, property.value)?
+                }
+                BlockElement::GlyphDefinition(glyph) => write!(f, "{glyph}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub enum BlockElement {
     Comment(Comment),
@@ -70,6 +110,41 @@ pub struct GlyphDefinition {
     pub value: Option<GlyphValue>,
 }
 
+/// Writes the labels and the value without the line break after the last row,
+/// which belongs to the whitespace following the definition.
+impl fmt::Display for GlyphDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let indent = if self.indent.is_empty() {
+            "  "
+        } else {
+            &self.indent
+        };
+        for label in &self.labels {
+            writeln!(f, "{label}:")?;
+        }
+        let Some(value) = &self.value else {
+            return write!(f, "{indent}-");
+        };
+        for (idx, row) in value.data.iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+            f.write_str(indent)?;
+            for (idx, color) in row.iter().enumerate() {
+                if idx > 0 {
+                    f.write_str(" ")?;
+                }
+                match color {
+                    Some(GlyphPaletteColor::Zero) => f.write_str("@")?,
+                    Some(color) => write!(f, "{:X}", color.value())?,
+                    None => f.write_str(".")?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 /// It is generally means 4-bit colors described as following table:
 ///
 /// |   ID | Color   |   ID | Color          |
@@ -240,7 +315,37 @@ impl GlyphLabel {
     }
 }
 
-#[derive(PartialEq, Eq, Hash)]
+impl fmt::Display for GlyphLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // characters which would be ambiguous or invisible between quotes are written as `u+XXXX`.
+        fn is_quotable(ch: char) -> bool {
+            ch == ' ' || !(ch == '\'' || ch.is_control() || ch.is_whitespace())
+        }
+        match self {
+            GlyphLabel::CodepointSingle(codepoint) => write!(f, "0x{codepoint:02x}"),
+            GlyphLabel::CodepointSequence(vec) => f.write_str(
+                &vec.iter()
+                    .map(|codepoint| format!("0x{codepoint:02x}"))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            GlyphLabel::CharacterSingle(ch) if is_quotable(*ch) => write!(f, "'{ch}'"),
+            GlyphLabel::CharacterSequence(vec) if vec.iter().all(|ch| is_quotable(*ch)) => {
+                write!(f, "'{}'", String::from_iter(vec))
+            }
+            GlyphLabel::CharacterSingle(ch) => write!(f, "u+{:04x}", *ch as u32),
+            GlyphLabel::CharacterSequence(vec) => f.write_str(
+                &vec.iter()
+                    .map(|ch| format!("u+{:04x}", *ch as u32))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            GlyphLabel::Tag(tag) => write!(f, "\"{tag}\""),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub enum SemanticGlyphLabel {
     CharSequence(Vec<char>),
     Tag(String),
@@ -1,8 +1,10 @@
+use std::ops::Range;
+
 use snafu::prelude::*;
 use winnow::{
     combinator::{alt, opt, repeat},
     error::ContextError,
-    seq, Parser,
+    seq, PResult, Parser,
 };
 
 use crate::{BlockElement, Document};
@@ -23,10 +25,11 @@ pub struct YaffParseError {
 }
 
 pub fn parse_document(input: &mut &str) -> Result<Document, YaffParseError> {
-    seq!(Document::new(
+    let total = input.len();
+    seq!(Document::with_spans(
         // A byte-order mark (u+FEFF) may be included at the start of the file.
         _: opt('\u{FEFF}'),
-        repeat(0.., alt((
+        repeat(0.., spanned(total, alt((
             parse_glyph_definition.map(BlockElement::GlyphDefinition),
             parse_property.map(BlockElement::Property),
             parse_comment.map(BlockElement::Comment),
@@ -37,7 +40,7 @@ pub fn parse_document(input: &mut &str) -> Result<Document, YaffParseError> {
                     parse_line_terminator.verify_map(|opt| opt),
                 ))
             ).map(|acc: Vec<_>| acc.join("")).verify(|s: &String| !s.is_empty()).map(BlockElement::Whitespace),
-        )))
+        ))))
     ))
     .parse(input)
     .map_err(|e| YaffParseError {
@@ -46,3 +49,15 @@ pub fn parse_document(input: &mut &str) -> Result<Document, YaffParseError> {
         origin: e.into_inner(),
     })
 }
+
+/// Attaches the byte range of the parsed input, counted from the start of the document.
+fn spanned<'i, O>(
+    total: usize,
+    mut parser: impl Parser<&'i str, O, ContextError>,
+) -> impl FnMut(&mut &'i str) -> PResult<(O, Range<usize>)> {
+    move |input| {
+        let start = total - input.len();
+        let output = parser.parse_next(input)?;
+        Ok((output, start..total - input.len()))
+    }
+}
@@ -108,7 +108,7 @@ fn parse_tag_label(input: &mut &str) -> PResult<GlyphLabel> {
         )
         .map(|acc: Vec<_>| acc.join("\"")),
     )
-    .map(|_| todo!())
+    .map(GlyphLabel::Tag)
     .parse_next(input)
 }
 
@@ -132,7 +132,7 @@ fn create_glyph_value_parser<'a>(
 fn parse_glyph_row(input: &mut &str) -> PResult<Vec<Option<GlyphPaletteColor>>> {
     repeat(
         1..,
-        preceded(opt(' '), any.try_map(|ch| GlyphPaletteColor::try_from(ch))),
+        preceded(opt(' '), any.try_map(GlyphPaletteColor::try_from)),
     )
     .parse_next(input)
 }